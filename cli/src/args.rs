@@ -0,0 +1,385 @@
+use std::path::PathBuf;
+
+use chip8_core::{Chip8Quirks, Rgb};
+use clap::{builder::RangedU64ValueParser, ArgGroup, Parser, ValueEnum};
+use serde::Deserialize;
+
+use crate::{
+    condition::Breakpoint,
+    config::QuirksConfig,
+    headless,
+    keymap::{parse_keymap, KeyMap},
+    renderer::Renderer,
+    watchdog,
+};
+
+// ~660 instructions a second at 60 frames a second, close to the original interpreters
+pub const DEFAULT_SPEED: usize = 11;
+pub const DEFAULT_BEEP_FREQ: u32 = 440;
+
+#[derive(Parser, Debug, Clone, PartialEq, Eq)]
+#[command(version, about = "Run a CHIP-8 ROM in the terminal")]
+#[command(group(ArgGroup::new("break_mode").args(["debug", "headless"]).multiple(true)))]
+pub struct Args {
+    /// ROM file to run, leave it out to pick one from a list
+    pub rom: Option<PathBuf>,
+
+    /// Also list the ROMs in this directory when picking one, can be repeated
+    #[arg(long, value_name = "DIR", conflicts_with = "rom")]
+    pub rom_dir: Vec<PathBuf>,
+
+    /// Instructions to run per 60Hz frame
+    #[arg(long, default_value_t = DEFAULT_SPEED, value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+    pub speed: usize,
+
+    /// Interpreter whose behaviour to emulate
+    #[arg(long, value_enum, default_value_t = QuirksPreset::Chip8)]
+    pub quirks: QuirksPreset,
+
+    /// Keypad bindings on top of the 1234/QWER/ASDF/ZXCV layout, e.g. "x=0,p=f"
+    #[arg(long, value_parser = parse_keymap, default_value = "", hide_default_value = true)]
+    pub keymap: KeyMap,
+
+    /// Color of lit pixels, as RRGGBB or #RRGGBB
+    #[arg(long, value_parser = parse_color)]
+    pub fg: Option<Rgb>,
+
+    /// Color of unlit pixels, as RRGGBB or #RRGGBB
+    #[arg(long, value_parser = parse_color)]
+    pub bg: Option<Rgb>,
+
+    /// How pixels are drawn: one per cell, two stacked per cell, or 2x4 braille dots per cell
+    #[arg(long, value_enum, default_value_t = Renderer::Halfblock)]
+    pub renderer: Renderer,
+
+    /// Show the 16 keypad keys beside the screen, with the held ones highlighted
+    #[arg(long, conflicts_with_all = ["debug", "headless"])]
+    pub show_keypad: bool,
+
+    /// Show the debugger beside the screen, Enter breaks into it
+    #[arg(long)]
+    pub debug: bool,
+
+    /// Stop at an address and/or when a condition holds, e.g. "0x2A4 when V3 == 0x1F" or
+    /// "when I changes", with --debug or --headless. Can be repeated.
+    #[arg(long = "break", value_name = "BREAKPOINT", value_parser = Breakpoint::parse, requires = "break_mode")]
+    pub breakpoints: Vec<Breakpoint>,
+
+    /// Only run an instruction when Enter is pressed, showing what it changed
+    #[arg(long)]
+    pub step: bool,
+
+    /// Don't play any sound
+    #[arg(long)]
+    pub mute: bool,
+
+    /// Pitch of the tone in Hz, when built with the audio feature
+    #[arg(long, value_name = "HZ", default_value_t = DEFAULT_BEEP_FREQ, value_parser = RangedU64ValueParser::<u32>::new().range(20..=20_000))]
+    pub beep_freq: u32,
+
+    /// Exit with an error once the watchdog thinks the CPU is stuck
+    #[arg(long)]
+    pub watchdog_exit: bool,
+
+    /// Frames between watchdog checks
+    #[arg(long, value_name = "FRAMES", default_value_t = watchdog::DEFAULT_INTERVAL_FRAMES)]
+    pub watchdog_interval: u64,
+
+    /// Stuck checks in a row before the CPU counts as stuck
+    #[arg(long, value_name = "CHECKS", default_value_t = watchdog::DEFAULT_THRESHOLD)]
+    pub watchdog_threshold: u32,
+
+    /// Save every keypad press and release to FILE, with the seed, to replay the run later
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["headless", "step", "replay_input"], requires = "rom")]
+    pub record_input: Option<PathBuf>,
+
+    /// Drive the keypad from a --record-input FILE, the keyboard only pauses and debugs
+    #[arg(long, value_name = "FILE", conflicts_with = "step", requires = "rom")]
+    pub replay_input: Option<PathBuf>,
+
+    /// Run without the terminal UI or keyboard and print the final registers as JSON
+    #[arg(long, conflicts_with_all = ["debug", "step"], requires = "rom")]
+    pub headless: bool,
+
+    /// Instructions to run in headless mode, it stops sooner at 00FD or an error
+    #[arg(long, default_value_t = headless::DEFAULT_CYCLES, requires = "headless")]
+    pub cycles: u64,
+
+    /// Write the final screen as text, in headless mode
+    #[arg(long, value_name = "FILE", requires = "headless")]
+    pub dump_screen: Option<PathBuf>,
+
+    /// Write the final screen as a PPM image, in headless mode
+    #[arg(long, value_name = "FILE", requires = "headless")]
+    pub dump_ppm: Option<PathBuf>,
+
+    /// Stop headless mode once the watchdog thinks the CPU is stuck, e.g. on a jump to itself
+    #[arg(long, requires = "headless")]
+    pub exit_on_halt: bool,
+
+    // Quirks a config file changes from the preset, see config::Config::apply
+    #[arg(skip)]
+    pub quirk_overrides: QuirksConfig,
+}
+
+impl Args {
+    pub fn cpu_quirks(&self) -> Chip8Quirks {
+        return self.quirk_overrides.apply(self.quirks.quirks());
+    }
+}
+
+#[derive(ValueEnum, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum QuirksPreset {
+    Chip8,
+    Schip,
+    Xochip,
+}
+
+impl QuirksPreset {
+    pub fn quirks(self) -> Chip8Quirks {
+        return match self {
+            QuirksPreset::Chip8 => Chip8Quirks::chip8(),
+            QuirksPreset::Schip => Chip8Quirks::schip(),
+            QuirksPreset::Xochip => Chip8Quirks::xochip(),
+        };
+    }
+}
+
+pub fn parse_color(value: &str) -> Result<Rgb, String> {
+    let hex = value.strip_prefix('#').unwrap_or(value);
+    if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!("expected a color like #33FF33, got {}", value));
+    }
+    let channel = |index: usize| u8::from_str_radix(&hex[index..index + 2], 16).unwrap();
+    return Ok(Rgb::new(channel(0), channel(2), channel(4)));
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::error::ErrorKind;
+
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Args, clap::Error> {
+        return Args::try_parse_from(["chip8-cli"].iter().chain(args));
+    }
+
+    #[test]
+    fn defaults() {
+        let args = parse(&["pong.ch8"]).unwrap();
+        assert_eq!(args.rom, Some(PathBuf::from("pong.ch8")));
+        assert!(args.rom_dir.is_empty());
+        assert_eq!(args.speed, DEFAULT_SPEED);
+        assert_eq!(args.quirks.quirks(), Chip8Quirks::default());
+        assert_eq!(args.keymap, KeyMap::default());
+        assert_eq!((args.fg, args.bg), (None, None));
+        assert_eq!(args.renderer, Renderer::Halfblock);
+        assert!(!args.debug && !args.step && !args.mute && !args.watchdog_exit);
+        assert!(!args.show_keypad);
+        assert_eq!(args.beep_freq, DEFAULT_BEEP_FREQ);
+        assert_eq!(args.watchdog_interval, watchdog::DEFAULT_INTERVAL_FRAMES);
+        assert_eq!(args.watchdog_threshold, watchdog::DEFAULT_THRESHOLD);
+        assert!(!args.headless && !args.exit_on_halt);
+        assert_eq!(args.cycles, headless::DEFAULT_CYCLES);
+        assert_eq!((args.dump_screen, args.dump_ppm), (None, None));
+        assert_eq!((args.record_input, args.replay_input), (None, None));
+        assert!(args.breakpoints.is_empty());
+    }
+
+    #[test]
+    fn every_option() {
+        let args = parse(&[
+            "--speed",
+            "20",
+            "--quirks",
+            "schip",
+            "--keymap",
+            "p=f,x=1",
+            "--fg",
+            "#33ff33",
+            "--bg",
+            "0A1A0A",
+            "--renderer",
+            "block",
+            "--debug",
+            "--step",
+            "--mute",
+            "--beep-freq",
+            "880",
+            "--watchdog-exit",
+            "--watchdog-interval",
+            "60",
+            "--watchdog-threshold",
+            "3",
+            "game.ch8",
+        ])
+        .unwrap();
+        assert_eq!(args.speed, 20);
+        assert_eq!(args.quirks.quirks(), Chip8Quirks::schip());
+        assert_eq!(args.keymap, parse_keymap("p=f,x=1").unwrap());
+        assert_eq!(args.fg, Some(Rgb::new(0x33, 0xFF, 0x33)));
+        assert_eq!(args.bg, Some(Rgb::new(0x0A, 0x1A, 0x0A)));
+        assert_eq!(args.renderer, Renderer::Block);
+        assert!(args.debug && args.step && args.mute && args.watchdog_exit);
+        assert_eq!(args.beep_freq, 880);
+        assert_eq!((args.watchdog_interval, args.watchdog_threshold), (60, 3));
+        assert_eq!(
+            parse(&["--quirks", "xochip", "game.ch8"]).unwrap().quirks,
+            QuirksPreset::Xochip
+        );
+    }
+
+    #[test]
+    fn headless_options() {
+        let args = parse(&[
+            "--headless",
+            "--cycles",
+            "50000",
+            "--dump-screen",
+            "out.txt",
+            "--dump-ppm",
+            "out.ppm",
+            "--exit-on-halt",
+            "game.ch8",
+        ])
+        .unwrap();
+        assert!(args.headless && args.exit_on_halt);
+        assert_eq!(args.cycles, 50000);
+        assert_eq!(args.dump_screen, Some(PathBuf::from("out.txt")));
+        assert_eq!(args.dump_ppm, Some(PathBuf::from("out.ppm")));
+
+        // They only mean something headless, and the debugger needs a terminal
+        assert_eq!(
+            parse(&["--cycles", "10", "a.ch8"]).unwrap_err().kind(),
+            ErrorKind::MissingRequiredArgument
+        );
+        assert_eq!(
+            parse(&["--headless", "--debug", "a.ch8"])
+                .unwrap_err()
+                .kind(),
+            ErrorKind::ArgumentConflict
+        );
+    }
+
+    #[test]
+    fn input_recording_options() {
+        let args = parse(&["--record-input", "run.c8in", "a.ch8"]).unwrap();
+        assert_eq!(args.record_input, Some(PathBuf::from("run.c8in")));
+        let args = parse(&["--headless", "--replay-input", "run.c8in", "a.ch8"]).unwrap();
+        assert_eq!(args.replay_input, Some(PathBuf::from("run.c8in")));
+
+        // Headless has no keyboard to record, and stepping never ticks the timers
+        for bad in [
+            &["--headless", "--record-input", "run.c8in", "a.ch8"][..],
+            &["--step", "--replay-input", "run.c8in", "a.ch8"],
+            &[
+                "--record-input",
+                "a.c8in",
+                "--replay-input",
+                "b.c8in",
+                "a.ch8",
+            ],
+        ] {
+            assert_eq!(
+                parse(bad).unwrap_err().kind(),
+                ErrorKind::ArgumentConflict,
+                "{:?}",
+                bad
+            );
+        }
+    }
+
+    #[test]
+    fn breakpoint_options() {
+        let args = parse(&[
+            "--debug",
+            "--break",
+            "0x2A4 when V3 == 0x1F",
+            "--break",
+            "when I changes",
+            "a.ch8",
+        ])
+        .unwrap();
+        assert_eq!(
+            args.breakpoints,
+            [
+                Breakpoint::parse("0x2A4 when V3 == 0x1F").unwrap(),
+                Breakpoint::parse("when I changes").unwrap()
+            ]
+        );
+        assert!(parse(&["--headless", "--break", "0x200", "a.ch8"]).is_ok());
+        // Only the debugger and headless mode stop anywhere
+        assert_eq!(
+            parse(&["--break", "0x200", "a.ch8"]).unwrap_err().kind(),
+            ErrorKind::MissingRequiredArgument
+        );
+        assert_eq!(
+            parse(&["--debug", "--break", "when V3 = 1", "a.ch8"])
+                .unwrap_err()
+                .kind(),
+            ErrorKind::ValueValidation
+        );
+    }
+
+    #[test]
+    fn keypad_widget_option() {
+        assert!(parse(&["--show-keypad", "a.ch8"]).unwrap().show_keypad);
+        assert!(parse(&["--show-keypad", "--rom-dir", "roms"]).is_ok());
+        // The debugger has the space beside the screen, headless has no screen
+        for other in ["--debug", "--headless"] {
+            assert_eq!(
+                parse(&["--show-keypad", other, "a.ch8"])
+                    .unwrap_err()
+                    .kind(),
+                ErrorKind::ArgumentConflict
+            );
+        }
+    }
+
+    #[test]
+    fn rom_is_optional() {
+        let args = parse(&["--rom-dir", "roms", "--rom-dir", "more"]).unwrap();
+        assert_eq!(args.rom, None);
+        assert_eq!(args.rom_dir, [PathBuf::from("roms"), PathBuf::from("more")]);
+        // The directories are only for picking, headless mode can't pick
+        assert_eq!(
+            parse(&["--rom-dir", "roms", "a.ch8"]).unwrap_err().kind(),
+            ErrorKind::ArgumentConflict
+        );
+        assert_eq!(
+            parse(&["--headless"]).unwrap_err().kind(),
+            ErrorKind::MissingRequiredArgument
+        );
+    }
+
+    #[test]
+    fn rejects_bad_values() {
+        for bad in [
+            &["--speed", "0", "a.ch8"][..],
+            &["--speed", "fast", "a.ch8"],
+            &["--beep-freq", "5", "a.ch8"],
+            &["--fg", "#12345", "a.ch8"],
+            &["--bg", "zzzzzz", "a.ch8"],
+            &["--keymap", "p=g", "a.ch8"],
+        ] {
+            assert_eq!(
+                parse(bad).unwrap_err().kind(),
+                ErrorKind::ValueValidation,
+                "{:?}",
+                bad
+            );
+        }
+        for bad in [
+            &["--quirks", "chip48", "a.ch8"],
+            &["--renderer", "sixel", "a.ch8"],
+        ] {
+            assert_eq!(
+                parse(bad).unwrap_err().kind(),
+                ErrorKind::InvalidValue,
+                "{:?}",
+                bad
+            );
+        }
+    }
+}
@@ -0,0 +1,292 @@
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    sync::mpsc::Receiver,
+};
+
+use crossterm::{
+    cursor::MoveTo,
+    event::KeyCode,
+    queue,
+    style::{Attribute, Print, SetAttribute},
+    terminal::{Clear, ClearType},
+};
+
+use crate::cli::CLIEvent;
+
+pub const ROM_EXTENSIONS: [&str; 2] = ["ch8", "c8"];
+
+// Title and blank line above the list, blank line and help below it
+const HEADER_ROWS: u16 = 2;
+const FOOTER_ROWS: u16 = 2;
+const HELP: &str = "Up/Down choose  Enter play  Esc quit";
+
+// ROM files directly inside the given directories, sorted by name without regard to case.
+// Directories that can't be read are skipped, an empty list tells the user as much.
+pub fn scan_roms(dirs: &[PathBuf]) -> Vec<PathBuf> {
+    let mut roms = dirs
+        .iter()
+        .filter_map(|dir| std::fs::read_dir(dir).ok())
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_ok_and(|kind| kind.is_file()))
+        .map(|entry| entry.path())
+        .filter(|path| is_rom(path))
+        .collect::<Vec<_>>();
+    roms.sort_by_cached_key(|path| (display_name(path).to_lowercase(), path.clone()));
+    // The same directory given twice
+    roms.dedup();
+    return roms;
+}
+
+fn is_rom(path: &Path) -> bool {
+    return path.extension().is_some_and(|ext| {
+        return ROM_EXTENSIONS
+            .iter()
+            .any(|rom_ext| ext.eq_ignore_ascii_case(rom_ext));
+    });
+}
+
+// ROMs in the current directory are listed by file name, others with their directory
+pub fn display_name(path: &Path) -> String {
+    return path.strip_prefix(".").unwrap_or(path).display().to_string();
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BrowserAction {
+    Launch(PathBuf),
+    Quit,
+}
+
+pub struct RomBrowser {
+    roms: Vec<PathBuf>,
+    dirs: Vec<PathBuf>,
+    selected: usize,
+    // First ROM shown, the list scrolls to keep the selection visible
+    top: usize,
+    // Shown above the list, e.g. why the last game stopped
+    message: String,
+}
+
+impl RomBrowser {
+    pub fn new(dirs: Vec<PathBuf>) -> RomBrowser {
+        return RomBrowser {
+            roms: scan_roms(&dirs),
+            dirs,
+            selected: 0,
+            top: 0,
+            message: String::new(),
+        };
+    }
+
+    pub fn roms(&self) -> &[PathBuf] {
+        return &self.roms;
+    }
+
+    pub fn selected(&self) -> Option<&PathBuf> {
+        return self.roms.get(self.selected);
+    }
+
+    pub fn set_message(&mut self, message: String) {
+        self.message = message;
+    }
+
+    // rows is how many ROMs fit on screen, for paging
+    pub fn handle_key(&mut self, code: KeyCode, rows: usize) -> Option<BrowserAction> {
+        let last = self.roms.len().saturating_sub(1);
+        let page = rows.max(1);
+        match code {
+            KeyCode::Up => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down => self.selected = (self.selected + 1).min(last),
+            KeyCode::PageUp => self.selected = self.selected.saturating_sub(page),
+            KeyCode::PageDown => self.selected = (self.selected + page).min(last),
+            KeyCode::Home => self.selected = 0,
+            KeyCode::End => self.selected = last,
+            KeyCode::Enter => return self.selected().cloned().map(BrowserAction::Launch),
+            KeyCode::Esc | KeyCode::Char('q') => return Some(BrowserAction::Quit),
+            _ => {}
+        }
+        self.scroll_to_selection(rows);
+        return None;
+    }
+
+    fn scroll_to_selection(&mut self, rows: usize) {
+        let rows = rows.max(1);
+        if self.selected < self.top {
+            self.top = self.selected;
+        } else if self.selected >= self.top + rows {
+            self.top = self.selected + 1 - rows;
+        }
+    }
+
+    // The lines to show for a list with room for rows ROMs, the selection marked with >
+    pub fn visible_lines(&self, rows: usize) -> Vec<(String, bool)> {
+        return self
+            .roms
+            .iter()
+            .enumerate()
+            .skip(self.top)
+            .take(rows)
+            .map(|(index, path)| {
+                let selected = index == self.selected;
+                let marker = if selected { '>' } else { ' ' };
+                return (format!("{} {}", marker, display_name(path)), selected);
+            })
+            .collect();
+    }
+
+    pub fn draw<W: Write>(&self, out: &mut W, height: u16) -> std::io::Result<()> {
+        let rows = list_rows(height);
+        let dirs = self
+            .dirs
+            .iter()
+            .map(|dir| dir.display().to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+        let title = if !self.message.is_empty() {
+            self.message.clone()
+        } else if self.roms.is_empty() {
+            format!("No .ch8 or .c8 files in {}", dirs)
+        } else {
+            format!("CHIP-8 ROMs in {}", dirs)
+        };
+        queue!(out, Clear(ClearType::All), MoveTo(0, 0), Print(title))?;
+        for (row, (line, selected)) in self.visible_lines(rows).into_iter().enumerate() {
+            queue!(out, MoveTo(0, HEADER_ROWS + row as u16))?;
+            if selected {
+                queue!(out, SetAttribute(Attribute::Reverse), Print(line))?;
+                queue!(out, SetAttribute(Attribute::Reset))?;
+            } else {
+                queue!(out, Print(line))?;
+            }
+        }
+        queue!(out, MoveTo(0, HEADER_ROWS + rows as u16 + 1), Print(HELP))?;
+        return out.flush();
+    }
+
+    // Shows the list until a ROM is picked, None when the user quits. Expects the keys to be
+    // captured, see CLIManager::set_capture_keys.
+    pub fn pick<W: Write>(
+        &mut self,
+        events: &Receiver<CLIEvent>,
+        out: &mut W,
+    ) -> std::io::Result<Option<PathBuf>> {
+        loop {
+            let height = crossterm::terminal::size().map_or(24, |(_, rows)| rows);
+            self.draw(out, height)?;
+            let code = match events.recv() {
+                Ok(CLIEvent::Key(code)) => code,
+                Ok(CLIEvent::Sigint) | Err(_) => return Ok(None),
+                Ok(_) => continue,
+            };
+            match self.handle_key(code, list_rows(height)) {
+                Some(BrowserAction::Launch(path)) => return Ok(Some(path)),
+                Some(BrowserAction::Quit) => return Ok(None),
+                None => {}
+            }
+        }
+    }
+}
+
+fn list_rows(height: u16) -> usize {
+    return usize::from(height.saturating_sub(HEADER_ROWS + FOOTER_ROWS)).max(1);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("chip8-browser-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        return dir;
+    }
+
+    #[test]
+    fn scans_for_roms_sorted_by_name() {
+        let dir = temp_dir("scan");
+        for name in [
+            "pong.ch8",
+            "Brix.C8",
+            "tetris.CH8",
+            "notes.txt",
+            "ch8",
+            "a.ch8.bak",
+        ] {
+            std::fs::write(dir.join(name), [0x00, 0xE0]).unwrap();
+        }
+        // Directories are skipped even with a ROM extension, and aren't searched
+        std::fs::create_dir_all(dir.join("games.ch8")).unwrap();
+        let more = dir.join("more");
+        std::fs::create_dir_all(&more).unwrap();
+        std::fs::write(more.join("Airplane.ch8"), [0x00, 0xE0]).unwrap();
+
+        assert_eq!(scan_roms(std::slice::from_ref(&dir)).len(), 3);
+        let missing = dir.join("missing");
+        let roms = scan_roms(&[dir.clone(), more.clone(), missing, dir.clone()]);
+        let names = roms
+            .iter()
+            .map(|path| path.strip_prefix(&dir).unwrap().to_str().unwrap())
+            .collect::<Vec<_>>();
+        // Sorted by the whole path without regard to case
+        assert_eq!(
+            names,
+            ["Brix.C8", "more/Airplane.ch8", "pong.ch8", "tetris.CH8"]
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn display_names_drop_the_current_directory() {
+        assert_eq!(display_name(Path::new("./pong.ch8")), "pong.ch8");
+        assert_eq!(display_name(Path::new("roms/pong.ch8")), "roms/pong.ch8");
+    }
+
+    #[test]
+    fn navigates_and_scrolls() {
+        let mut browser = RomBrowser {
+            roms: (0..10)
+                .map(|n| PathBuf::from(format!("{}.ch8", n)))
+                .collect(),
+            dirs: vec![PathBuf::from(".")],
+            selected: 0,
+            top: 0,
+            message: String::new(),
+        };
+        let rows = 3;
+        assert_eq!(browser.handle_key(KeyCode::Up, rows), None);
+        assert_eq!(browser.selected(), Some(&PathBuf::from("0.ch8")));
+        for _ in 0..4 {
+            browser.handle_key(KeyCode::Down, rows);
+        }
+        let lines = browser.visible_lines(rows);
+        assert_eq!(
+            lines,
+            [
+                ("  2.ch8".to_string(), false),
+                ("  3.ch8".to_string(), false),
+                ("> 4.ch8".to_string(), true),
+            ]
+        );
+        browser.handle_key(KeyCode::PageDown, rows);
+        browser.handle_key(KeyCode::PageDown, rows);
+        assert_eq!(browser.selected(), Some(&PathBuf::from("9.ch8")));
+        assert_eq!(browser.visible_lines(rows)[0].0, "  7.ch8");
+        browser.handle_key(KeyCode::Home, rows);
+        assert_eq!(browser.visible_lines(rows)[0].0, "> 0.ch8");
+        assert_eq!(
+            browser.handle_key(KeyCode::Enter, rows),
+            Some(BrowserAction::Launch(PathBuf::from("0.ch8")))
+        );
+        assert_eq!(
+            browser.handle_key(KeyCode::Esc, rows),
+            Some(BrowserAction::Quit)
+        );
+
+        let mut empty = RomBrowser::new(vec![]);
+        assert_eq!(empty.handle_key(KeyCode::Down, rows), None);
+        assert_eq!(empty.handle_key(KeyCode::Enter, rows), None);
+    }
+}
@@ -0,0 +1,364 @@
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Condvar, Mutex},
+    thread::{self, JoinHandle},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use chip8_core::Frame;
+
+// Ten seconds of frames at 60Hz. If the disk can't keep up the oldest are dropped rather than
+// slowing the emulator down.
+pub const RING_CAPACITY: usize = 600;
+pub const TIMESTAMPS_FILE: &str = "timestamps.txt";
+
+// YYYYMMDD-HHMMSS in UTC, the time zone would need a date crate
+pub fn timestamp(at: SystemTime) -> String {
+    let secs = at
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs());
+    let (year, month, day) = civil_from_days((secs / 86_400) as i64);
+    let secs_of_day = secs % 86_400;
+    return format!(
+        "{:04}{:02}{:02}-{:02}{:02}{:02}",
+        year,
+        month,
+        day,
+        secs_of_day / 3600,
+        secs_of_day / 60 % 60,
+        secs_of_day % 60
+    );
+}
+
+// Days since 1970-01-01 to a date, from Howard Hinnant's civil_from_days
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    return (year, month, day);
+}
+
+// Next to the ROM, pong.ch8 gives pong-20261016-190000 plus the extension
+fn capture_path(rom: &Path, at: SystemTime, extension: &str) -> PathBuf {
+    let stem = rom
+        .file_stem()
+        .map_or_else(|| "chip8".into(), |stem| stem.to_string_lossy());
+    return rom.with_file_name(format!("{}-{}{}", stem, timestamp(at), extension));
+}
+
+pub fn screenshot_path(rom: &Path, at: SystemTime) -> PathBuf {
+    return capture_path(rom, at, ".ppm");
+}
+
+pub fn recording_dir(rom: &Path, at: SystemTime) -> PathBuf {
+    return capture_path(rom, at, "");
+}
+
+pub fn save_screenshot(path: &Path, frame: &Frame) -> Result<(), String> {
+    return File::create(path)
+        .and_then(|file| {
+            let mut out = BufWriter::new(file);
+            frame.write_ppm(&mut out)?;
+            return out.flush();
+        })
+        .map_err(|err| format!("Could not write {}: {}", path.display(), err));
+}
+
+pub struct TimedFrame {
+    pub frame: Frame,
+    // Since the recording started
+    pub at: Duration,
+}
+
+// Frames waiting to be written, dropping the oldest once full
+pub struct FrameRing {
+    frames: VecDeque<TimedFrame>,
+    capacity: usize,
+    dropped: u64,
+}
+
+impl FrameRing {
+    pub fn new(capacity: usize) -> FrameRing {
+        return FrameRing {
+            frames: VecDeque::with_capacity(capacity),
+            capacity,
+            dropped: 0,
+        };
+    }
+
+    pub fn push(&mut self, frame: TimedFrame) {
+        if self.frames.len() == self.capacity {
+            self.frames.pop_front();
+            self.dropped += 1;
+        }
+        self.frames.push_back(frame);
+    }
+
+    pub fn pop(&mut self) -> Option<TimedFrame> {
+        return self.frames.pop_front();
+    }
+
+    pub fn len(&self) -> usize {
+        return self.frames.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.frames.is_empty();
+    }
+
+    pub fn dropped(&self) -> u64 {
+        return self.dropped;
+    }
+}
+
+// A directory of frame-000000.ppm, frame-000001.ppm, ... plus timestamps.txt with each frame's
+// number and milliseconds since the recording started, for turning into a video later
+pub struct PpmSequence {
+    dir: PathBuf,
+    written: u64,
+    timestamps: BufWriter<File>,
+}
+
+impl PpmSequence {
+    pub fn create(dir: &Path) -> std::io::Result<PpmSequence> {
+        std::fs::create_dir_all(dir)?;
+        let timestamps = BufWriter::new(File::create(dir.join(TIMESTAMPS_FILE))?);
+        return Ok(PpmSequence {
+            dir: dir.to_path_buf(),
+            written: 0,
+            timestamps,
+        });
+    }
+
+    pub fn write(&mut self, frame: &TimedFrame) -> std::io::Result<()> {
+        let name = format!("frame-{:06}.ppm", self.written);
+        let mut out = BufWriter::new(File::create(self.dir.join(&name))?);
+        frame.frame.write_ppm(&mut out)?;
+        out.flush()?;
+        writeln!(self.timestamps, "{} {}", name, frame.at.as_millis())?;
+        self.written += 1;
+        return Ok(());
+    }
+
+    // How many frames were written
+    pub fn finish(mut self) -> std::io::Result<u64> {
+        self.timestamps.flush()?;
+        return Ok(self.written);
+    }
+}
+
+struct Queue {
+    ring: FrameRing,
+    stopping: bool,
+}
+
+// Records frames to a PpmSequence on a background thread so writing never holds up a frame
+pub struct Recorder {
+    dir: PathBuf,
+    started: SystemTime,
+    queue: Arc<(Mutex<Queue>, Condvar)>,
+    writer: Option<JoinHandle<std::io::Result<u64>>>,
+}
+
+pub struct Recording {
+    pub dir: PathBuf,
+    pub frames: u64,
+    // Frames the writer couldn't keep up with
+    pub dropped: u64,
+}
+
+impl Recorder {
+    // Creates the directory straight away so a bad path fails here rather than on the thread
+    pub fn start(dir: PathBuf, started: SystemTime) -> Result<Recorder, String> {
+        let mut sequence = PpmSequence::create(&dir)
+            .map_err(|err| format!("Could not record to {}: {}", dir.display(), err))?;
+        let queue = Arc::new((
+            Mutex::new(Queue {
+                ring: FrameRing::new(RING_CAPACITY),
+                stopping: false,
+            }),
+            Condvar::new(),
+        ));
+        let writer_queue = queue.clone();
+        let writer = thread::spawn(move || {
+            let (queue, ready) = &*writer_queue;
+            loop {
+                let mut waiting = queue.lock().unwrap();
+                while waiting.ring.is_empty() && !waiting.stopping {
+                    waiting = ready.wait(waiting).unwrap();
+                }
+                let Some(frame) = waiting.ring.pop() else {
+                    // Stopping with nothing left to write
+                    break;
+                };
+                drop(waiting);
+                sequence.write(&frame)?;
+            }
+            return sequence.finish();
+        });
+        return Ok(Recorder {
+            dir,
+            started,
+            queue,
+            writer: Some(writer),
+        });
+    }
+
+    pub fn dir(&self) -> &Path {
+        return &self.dir;
+    }
+
+    pub fn push(&self, frame: Frame, at: SystemTime) {
+        let at = at.duration_since(self.started).unwrap_or_default();
+        let (queue, ready) = &*self.queue;
+        queue.lock().unwrap().ring.push(TimedFrame { frame, at });
+        ready.notify_one();
+    }
+
+    // Waits for the frames still buffered to be written
+    pub fn stop(mut self) -> Result<Recording, String> {
+        let written = self.finish_writing();
+        let dropped = self.queue.0.lock().unwrap().ring.dropped();
+        return match written {
+            Ok(frames) => Ok(Recording {
+                dir: self.dir.clone(),
+                frames,
+                dropped,
+            }),
+            Err(err) => Err(format!(
+                "Could not record to {}: {}",
+                self.dir.display(),
+                err
+            )),
+        };
+    }
+
+    fn finish_writing(&mut self) -> std::io::Result<u64> {
+        let Some(writer) = self.writer.take() else {
+            return Ok(0);
+        };
+        let (queue, ready) = &*self.queue;
+        queue.lock().unwrap().stopping = true;
+        ready.notify_one();
+        return writer.join().unwrap();
+    }
+}
+
+// Quitting mid-recording still writes what was buffered
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        let _ = self.finish_writing();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chip8_core::Screen;
+
+    use super::*;
+
+    // 2026-10-16 19:04:42 UTC
+    const FAKE_NOW: u64 = 1_792_177_482;
+
+    fn fake_clock(seconds: u64, millis: u64) -> SystemTime {
+        return UNIX_EPOCH
+            + Duration::from_secs(FAKE_NOW + seconds)
+            + Duration::from_millis(millis);
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        return std::env::temp_dir().join(format!("chip8-capture-{}-{}", name, std::process::id()));
+    }
+
+    fn frame(lit: bool) -> Frame {
+        let screen = Screen::new();
+        screen.fill(lit);
+        return screen.frame();
+    }
+
+    #[test]
+    fn names_captures_after_the_rom() {
+        assert_eq!(timestamp(UNIX_EPOCH), "19700101-000000");
+        assert_eq!(timestamp(fake_clock(0, 0)), "20261016-190442");
+        // Leap day
+        assert_eq!(
+            timestamp(UNIX_EPOCH + Duration::from_secs(951_782_400)),
+            "20000229-000000"
+        );
+        assert_eq!(
+            screenshot_path(Path::new("roms/pong.ch8"), fake_clock(0, 0)),
+            PathBuf::from("roms/pong-20261016-190442.ppm")
+        );
+        assert_eq!(
+            recording_dir(Path::new("pong.ch8"), fake_clock(1, 0)),
+            PathBuf::from("pong-20261016-190443")
+        );
+    }
+
+    #[test]
+    fn ring_drops_the_oldest_frames() {
+        let mut ring = FrameRing::new(2);
+        assert!(ring.is_empty());
+        for ms in [0, 16, 33] {
+            ring.push(TimedFrame {
+                frame: frame(false),
+                at: Duration::from_millis(ms),
+            });
+        }
+        assert_eq!((ring.len(), ring.dropped()), (2, 1));
+        assert_eq!(ring.pop().unwrap().at, Duration::from_millis(16));
+        assert_eq!(ring.pop().unwrap().at, Duration::from_millis(33));
+        assert!(ring.pop().is_none());
+    }
+
+    #[test]
+    fn records_a_ppm_sequence() {
+        let dir = temp_dir("sequence");
+        let recorder = Recorder::start(dir.clone(), fake_clock(0, 0)).unwrap();
+        for (n, ms) in [0, 17, 33].into_iter().enumerate() {
+            recorder.push(frame(n == 1), fake_clock(0, ms));
+        }
+        let recording = recorder.stop().unwrap();
+        assert_eq!((recording.frames, recording.dropped), (3, 0));
+
+        let timestamps = std::fs::read_to_string(dir.join(TIMESTAMPS_FILE)).unwrap();
+        assert_eq!(
+            timestamps,
+            "frame-000000.ppm 0\nframe-000001.ppm 17\nframe-000002.ppm 33\n"
+        );
+        let mut expected = vec![];
+        frame(true).write_ppm(&mut expected).unwrap();
+        assert_eq!(
+            std::fs::read(dir.join("frame-000001.ppm")).unwrap(),
+            expected
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn reports_unwritable_paths() {
+        let file = temp_dir("not-a-dir");
+        std::fs::write(&file, "").unwrap();
+        let Err(err) = Recorder::start(file.join("recording"), fake_clock(0, 0)) else {
+            panic!("recorded under a file");
+        };
+        assert!(err.starts_with("Could not record to"), "{}", err);
+        let err = save_screenshot(&file.join("shot.ppm"), &frame(false)).unwrap_err();
+        assert!(err.starts_with("Could not write"), "{}", err);
+        std::fs::remove_file(&file).unwrap();
+    }
+}
@@ -1,89 +1,362 @@
 use std::{
-    sync::{Arc, RwLock},
+    cell::Cell,
+    io::Write,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{Receiver, RecvTimeoutError},
+        Arc, Mutex,
+    },
     thread,
-    time::Duration,
+    time::{Duration, Instant},
 };
 
-use chip8_core::{Chip8Input, Chip8Screen, Screen};
+use chip8_core::{Chip8Input, Chip8Screen, Frame, Palette, PixelBuffer, Rgb, Screen};
+
 use crossterm::{
-    cursor::{MoveTo, MoveToColumn, MoveToNextLine},
-    event::{KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
-    execute,
-    style::Print,
+    cursor::MoveTo,
+    event::{Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    queue,
+    style::{Color, Print, ResetColor, SetBackgroundColor, SetForegroundColor},
 };
 
+use crate::{keymap::KeyMap, renderer::Renderer};
+
+// Without release events a key counts as held until this long after its last press or repeat.
+// Terminals wait 250-500ms before repeating a held key, so this has to outlast that delay or the
+// key is released and pressed again before the first repeat. A single tap is held this long too.
+pub const FALLBACK_RELEASE: Duration = Duration::from_millis(550);
+
+// What the input thread knows about the 16 keypad keys
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KeypadState {
+    held: [bool; 16],
+    released: Option<u8>,
+    // Fallback mode only, when each held key gets released
+    release_at: [Option<Instant>; 16],
+}
+
+impl KeypadState {
+    // release_at is None when a release event will arrive for this key
+    pub fn press(&mut self, key: u8, release_at: Option<Instant>) {
+        let key = usize::from(key & 0xF);
+        self.held[key] = true;
+        self.release_at[key] = release_at;
+    }
+
+    pub fn release(&mut self, key: u8) {
+        let index = usize::from(key & 0xF);
+        self.release_at[index] = None;
+        if self.held[index] {
+            self.held[index] = false;
+            self.released = Some(key & 0xF);
+        }
+    }
+
+    // Releases the fallback keys whose time is up
+    pub fn expire(&mut self, now: Instant) {
+        for key in 0..16u8 {
+            if self.release_at[usize::from(key)].is_some_and(|at| at <= now) {
+                self.release(key);
+            }
+        }
+    }
+
+    pub fn next_expiry(&self) -> Option<Instant> {
+        return self.release_at.iter().flatten().min().copied();
+    }
+
+    pub fn is_held(&self, key: u8) -> bool {
+        return key <= 0xF && self.held[usize::from(key)];
+    }
+
+    // Bit n is set while key n is held
+    pub fn held_mask(&self) -> u16 {
+        return (0..16)
+            .filter(|key| self.held[*key])
+            .map(|key| 1 << key)
+            .sum();
+    }
+
+    // Lowest held key wins when several are down
+    pub fn lowest_held(&self) -> Option<u8> {
+        return (0..16u8).find(|key| self.held[usize::from(*key)]);
+    }
+
+    pub fn take_released(&mut self) -> Option<u8> {
+        return self.released.take();
+    }
+}
+
 pub struct CLIManager {
-    pub pressed_key: Arc<RwLock<Option<u8>>>,
+    keypad: Arc<Mutex<KeypadState>>,
+    capture_keys: Arc<AtomicBool>,
     screen: Screen,
+    // Shared with the input thread so a ROM picked from the list can bring its own bindings
+    keymap: Arc<Mutex<KeyMap>>,
+    renderer: Cell<Renderer>,
 }
 
 pub enum CLIEvent {
     Sigint,
+    // Enter, runs one instruction in step mode
+    Step,
+    // Any key press while the keys are captured, see set_capture_keys
+    Key(KeyCode),
+    Hotkey(Hotkey),
+}
+
+// Emulator controls. They use keys the keymap leaves unbound, holding Ctrl reaches them even when
+// the key is bound, e.g. Ctrl-R resets with the default layout where R is keypad D. Save state
+// slots are picked with Alt or Ctrl and a digit since 1-4 are keypad keys.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hotkey {
+    Pause,
+    Reset,
+    Faster,
+    Slower,
+    Mute,
+    SaveState,
+    LoadState,
+    // 1-9
+    Slot(u8),
+    // Esc, back to the ROM list when the ROM was picked from one
+    Menu,
+    // F12
+    Screenshot,
+    // F9, starts or stops recording every drawn frame
+    Record,
+}
+
+impl Hotkey {
+    fn from_key(code: KeyCode, modifiers: KeyModifiers, keymap: &KeyMap) -> Option<Hotkey> {
+        let c = match code {
+            KeyCode::F(5) => return Some(Hotkey::SaveState),
+            KeyCode::F(7) => return Some(Hotkey::LoadState),
+            KeyCode::Esc => return Some(Hotkey::Menu),
+            KeyCode::F(12) => return Some(Hotkey::Screenshot),
+            KeyCode::F(9) => return Some(Hotkey::Record),
+            KeyCode::Char(c) => c,
+            _ => return None,
+        };
+        if modifiers.intersects(KeyModifiers::ALT | KeyModifiers::CONTROL) {
+            if let Some(slot @ 1..=9) = c.to_digit(10) {
+                return Some(Hotkey::Slot(slot as u8));
+            }
+        }
+        if !modifiers.contains(KeyModifiers::CONTROL) && keymap.key(code).is_some() {
+            return None;
+        }
+        return match c.to_ascii_lowercase() {
+            'p' => Some(Hotkey::Pause),
+            'r' => Some(Hotkey::Reset),
+            '+' | '=' => Some(Hotkey::Faster),
+            '-' | '_' => Some(Hotkey::Slower),
+            'm' => Some(Hotkey::Mute),
+            _ => None,
+        };
+    }
+}
+
+impl Default for CLIManager {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl CLIManager {
     pub fn new() -> CLIManager {
+        return CLIManager::with_keymap(KeyMap::default());
+    }
+
+    pub fn with_keymap(keymap: KeyMap) -> CLIManager {
         return CLIManager {
-            pressed_key: Arc::new(RwLock::new(None)),
+            keypad: Arc::new(Mutex::new(KeypadState::default())),
+            capture_keys: Arc::new(AtomicBool::new(false)),
             screen: Screen::new(),
+            keymap: Arc::new(Mutex::new(keymap)),
+            renderer: Cell::new(Renderer::default()),
         };
     }
 
-    pub fn watch_for_key(&self) -> std::sync::mpsc::Receiver<CLIEvent> {
+    // While set key presses come through as CLIEvent::Key instead of reaching the keypad, for the
+    // debugger. Releases still reach it so nothing stays held.
+    pub fn set_capture_keys(&self, capture: bool) {
+        self.capture_keys.store(capture, Ordering::Relaxed);
+    }
+
+    pub fn set_keymap(&self, keymap: KeyMap) {
+        *self.keymap.lock().unwrap() = keymap;
+    }
+
+    pub fn held_keys(&self) -> u16 {
+        return self.keypad.lock().unwrap().held_mask();
+    }
+
+    pub fn pressed_key(&self) -> Option<u8> {
+        return self.keypad.lock().unwrap().lowest_held();
+    }
+
+    // key_releases says whether the terminal reports key up events, which it only does with the
+    // keyboard enhancement flags pushed. Without them keys are released after FALLBACK_RELEASE.
+    pub fn watch_for_key(&self, key_releases: bool) -> Receiver<CLIEvent> {
         let (tx, rx) = std::sync::mpsc::channel();
-        let pressed_key = self.pressed_key.clone();
         thread::spawn(move || loop {
-            let hex = match crossterm::event::read().unwrap() {
-                crossterm::event::Event::Key(KeyEvent {
-                    code: KeyCode::Char('c'),
-                    modifiers: KeyModifiers::CONTROL,
-                    ..
-                }) => {
-                    tx.send(CLIEvent::Sigint).unwrap();
-                    None
-                }
-                crossterm::event::Event::Key(KeyEvent { code, .. }) => match code {
-                    KeyCode::Char('0'..='9')
-                    | KeyCode::Char('a'..='f')
-                    | KeyCode::Char('A'..='F') => u8::from_str_radix(&code.to_string(), 16).ok(),
-                    _ => None,
-                },
-                _ => None, // Ignore other events
-            };
+            if tx.send(crossterm::event::read().unwrap()).is_err() {
+                return;
+            }
+        });
+        return self.watch_events(rx, key_releases);
+    }
 
-            if let Some(key) = hex {
-                pressed_key.write().unwrap().replace(key);
-                thread::sleep(Duration::from_millis(50));
-                pressed_key.write().unwrap().take();
+    // Spawns the input thread, which applies events to the keypad and times out fallback presses
+    // until the sender hangs up
+    pub fn watch_events(&self, events: Receiver<Event>, key_releases: bool) -> Receiver<CLIEvent> {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let keypad = self.keypad.clone();
+        let keymap = self.keymap.clone();
+        let capture_keys = self.capture_keys.clone();
+        thread::spawn(move || loop {
+            let next_expiry = keypad.lock().unwrap().next_expiry();
+            let timeout = next_expiry.map_or(Duration::from_secs(1), |at| {
+                at.saturating_duration_since(Instant::now())
+            });
+            let event = match events.recv_timeout(timeout) {
+                Ok(event) => Some(event),
+                Err(RecvTimeoutError::Timeout) => None,
+                Err(RecvTimeoutError::Disconnected) => return,
+            };
+            let now = Instant::now();
+            let mut keypad = keypad.lock().unwrap();
+            if let Some(event) = event {
+                let release_at = (!key_releases).then_some(now + FALLBACK_RELEASE);
+                let capture = capture_keys.load(Ordering::Relaxed);
+                let keymap = keymap.lock().unwrap();
+                if let Some(cli_event) =
+                    handle_event(&event, &keymap, &mut keypad, release_at, capture)
+                {
+                    // The main loop may already be gone, there's nobody left to tell
+                    let _ = tx.send(cli_event);
+                }
             }
+            keypad.expire(now);
         });
 
         return rx;
     }
 
+    pub fn set_palette(&self, palette: Palette) {
+        self.screen.set_palette(palette);
+    }
+
+    pub fn set_renderer(&self, renderer: Renderer) {
+        self.renderer.set(renderer);
+        self.set_screen_buffer(self.screen_buffer());
+    }
+
+    // The first free column right of the screen, leaving two blank columns between them
+    pub fn side_column(&self) -> u16 {
+        return self.renderer.get().columns() + 2;
+    }
+
+    // The first free line below the screen, leaving a blank line between them
+    pub fn status_row(&self) -> u16 {
+        return self.renderer.get().lines() + 1;
+    }
+
+    pub fn screen_buffer(&self) -> PixelBuffer {
+        return self.screen.buffer.borrow().clone();
+    }
+
+    pub fn frame(&self) -> Frame {
+        return self.screen.frame();
+    }
+
+    // Redraws the whole screen on the next draw_if_needed
+    pub fn set_screen_buffer(&self, buffer: PixelBuffer) {
+        self.screen.set_buffer(buffer);
+    }
+
     pub fn draw_if_needed(&self) -> bool {
         if !self.screen.is_pending_draw() {
             return false;
         }
-        execute!(std::io::stdout(), MoveTo(0, 0)).unwrap();
-
-        self.screen.draw_as_string().split("\n").for_each(|line| {
-            execute!(
-                std::io::stdout(),
-                Print(line),
-                MoveToNextLine(1),
-                MoveToColumn(0)
-            )
-            .unwrap();
-        });
+        let palette = self.screen.palette();
+        let renderer = self.renderer.get();
+        let buffer = self.screen.buffer.borrow();
+        let mut stdout = std::io::stdout();
+        queue!(
+            stdout,
+            SetForegroundColor(to_color(palette.on())),
+            SetBackgroundColor(to_color(palette.off())),
+        )
+        .unwrap();
+        // Only the lines holding rows that changed since the last draw
+        for line in renderer.dirty_lines(self.screen.take_dirty_rows()) {
+            queue!(stdout, MoveTo(0, line), Print(renderer.line(&buffer, line))).unwrap();
+        }
+        queue!(stdout, ResetColor).unwrap();
+        stdout.flush().unwrap();
         self.screen.mark_drawn();
         return true;
     }
 }
 
+// Ctrl-C and Enter are handled before the keymap so they work with any mapping. Presses of mapped
+// keys are held until their release event, or release_at when the terminal doesn't send those.
+fn handle_event(
+    event: &Event,
+    keymap: &KeyMap,
+    keypad: &mut KeypadState,
+    release_at: Option<Instant>,
+    capture: bool,
+) -> Option<CLIEvent> {
+    let Event::Key(KeyEvent {
+        code,
+        modifiers,
+        kind,
+        ..
+    }) = *event
+    else {
+        return None; // Ignore other events
+    };
+    match code {
+        KeyCode::Char('c') if modifiers.contains(KeyModifiers::CONTROL) => {
+            return (kind == KeyEventKind::Press).then_some(CLIEvent::Sigint);
+        }
+        _ if capture && kind != KeyEventKind::Release => return Some(CLIEvent::Key(code)),
+        KeyCode::Enter => return (kind == KeyEventKind::Press).then_some(CLIEvent::Step),
+        _ => {}
+    }
+    if let Some(hotkey) = Hotkey::from_key(code, modifiers, keymap) {
+        return (kind == KeyEventKind::Press).then_some(CLIEvent::Hotkey(hotkey));
+    }
+    let key = keymap.key(code)?;
+    match kind {
+        KeyEventKind::Press | KeyEventKind::Repeat => keypad.press(key, release_at),
+        KeyEventKind::Release => keypad.release(key),
+    }
+    return None;
+}
+
+fn to_color(rgb: Rgb) -> Color {
+    return Color::Rgb {
+        r: rgb.r,
+        g: rgb.g,
+        b: rgb.b,
+    };
+}
+
 impl Chip8Input for CLIManager {
     fn get_key(&self) -> Option<u8> {
-        self.pressed_key.read().unwrap().clone()
+        self.pressed_key()
+    }
+
+    fn was_key_released(&self) -> Option<u8> {
+        self.keypad.lock().unwrap().take_released()
+    }
+
+    fn is_key_held(&self, key: u8) -> bool {
+        self.keypad.lock().unwrap().is_held(key)
     }
 }
 
@@ -95,4 +368,297 @@ impl Chip8Screen for CLIManager {
     fn clear(&self) {
         self.screen.clear();
     }
+
+    fn draw_wide_sprite(&self, x: u8, y: u8, sprite: &[u8]) -> bool {
+        self.screen.draw_wide_sprite(x, y, sprite)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crossterm::event::KeyEventState;
+
+    use super::*;
+
+    fn key(code: KeyCode, kind: KeyEventKind) -> Event {
+        return Event::Key(KeyEvent {
+            code,
+            modifiers: KeyModifiers::NONE,
+            kind,
+            state: KeyEventState::NONE,
+        });
+    }
+
+    // Enter comes back as a Step event once everything sent before it has been handled
+    fn sync(events: &std::sync::mpsc::Sender<Event>, rx: &Receiver<CLIEvent>) {
+        events
+            .send(key(KeyCode::Enter, KeyEventKind::Press))
+            .unwrap();
+        assert!(matches!(
+            rx.recv_timeout(Duration::from_secs(5)),
+            Ok(CLIEvent::Step)
+        ));
+    }
+
+    #[test]
+    fn key_stays_held_until_released() {
+        let manager = CLIManager::new();
+        let (events, rx_events) = std::sync::mpsc::channel();
+        let rx = manager.watch_events(rx_events, true);
+
+        events
+            .send(key(KeyCode::Char('a'), KeyEventKind::Press))
+            .unwrap();
+        sync(&events, &rx);
+        thread::sleep(Duration::from_millis(100));
+        assert_eq!(manager.get_key(), Some(0x7));
+        assert_eq!(manager.was_key_released(), None);
+
+        // Releasing another key and key repeats don't change what's held
+        events
+            .send(key(KeyCode::Char('w'), KeyEventKind::Release))
+            .unwrap();
+        events
+            .send(key(KeyCode::Char('a'), KeyEventKind::Repeat))
+            .unwrap();
+        sync(&events, &rx);
+        assert_eq!(manager.get_key(), Some(0x7));
+        assert_eq!(manager.was_key_released(), None);
+
+        events
+            .send(key(KeyCode::Char('a'), KeyEventKind::Release))
+            .unwrap();
+        sync(&events, &rx);
+        assert_eq!(manager.get_key(), None);
+        assert_eq!(manager.was_key_released(), Some(0x7));
+        assert_eq!(manager.was_key_released(), None);
+    }
+
+    #[test]
+    fn presses_auto_release_without_release_events() {
+        let manager = CLIManager::new();
+        let (events, rx_events) = std::sync::mpsc::channel();
+        let rx = manager.watch_events(rx_events, false);
+
+        events
+            .send(key(KeyCode::Char('x'), KeyEventKind::Press))
+            .unwrap();
+        sync(&events, &rx);
+        assert!(manager.is_key_held(0x0));
+        assert_eq!(manager.was_key_released(), None);
+
+        // The input thread wakes up by itself to release it
+        thread::sleep(FALLBACK_RELEASE * 2);
+        assert_eq!(manager.get_key(), None);
+        assert_eq!(manager.was_key_released(), Some(0x0));
+    }
+
+    #[test]
+    fn fallback_holds_a_key_until_the_first_repeat() {
+        let manager = CLIManager::new();
+        let (events, rx_events) = std::sync::mpsc::channel();
+        let rx = manager.watch_events(rx_events, false);
+
+        // Without the enhancement flags, repeats arrive as more presses
+        let press = || key(KeyCode::Char('x'), KeyEventKind::Press);
+        events.send(press()).unwrap();
+        sync(&events, &rx);
+        thread::sleep(Duration::from_millis(300));
+        events.send(press()).unwrap();
+        sync(&events, &rx);
+        assert!(manager.is_key_held(0x0));
+        assert_eq!(manager.was_key_released(), None);
+
+        // The repeat pushed the release back
+        thread::sleep(Duration::from_millis(300));
+        assert!(manager.is_key_held(0x0));
+        assert_eq!(manager.was_key_released(), None);
+    }
+
+    #[test]
+    fn fallback_releases_each_key_on_its_own() {
+        let start = Instant::now();
+        let ms = |ms| start + Duration::from_millis(ms);
+        let mut keypad = KeypadState::default();
+        assert_eq!(keypad.next_expiry(), None);
+
+        keypad.press(0x1, Some(ms(100)));
+        keypad.press(0x2, Some(ms(150)));
+        assert_eq!(keypad.next_expiry(), Some(ms(100)));
+        // A repeat of key 1 pushes its release back
+        keypad.press(0x1, Some(ms(180)));
+        assert_eq!(keypad.next_expiry(), Some(ms(150)));
+
+        keypad.expire(ms(120));
+        assert!(keypad.is_held(0x1) && keypad.is_held(0x2));
+        assert_eq!(keypad.held_mask(), 0b110);
+
+        keypad.expire(ms(150));
+        assert!(keypad.is_held(0x1) && !keypad.is_held(0x2));
+        assert_eq!(keypad.take_released(), Some(0x2));
+        assert_eq!(keypad.lowest_held(), Some(0x1));
+        assert_eq!(keypad.next_expiry(), Some(ms(180)));
+
+        keypad.expire(ms(200));
+        assert_eq!(keypad.lowest_held(), None);
+        assert_eq!(keypad.take_released(), Some(0x1));
+        assert_eq!(keypad.take_released(), None);
+        assert_eq!(keypad.next_expiry(), None);
+
+        // Keys pressed without a deadline wait for their release event
+        keypad.press(0x3, None);
+        keypad.expire(ms(10_000));
+        assert!(keypad.is_held(0x3));
+    }
+
+    #[test]
+    fn events_update_the_keypad() {
+        let keymap = KeyMap::default();
+        let mut keypad = KeypadState::default();
+        let mut handle = |event: Event| handle_event(&event, &keymap, &mut keypad, None, false);
+
+        assert!(handle(key(KeyCode::Char('q'), KeyEventKind::Press)).is_none());
+        assert!(handle(key(KeyCode::Char('v'), KeyEventKind::Repeat)).is_none());
+        assert!(handle(key(KeyCode::Char('o'), KeyEventKind::Press)).is_none());
+        assert!(handle(key(KeyCode::Char('q'), KeyEventKind::Release)).is_none());
+        assert!(matches!(
+            handle(key(KeyCode::Enter, KeyEventKind::Press)),
+            Some(CLIEvent::Step)
+        ));
+        assert!(handle(key(KeyCode::Enter, KeyEventKind::Release)).is_none());
+        assert!(matches!(
+            handle(Event::Key(KeyEvent::new(
+                KeyCode::Char('c'),
+                KeyModifiers::CONTROL
+            ))),
+            Some(CLIEvent::Sigint)
+        ));
+        assert!(handle(Event::FocusLost).is_none());
+
+        // Ctrl-C didn't press 0xB
+        let held = (0..16u8).filter(|k| keypad.is_held(*k)).collect::<Vec<_>>();
+        assert_eq!(held, [0xF]);
+        assert_eq!(keypad.take_released(), Some(0x4));
+    }
+
+    #[test]
+    fn hotkeys_avoid_bound_keys() {
+        let keymap = KeyMap::default().with_overrides("m=1").unwrap();
+        let mut keypad = KeypadState::default();
+        let mut handle = |code: KeyCode, modifiers: KeyModifiers| {
+            let event = Event::Key(KeyEvent::new(code, modifiers));
+            return match handle_event(&event, &keymap, &mut keypad, None, false) {
+                Some(CLIEvent::Hotkey(hotkey)) => Some(hotkey),
+                _ => None,
+            };
+        };
+
+        assert_eq!(
+            handle(KeyCode::Char('p'), KeyModifiers::NONE),
+            Some(Hotkey::Pause)
+        );
+        assert_eq!(
+            handle(KeyCode::Char('P'), KeyModifiers::SHIFT),
+            Some(Hotkey::Pause)
+        );
+        assert_eq!(
+            handle(KeyCode::Char('+'), KeyModifiers::SHIFT),
+            Some(Hotkey::Faster)
+        );
+        assert_eq!(
+            handle(KeyCode::Char('-'), KeyModifiers::NONE),
+            Some(Hotkey::Slower)
+        );
+        // R is keypad D and M was bound above, those need Ctrl
+        assert_eq!(handle(KeyCode::Char('r'), KeyModifiers::NONE), None);
+        assert_eq!(handle(KeyCode::Char('m'), KeyModifiers::NONE), None);
+        assert_eq!(
+            handle(KeyCode::Char('r'), KeyModifiers::CONTROL),
+            Some(Hotkey::Reset)
+        );
+        assert_eq!(
+            handle(KeyCode::Char('m'), KeyModifiers::CONTROL),
+            Some(Hotkey::Mute)
+        );
+        assert_eq!(handle(KeyCode::Esc, KeyModifiers::NONE), Some(Hotkey::Menu));
+        assert_eq!(
+            handle(KeyCode::F(12), KeyModifiers::NONE),
+            Some(Hotkey::Screenshot)
+        );
+        assert_eq!(
+            handle(KeyCode::F(9), KeyModifiers::NONE),
+            Some(Hotkey::Record)
+        );
+        assert_eq!(
+            handle(KeyCode::F(5), KeyModifiers::NONE),
+            Some(Hotkey::SaveState)
+        );
+        assert_eq!(
+            handle(KeyCode::F(7), KeyModifiers::NONE),
+            Some(Hotkey::LoadState)
+        );
+        assert_eq!(
+            handle(KeyCode::Char('2'), KeyModifiers::ALT),
+            Some(Hotkey::Slot(2))
+        );
+        assert_eq!(
+            handle(KeyCode::Char('9'), KeyModifiers::CONTROL),
+            Some(Hotkey::Slot(9))
+        );
+        assert_eq!(handle(KeyCode::Char('0'), KeyModifiers::ALT), None);
+        // Plain 2 is keypad 2
+        assert_eq!(handle(KeyCode::Char('2'), KeyModifiers::NONE), None);
+        assert_eq!(keypad.lowest_held(), Some(0x1));
+        assert!(keypad.is_held(0x2));
+    }
+
+    #[test]
+    fn captured_keys_skip_the_keypad() {
+        let keymap = KeyMap::default();
+        let mut keypad = KeypadState::default();
+        keypad.press(0x8, None);
+        let mut handle = |event: Event| handle_event(&event, &keymap, &mut keypad, None, true);
+
+        assert!(matches!(
+            handle(key(KeyCode::Char('w'), KeyEventKind::Press)),
+            Some(CLIEvent::Key(KeyCode::Char('w')))
+        ));
+        assert!(matches!(
+            handle(key(KeyCode::Enter, KeyEventKind::Press)),
+            Some(CLIEvent::Key(KeyCode::Enter))
+        ));
+        // A key held before the capture started can still be let go
+        assert!(handle(key(KeyCode::Char('s'), KeyEventKind::Release)).is_none());
+        assert_eq!(keypad.lowest_held(), None);
+        assert_eq!(keypad.take_released(), Some(0x8));
+    }
+
+    #[test]
+    fn keys_go_through_the_keymap() {
+        let keymap = KeyMap::default().with_overrides("p=5").unwrap();
+        let manager = CLIManager::with_keymap(keymap);
+        let (events, rx_events) = std::sync::mpsc::channel();
+        let rx = manager.watch_events(rx_events, true);
+
+        // Unmapped keys don't touch the keypad but Enter still steps
+        events
+            .send(key(KeyCode::Char('9'), KeyEventKind::Press))
+            .unwrap();
+        sync(&events, &rx);
+        assert_eq!(manager.get_key(), None);
+
+        events
+            .send(key(KeyCode::Char('P'), KeyEventKind::Press))
+            .unwrap();
+        sync(&events, &rx);
+        assert_eq!(manager.get_key(), Some(0x5));
+
+        // The input thread picks up a new keymap
+        manager.set_keymap(KeyMap::default().with_overrides("9=2").unwrap());
+        events
+            .send(key(KeyCode::Char('9'), KeyEventKind::Press))
+            .unwrap();
+        sync(&events, &rx);
+        assert_eq!(manager.get_key(), Some(0x2));
+    }
 }
@@ -0,0 +1,589 @@
+use std::{cell::Cell, fmt};
+
+use chip8_core::CpuView;
+
+// What a condition or watch can look at, the registers the pre-step hook sees
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Register {
+    V(u8),
+    I,
+    Pc,
+}
+
+impl Register {
+    fn parse(word: &str) -> Option<Register> {
+        let word = word.to_ascii_uppercase();
+        return match word.as_str() {
+            "I" => Some(Register::I),
+            "PC" => Some(Register::Pc),
+            _ => {
+                let digit = word.strip_prefix('V')?;
+                if digit.len() != 1 {
+                    return None;
+                }
+                u8::from_str_radix(digit, 16).ok().map(Register::V)
+            }
+        };
+    }
+
+    pub fn read(self, pc: u16, i: u16, v: &[u8; 16]) -> u16 {
+        return match self {
+            Register::V(x) => u16::from(v[usize::from(x)]),
+            Register::I => i,
+            Register::Pc => pc,
+        };
+    }
+
+    // V registers are a byte, I and the PC are addresses
+    fn format_value(self, value: u16) -> String {
+        return match self {
+            Register::V(_) => format!("{:02X}", value),
+            _ => format!("{:03X}", value),
+        };
+    }
+}
+
+impl fmt::Display for Register {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return match self {
+            Register::V(x) => write!(f, "V{:X}", x),
+            Register::I => write!(f, "I"),
+            Register::Pc => write!(f, "PC"),
+        };
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Comparison {
+    fn parse(symbol: &str) -> Option<Comparison> {
+        return match symbol {
+            "==" => Some(Comparison::Eq),
+            "!=" => Some(Comparison::Ne),
+            "<" => Some(Comparison::Lt),
+            "<=" => Some(Comparison::Le),
+            ">" => Some(Comparison::Gt),
+            ">=" => Some(Comparison::Ge),
+            _ => None,
+        };
+    }
+
+    pub fn symbol(self) -> &'static str {
+        return match self {
+            Comparison::Eq => "==",
+            Comparison::Ne => "!=",
+            Comparison::Lt => "<",
+            Comparison::Le => "<=",
+            Comparison::Gt => ">",
+            Comparison::Ge => ">=",
+        };
+    }
+
+    pub fn test(self, left: u16, right: u16) -> bool {
+        return match self {
+            Comparison::Eq => left == right,
+            Comparison::Ne => left != right,
+            Comparison::Lt => left < right,
+            Comparison::Le => left <= right,
+            Comparison::Gt => left > right,
+            Comparison::Ge => left >= right,
+        };
+    }
+}
+
+// A register compared with a number, e.g. V3 == 0x1F, or a register that changes between two
+// instructions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Condition {
+    Compare {
+        register: Register,
+        comparison: Comparison,
+        value: u16,
+    },
+    Changes(Register),
+}
+
+impl Condition {
+    pub fn parse(text: &str) -> Result<Condition, String> {
+        let words = tokenize(text)?;
+        return match words.as_slice() {
+            [register, changes] if changes.eq_ignore_ascii_case("changes") => {
+                Ok(Condition::Changes(parse_register(register)?))
+            }
+            [register, comparison, value] => Ok(Condition::Compare {
+                register: parse_register(register)?,
+                comparison: Comparison::parse(comparison).ok_or_else(|| {
+                    format!("expected one of == != < <= > >=, got {}", comparison)
+                })?,
+                value: parse_literal(value)?,
+            }),
+            _ => Err(format!(
+                "expected a condition like V3 == 0x1F or I changes, got {}",
+                text.trim()
+            )),
+        };
+    }
+
+    // last is what a changes condition saw the time before, it's updated every call
+    pub fn holds(&self, pc: u16, i: u16, v: &[u8; 16], last: &Cell<Option<u16>>) -> bool {
+        return match *self {
+            Condition::Compare {
+                register,
+                comparison,
+                value,
+            } => comparison.test(register.read(pc, i, v), value),
+            Condition::Changes(register) => {
+                let value = register.read(pc, i, v);
+                last.replace(Some(value)).is_some_and(|old| old != value)
+            }
+        };
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return match self {
+            Condition::Compare {
+                register,
+                comparison,
+                value,
+            } => write!(f, "{} {} 0x{:X}", register, comparison.symbol(), value),
+            Condition::Changes(register) => write!(f, "{} changes", register),
+        };
+    }
+}
+
+fn parse_register(word: &str) -> Result<Register, String> {
+    return Register::parse(word)
+        .ok_or_else(|| format!("expected a register V0-VF, I or PC, got {}", word));
+}
+
+// 0x1F is hex, 31 is decimal
+pub fn parse_literal(word: &str) -> Result<u16, String> {
+    let parsed = match word.strip_prefix("0x").or_else(|| word.strip_prefix("0X")) {
+        Some(hex) => u16::from_str_radix(hex, 16),
+        None => word.parse(),
+    };
+    return parsed.map_err(|_| format!("expected a number like 0x1F or 31, got {}", word));
+}
+
+// Words and operators, so V3==0x1F reads the same as V3 == 0x1F
+fn tokenize(text: &str) -> Result<Vec<String>, String> {
+    let mut words: Vec<String> = vec![];
+    let mut last_kind = None;
+    for c in text.chars() {
+        let kind = if c.is_ascii_alphanumeric() {
+            Some(false)
+        } else if "=!<>".contains(c) {
+            Some(true)
+        } else if c.is_whitespace() {
+            None
+        } else {
+            return Err(format!("unexpected {} in {}", c, text.trim()));
+        };
+        match (kind, words.last_mut()) {
+            (None, _) => {}
+            (Some(_), Some(word)) if kind == last_kind => word.push(c),
+            (Some(_), _) => words.push(c.to_string()),
+        }
+        last_kind = kind;
+    }
+    return Ok(words);
+}
+
+// Stops at an address, when a condition holds, or both, e.g. "0x2A4 when V3 == 0x1F"
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Breakpoint {
+    pub at: Option<u16>,
+    pub when: Option<Condition>,
+}
+
+impl Breakpoint {
+    pub fn at(addr: u16) -> Breakpoint {
+        return Breakpoint {
+            at: Some(addr),
+            when: None,
+        };
+    }
+
+    // [at] ADDRESS [when CONDITION], or when CONDITION
+    pub fn parse(text: &str) -> Result<Breakpoint, String> {
+        let text = text.trim();
+        let lower = text.to_ascii_lowercase();
+        let (address, condition) = match lower.find("when") {
+            Some(index) => (&text[..index], Some(&text[index + "when".len()..])),
+            None => (text, None),
+        };
+        let address = address.trim();
+        let address = match address.get(..3) {
+            Some(at) if at.eq_ignore_ascii_case("at ") => address[3..].trim(),
+            _ => address,
+        };
+        let at = match address {
+            "" => None,
+            address => Some(parse_literal(address)?),
+        };
+        let when = condition.map(Condition::parse).transpose()?;
+        if at.is_none() && when.is_none() {
+            return Err("expected an address, a condition or both".to_string());
+        }
+        return Ok(Breakpoint { at, when });
+    }
+}
+
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return match (self.at, self.when) {
+            (Some(at), Some(when)) => write!(f, "0x{:03X} when {}", at, when),
+            (Some(at), None) => write!(f, "0x{:03X}", at),
+            (None, Some(when)) => write!(f, "when {}", when),
+            (None, None) => write!(f, "never"),
+        };
+    }
+}
+
+// The breakpoints a pre-step hook checks, each with what its changes condition last saw
+#[derive(Debug, Default)]
+pub struct BreakpointList {
+    entries: Vec<(Breakpoint, Cell<Option<u16>>)>,
+}
+
+impl BreakpointList {
+    pub fn new(breakpoints: &[Breakpoint]) -> BreakpointList {
+        let mut list = BreakpointList::default();
+        breakpoints
+            .iter()
+            .for_each(|breakpoint| list.add(*breakpoint));
+        return list;
+    }
+
+    pub fn add(&mut self, breakpoint: Breakpoint) {
+        self.entries.push((breakpoint, Cell::new(None)));
+    }
+
+    pub fn remove(&mut self, index: usize) -> Option<Breakpoint> {
+        if index >= self.entries.len() {
+            return None;
+        }
+        return Some(self.entries.remove(index).0);
+    }
+
+    // Drops the plain breakpoints at addr, returns false if there weren't any
+    pub fn remove_at(&mut self, addr: u16) -> bool {
+        let before = self.entries.len();
+        self.entries
+            .retain(|(breakpoint, _)| *breakpoint != Breakpoint::at(addr));
+        return self.entries.len() != before;
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Breakpoint> {
+        return self.entries.iter().map(|(breakpoint, _)| breakpoint);
+    }
+
+    pub fn len(&self) -> usize {
+        return self.entries.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.entries.is_empty();
+    }
+
+    // The first breakpoint that stops the instruction about to run. Every condition at this
+    // address is checked, so each changes condition keeps up with its register.
+    pub fn check(&self, view: &CpuView) -> Option<usize> {
+        let mut hit = None;
+        for (index, (breakpoint, last)) in self.entries.iter().enumerate() {
+            if breakpoint.at.is_some_and(|at| at != view.pc) {
+                continue;
+            }
+            let holds = breakpoint
+                .when
+                .is_none_or(|when| when.holds(view.pc, view.i, view.v, last));
+            if holds && hit.is_none() {
+                hit = Some(index);
+            }
+        }
+        return hit;
+    }
+
+    // Changes conditions start over from the registers as they are now, so what the user did in
+    // the debugger doesn't count as a change
+    pub fn rebase(&self, pc: u16, i: u16, v: &[u8; 16]) {
+        for (breakpoint, last) in &self.entries {
+            if let Some(Condition::Changes(register)) = breakpoint.when {
+                last.set(Some(register.read(pc, i, v)));
+            }
+        }
+    }
+}
+
+// Shown in the debugger's register pane all the time, a register's value or whether a
+// comparison holds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Watch {
+    Value(Register),
+    Test(Condition),
+}
+
+impl Watch {
+    pub fn parse(text: &str) -> Result<Watch, String> {
+        if let Some(register) = Register::parse(text.trim()) {
+            return Ok(Watch::Value(register));
+        }
+        return match Condition::parse(text)? {
+            Condition::Changes(_) => Err("watches can't use changes, watch the register".into()),
+            condition => Ok(Watch::Test(condition)),
+        };
+    }
+
+    pub fn show(&self, pc: u16, i: u16, v: &[u8; 16]) -> String {
+        return match self {
+            Watch::Value(register) => format!(
+                "{} = {}",
+                register,
+                register.format_value(register.read(pc, i, v))
+            ),
+            Watch::Test(condition) => {
+                let holds = condition.holds(pc, i, v, &Cell::new(None));
+                format!("{}: {}", condition, if holds { "yes" } else { "no" })
+            }
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chip8_core::OpCodes;
+
+    use super::*;
+
+    fn compare(register: Register, comparison: Comparison, value: u16) -> Condition {
+        return Condition::Compare {
+            register,
+            comparison,
+            value,
+        };
+    }
+
+    fn view(pc: u16, i: u16, v: &[u8; 16]) -> CpuView<'_> {
+        return CpuView {
+            pc,
+            i,
+            v,
+            next_opcode: OpCodes::_00E0,
+        };
+    }
+
+    #[test]
+    fn parses_conditions() {
+        let cases = [
+            ("V3 == 0x1F", compare(Register::V(3), Comparison::Eq, 0x1F)),
+            ("vf != 31", compare(Register::V(0xF), Comparison::Ne, 31)),
+            ("I<0X300", compare(Register::I, Comparison::Lt, 0x300)),
+            ("pc <= 512", compare(Register::Pc, Comparison::Le, 512)),
+            ("  VA>0  ", compare(Register::V(0xA), Comparison::Gt, 0)),
+            ("V0 >= 0xff", compare(Register::V(0), Comparison::Ge, 0xFF)),
+            ("I changes", Condition::Changes(Register::I)),
+            ("V7 CHANGES", Condition::Changes(Register::V(7))),
+        ];
+        for (text, expected) in cases {
+            assert_eq!(Condition::parse(text), Ok(expected), "{}", text);
+        }
+        // Display gives back something parse reads the same way
+        for (_, condition) in cases {
+            assert_eq!(
+                Condition::parse(&condition.to_string()),
+                Ok(condition),
+                "{}",
+                condition
+            );
+        }
+        assert_eq!(
+            compare(Register::V(3), Comparison::Eq, 0x1F).to_string(),
+            "V3 == 0x1F"
+        );
+    }
+
+    #[test]
+    fn rejects_bad_conditions() {
+        let cases = [
+            (
+                "",
+                "expected a condition like V3 == 0x1F or I changes, got ",
+            ),
+            ("V3 ==", "expected a condition like"),
+            ("V3 == 1 2", "expected a condition like"),
+            ("VG == 1", "expected a register V0-VF, I or PC, got VG"),
+            ("V10 == 1", "expected a register"),
+            ("DT == 1", "expected a register"),
+            ("V3 = 1", "expected one of == != < <= > >=, got ="),
+            ("V3 =< 1", "expected one of"),
+            ("V3 == 0x", "expected a number like 0x1F or 31, got 0x"),
+            ("V3 == 0x10000", "expected a number"),
+            ("V3 == 1F", "expected a number"),
+            ("V3 == -1", "unexpected - in V3 == -1"),
+            ("V3 grows", "expected a condition like"),
+        ];
+        for (text, error) in cases {
+            let err = Condition::parse(text).unwrap_err();
+            assert!(err.starts_with(error), "{}: {}", text, err);
+        }
+    }
+
+    #[test]
+    fn literals_are_hex_or_decimal() {
+        assert_eq!(parse_literal("0x2A4"), Ok(0x2A4));
+        assert_eq!(parse_literal("0XfF"), Ok(0xFF));
+        assert_eq!(parse_literal("676"), Ok(676));
+        assert_eq!(parse_literal("65535"), Ok(u16::MAX));
+        for bad in ["65536", "2A4", "0x", "", "x1"] {
+            assert!(parse_literal(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn comparisons_evaluate() {
+        let mut v = [0; 16];
+        v[3] = 0x1F;
+        let last = Cell::new(None);
+        let holds = |condition: &str| {
+            return Condition::parse(condition)
+                .unwrap()
+                .holds(0x2A4, 0x300, &v, &last);
+        };
+        for condition in [
+            "V3 == 0x1F",
+            "V3 != 0x1E",
+            "V3 < 32",
+            "V3 <= 31",
+            "V3 > 30",
+            "V3 >= 31",
+            "I == 0x300",
+            "PC == 0x2A4",
+            "V0 == 0",
+        ] {
+            assert!(holds(condition), "{}", condition);
+        }
+        for condition in [
+            "V3 == 0x1E",
+            "V3 != 0x1F",
+            "V3 < 31",
+            "V3 <= 30",
+            "V3 > 31",
+            "V3 >= 32",
+            "I < 0x300",
+        ] {
+            assert!(!holds(condition), "{}", condition);
+        }
+    }
+
+    #[test]
+    fn changes_compares_with_the_last_check() {
+        let condition = Condition::Changes(Register::I);
+        let last = Cell::new(None);
+        let v = [0; 16];
+        // Nothing to compare with the first time
+        assert!(!condition.holds(0x200, 0x300, &v, &last));
+        assert!(!condition.holds(0x202, 0x300, &v, &last));
+        assert!(condition.holds(0x204, 0x302, &v, &last));
+        assert!(!condition.holds(0x206, 0x302, &v, &last));
+    }
+
+    #[test]
+    fn parses_breakpoints() {
+        let when = Some(compare(Register::V(3), Comparison::Eq, 0x1F));
+        let cases = [
+            ("0x2A4", Breakpoint::at(0x2A4)),
+            ("at 0x2A4", Breakpoint::at(0x2A4)),
+            (
+                "at 0x2A4 when V3 == 0x1F",
+                Breakpoint {
+                    at: Some(0x2A4),
+                    when,
+                },
+            ),
+            (
+                "0x2a4 WHEN v3==31",
+                Breakpoint {
+                    at: Some(0x2A4),
+                    when,
+                },
+            ),
+            (
+                "when I changes",
+                Breakpoint {
+                    at: None,
+                    when: Some(Condition::Changes(Register::I)),
+                },
+            ),
+        ];
+        for (text, expected) in cases {
+            assert_eq!(Breakpoint::parse(text), Ok(expected), "{}", text);
+            assert_eq!(Breakpoint::parse(&expected.to_string()), Ok(expected));
+        }
+        for bad in ["", "at", "when", "0x2A4 when", "2A4", "at 0x2A4 V3 == 1"] {
+            assert!(Breakpoint::parse(bad).is_err(), "{}", bad);
+        }
+        assert_eq!(cases[2].1.to_string(), "0x2A4 when V3 == 0x1F");
+    }
+
+    #[test]
+    fn breakpoint_list_checks_every_condition() {
+        let v = [0; 16];
+        let list = BreakpointList::new(&[
+            Breakpoint::parse("0x204").unwrap(),
+            Breakpoint::parse("when I changes").unwrap(),
+            Breakpoint::parse("0x206 when V0 == 1").unwrap(),
+        ]);
+        assert_eq!(list.check(&view(0x200, 0x300, &v)), None);
+        assert_eq!(list.check(&view(0x202, 0x300, &v)), None);
+        // Both the address and the change hit, the first one wins but I is still taken in
+        assert_eq!(list.check(&view(0x204, 0x302, &v)), Some(0));
+        assert_eq!(list.check(&view(0x206, 0x302, &v)), None);
+        let mut v1 = v;
+        v1[0] = 1;
+        assert_eq!(list.check(&view(0x206, 0x302, &v1)), Some(2));
+
+        // A change made from the debugger isn't a hit
+        list.rebase(0x208, 0x400, &v);
+        assert_eq!(list.check(&view(0x208, 0x400, &v)), None);
+    }
+
+    #[test]
+    fn breakpoint_list_edits() {
+        let mut list = BreakpointList::default();
+        assert!(list.is_empty());
+        list.add(Breakpoint::at(0x200));
+        list.add(Breakpoint::parse("0x200 when I changes").unwrap());
+        assert!(list.remove_at(0x200));
+        assert!(!list.remove_at(0x200));
+        assert_eq!(list.len(), 1);
+        assert_eq!(
+            list.remove(0).map(|breakpoint| breakpoint.to_string()),
+            Some("0x200 when I changes".to_string())
+        );
+        assert_eq!(list.remove(0), None);
+    }
+
+    #[test]
+    fn watches_show_values_and_tests() {
+        let mut v = [0; 16];
+        v[3] = 0x1F;
+        let show = |text: &str| Watch::parse(text).unwrap().show(0x2A4, 0x30, &v);
+        assert_eq!(show("V3"), "V3 = 1F");
+        assert_eq!(show("i"), "I = 030");
+        assert_eq!(show("PC"), "PC = 2A4");
+        assert_eq!(show("V3 == 0x1F"), "V3 == 0x1F: yes");
+        assert_eq!(show("V3 < 4"), "V3 < 0x4: no");
+        assert!(Watch::parse("I changes").is_err());
+        assert!(Watch::parse("V3 ==").is_err());
+    }
+}
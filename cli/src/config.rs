@@ -0,0 +1,364 @@
+use std::{
+    collections::BTreeMap,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use chip8_core::{Chip8Quirks, Rgb};
+use clap::{parser::ValueSource, ArgMatches};
+use serde::{de::Error, Deserialize, Deserializer};
+
+use crate::{
+    args::{parse_color, Args, QuirksPreset},
+    keymap::KeyMap,
+};
+
+pub const CONFIG_FILE: &str = "config.toml";
+
+// Settings for every ROM in ~/.config/chip8/config.toml, or for one ROM in <rom>.toml next to it:
+//
+//     speed = 20
+//
+//     [quirks]
+//     preset = "schip"
+//     vf_reset = false
+//
+//     [palette]
+//     fg = "#33FF33"
+//
+//     [keymap]
+//     x = "0"
+//
+// Every setting is optional. Bad values are caught while parsing so the error points at the line.
+#[derive(Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    #[serde(default, deserialize_with = "speed")]
+    pub speed: Option<usize>,
+    #[serde(default)]
+    pub quirks: QuirksConfig,
+    #[serde(default)]
+    pub palette: PaletteConfig,
+    // Applied in order on top of the default layout
+    #[serde(default, deserialize_with = "bindings")]
+    pub keymap: Vec<(char, u8)>,
+}
+
+// A preset plus the quirks to change from it
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct QuirksConfig {
+    pub preset: Option<QuirksPreset>,
+    // Chip8Quirks::logic_resets_vf
+    pub vf_reset: Option<bool>,
+    pub jump_uses_vx: Option<bool>,
+    // Chip8Quirks::i_overflow_flag
+    pub i_overflow: Option<bool>,
+}
+
+impl QuirksConfig {
+    pub fn apply(&self, mut quirks: Chip8Quirks) -> Chip8Quirks {
+        quirks.logic_resets_vf = self.vf_reset.unwrap_or(quirks.logic_resets_vf);
+        quirks.jump_uses_vx = self.jump_uses_vx.unwrap_or(quirks.jump_uses_vx);
+        quirks.i_overflow_flag = self.i_overflow.unwrap_or(quirks.i_overflow_flag);
+        return quirks;
+    }
+
+    fn merge(self, other: QuirksConfig) -> QuirksConfig {
+        return QuirksConfig {
+            preset: other.preset.or(self.preset),
+            vf_reset: other.vf_reset.or(self.vf_reset),
+            jump_uses_vx: other.jump_uses_vx.or(self.jump_uses_vx),
+            i_overflow: other.i_overflow.or(self.i_overflow),
+        };
+    }
+}
+
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct PaletteConfig {
+    #[serde(default, deserialize_with = "color")]
+    pub fg: Option<Rgb>,
+    #[serde(default, deserialize_with = "color")]
+    pub bg: Option<Rgb>,
+}
+
+fn speed<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<usize>, D::Error> {
+    let speed = usize::deserialize(deserializer)?;
+    if speed == 0 {
+        return Err(D::Error::custom("speed must be at least 1"));
+    }
+    return Ok(Some(speed));
+}
+
+fn color<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Rgb>, D::Error> {
+    let value = String::deserialize(deserializer)?;
+    return parse_color(&value).map(Some).map_err(D::Error::custom);
+}
+
+// A table of key = "hex digit", e.g. x = "0". The key and the digit are checked on their own so
+// an error points at the binding rather than the table.
+fn bindings<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<(char, u8)>, D::Error> {
+    let table = BTreeMap::<BoundChar, KeypadKey>::deserialize(deserializer)?;
+    return Ok(table
+        .into_iter()
+        .map(|(BoundChar(c), KeypadKey(key))| (c, key))
+        .collect());
+}
+
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+struct BoundChar(char);
+
+impl<'de> Deserialize<'de> for BoundChar {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        let mut chars = name.chars();
+        return match (chars.next(), chars.next()) {
+            (Some(c), None) => Ok(BoundChar(c)),
+            _ => Err(D::Error::custom(format!(
+                "expected a single key like x, got {}",
+                name
+            ))),
+        };
+    }
+}
+
+struct KeypadKey(u8);
+
+impl<'de> Deserialize<'de> for KeypadKey {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        return match u8::from_str_radix(&value, 16) {
+            Ok(key @ 0..=0xF) => Ok(KeypadKey(key)),
+            _ => Err(D::Error::custom(format!(
+                "expected a keypad key from 0 to F, got {}",
+                value
+            ))),
+        };
+    }
+}
+
+impl Config {
+    // path is only for the error message
+    pub fn parse(text: &str, path: &Path) -> Result<Config, String> {
+        return toml::from_str(text).map_err(|err| format!("{}: {}", path.display(), err));
+    }
+
+    // A missing file is an empty config
+    pub fn load(path: &Path) -> Result<Config, String> {
+        return match std::fs::read_to_string(path) {
+            Ok(text) => Config::parse(&text, path),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(Config::default()),
+            Err(err) => Err(format!("Could not read {}: {}", path.display(), err)),
+        };
+    }
+
+    // The global config, from $XDG_CONFIG_HOME or ~/.config
+    pub fn load_global() -> Result<Config, String> {
+        return match global_config_path() {
+            Some(path) => Config::load(&path),
+            None => Ok(Config::default()),
+        };
+    }
+
+    // Settings in other win over the ones in self, key bindings add up
+    pub fn merge(mut self, other: Config) -> Config {
+        self.speed = other.speed.or(self.speed);
+        self.quirks = self.quirks.merge(other.quirks);
+        self.palette = PaletteConfig {
+            fg: other.palette.fg.or(self.palette.fg),
+            bg: other.palette.bg.or(self.palette.bg),
+        };
+        self.keymap.extend(other.keymap);
+        return self;
+    }
+
+    // Fills in the settings that didn't come from the command line. matches is what args was
+    // parsed from.
+    pub fn apply(&self, args: &mut Args, matches: &ArgMatches) {
+        let from_cli = |id: &str| matches.value_source(id) == Some(ValueSource::CommandLine);
+        if let (Some(speed), false) = (self.speed, from_cli("speed")) {
+            args.speed = speed;
+        }
+        // --quirks replaces the whole section, a preset with tweaks from a file would be a surprise
+        if !from_cli("quirks") {
+            args.quirks = self.quirks.preset.unwrap_or(args.quirks);
+            args.quirk_overrides = self.quirks;
+        }
+        if !from_cli("fg") {
+            args.fg = self.palette.fg;
+        }
+        if !from_cli("bg") {
+            args.bg = self.palette.bg;
+        }
+        // The file's bindings go under the --keymap ones
+        let mut keymap = KeyMap::default();
+        for (c, key) in &self.keymap {
+            keymap.bind(*c, *key);
+        }
+        let overrides = matches
+            .get_raw("keymap")
+            .and_then(|mut values| values.next_back())
+            .filter(|_| from_cli("keymap"))
+            .and_then(|value| value.to_str());
+        if let Some(overrides) = overrides {
+            // clap already checked them
+            keymap = keymap.with_overrides(overrides).unwrap();
+        }
+        args.keymap = keymap;
+    }
+}
+
+pub fn global_config_path() -> Option<PathBuf> {
+    let config_dir = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    return Some(config_dir.join("chip8").join(CONFIG_FILE));
+}
+
+// pong.ch8 reads pong.ch8.toml
+pub fn rom_config_path(rom: &Path) -> PathBuf {
+    let mut path = rom.as_os_str().to_owned();
+    path.push(".toml");
+    return PathBuf::from(path);
+}
+
+// The settings to run a ROM with, the defaults overridden by the global config, then the ROM's
+// config, then the command line. Without a ROM only the global config applies.
+pub fn merged_args(
+    args: &Args,
+    matches: &ArgMatches,
+    global: &Config,
+    rom: Option<&Path>,
+) -> Result<Args, String> {
+    let config = match rom {
+        Some(rom) => global.clone().merge(Config::load(&rom_config_path(rom))?),
+        None => global.clone(),
+    };
+    let mut args = args.clone();
+    config.apply(&mut args, matches);
+    return Ok(args);
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::{CommandFactory, FromArgMatches};
+    use crossterm::event::KeyCode;
+
+    use super::*;
+
+    fn parse(text: &str) -> Result<Config, String> {
+        return Config::parse(text, Path::new("pong.ch8.toml"));
+    }
+
+    fn apply(configs: &[&str], cli: &[&str]) -> Args {
+        let matches = Args::command()
+            .try_get_matches_from(["chip8-cli"].iter().chain(cli).chain(&["pong.ch8"]))
+            .unwrap();
+        let mut args = Args::from_arg_matches(&matches).unwrap();
+        let config = configs
+            .iter()
+            .map(|text| parse(text).unwrap())
+            .fold(Config::default(), Config::merge);
+        config.apply(&mut args, &matches);
+        return args;
+    }
+
+    #[test]
+    fn later_layers_win() {
+        let global = "speed = 20\n[palette]\nfg = \"#33FF33\"\nbg = \"000000\"";
+        let rom = "speed = 30\n[palette]\nbg = \"#0A1A0A\"";
+        assert_eq!(apply(&[], &[]).speed, crate::args::DEFAULT_SPEED);
+        assert_eq!(apply(&[global], &[]).speed, 20);
+
+        let args = apply(&[global, rom], &[]);
+        assert_eq!(args.speed, 30);
+        assert_eq!(args.fg, Some(Rgb::new(0x33, 0xFF, 0x33)));
+        assert_eq!(args.bg, Some(Rgb::new(0x0A, 0x1A, 0x0A)));
+
+        let args = apply(&[global, rom], &["--speed", "40", "--fg", "FFFFFF"]);
+        assert_eq!(args.speed, 40);
+        assert_eq!(args.fg, Some(Rgb::new(0xFF, 0xFF, 0xFF)));
+        assert_eq!(args.bg, Some(Rgb::new(0x0A, 0x1A, 0x0A)));
+        // Giving the default on the command line still counts
+        assert_eq!(apply(&[global], &["--speed", "11"]).speed, 11);
+    }
+
+    #[test]
+    fn quirks_tweak_the_preset() {
+        let config = "[quirks]\npreset = \"schip\"\njump_uses_vx = false\ni_overflow = true";
+        let args = apply(&[config], &[]);
+        assert_eq!(args.quirks, QuirksPreset::Schip);
+        let expected = Chip8Quirks {
+            jump_uses_vx: false,
+            i_overflow_flag: true,
+            ..Chip8Quirks::schip()
+        };
+        assert_eq!(args.cpu_quirks(), expected);
+
+        // The ROM's file only changes vf_reset, the rest still comes from the global one
+        let args = apply(&[config, "[quirks]\nvf_reset = false"], &[]);
+        assert_eq!(
+            args.cpu_quirks(),
+            Chip8Quirks {
+                logic_resets_vf: false,
+                ..expected
+            }
+        );
+
+        let args = apply(&[config], &["--quirks", "chip8"]);
+        assert_eq!(args.cpu_quirks(), Chip8Quirks::chip8());
+    }
+
+    #[test]
+    fn keymap_bindings_stack() {
+        let global = "[keymap]\nx = \"0\"\np = \"f\"";
+        let rom = "[keymap]\np = \"e\"";
+        let args = apply(&[global, rom], &["--keymap", "x=1"]);
+        let key = |c| args.keymap.key(KeyCode::Char(c));
+        assert_eq!(
+            (key('x'), key('p'), key('1')),
+            (Some(0x1), Some(0xE), Some(0x1))
+        );
+        assert_eq!(apply(&[], &[]).keymap, KeyMap::default());
+    }
+
+    #[test]
+    fn bad_values_point_at_the_line() {
+        for (text, message) in [
+            ("\n\nspeed = 0", "speed must be at least 1"),
+            ("speed = \"fast\"", "invalid type"),
+            (
+                "[palette]\n\nfg = \"#12345\"",
+                "expected a color like #33FF33",
+            ),
+            (
+                "[keymap]\nx = \"g\"",
+                "expected a keypad key from 0 to F, got g",
+            ),
+            (
+                "[keymap]\nx = \"1\"\nxy = \"1\"",
+                "expected a single key like x, got xy",
+            ),
+            ("[quirks]\nshift = true", "unknown field `shift`"),
+            ("[quirks]\npreset = \"cosmac\"", "unknown variant `cosmac`"),
+        ] {
+            let err = parse(text).unwrap_err();
+            let line = format!("line {}", text.lines().count());
+            assert!(err.starts_with("pong.ch8.toml: "), "{}", err);
+            assert!(err.contains(&line), "{} in {}", line, err);
+            assert!(err.contains(message), "{} in {}", message, err);
+        }
+    }
+
+    #[test]
+    fn missing_files_are_empty() {
+        let path = std::env::temp_dir().join("chip8-no-such-config.toml");
+        assert_eq!(Config::load(&path), Ok(Config::default()));
+        assert_eq!(
+            rom_config_path(Path::new("roms/pong.ch8")),
+            PathBuf::from("roms/pong.ch8.toml")
+        );
+    }
+}
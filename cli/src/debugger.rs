@@ -0,0 +1,714 @@
+use std::{
+    cell::{Cell, RefCell},
+    io::Write,
+    rc::Rc,
+};
+
+use crate::condition::{Breakpoint, BreakpointList, Watch};
+use chip8_core::{
+    disasm::disassemble, Chip8CPU, Chip8Error, Chip8Input, Chip8Screen, CpuState, HookAction, CPU,
+    PGRM_LOAD_START_ADDR, SCREEN_HEIGHT, SCREEN_WIDTH,
+};
+
+use crossterm::{
+    cursor::MoveTo,
+    event::KeyCode,
+    queue,
+    style::{Attribute, Print, SetAttribute},
+};
+
+// The panes sit to the right of and below the 64x32 game screen
+const PANE_COLUMN: u16 = SCREEN_WIDTH as u16 + 2;
+const PANE_WIDTH: usize = 40;
+const DISASM_ROW: u16 = 10;
+const DISASM_ROWS: usize = SCREEN_HEIGHT as usize - DISASM_ROW as usize;
+const STATUS_ROW: u16 = SCREEN_HEIGHT as u16;
+const MEMORY_ROW: u16 = STATUS_ROW + 1;
+const MEMORY_ROWS: u16 = 8;
+const MEMORY_END: u16 = 0x1000;
+// The rows between the registers and the disassembly
+const MAX_WATCHES: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Focus {
+    // Keys go to the keypad, Enter breaks into the debugger
+    Game,
+    // Keys go to the debugger, the CPU runs after c until a breakpoint or s
+    Debugger,
+}
+
+// A line of a pane, highlighted lines are drawn in reverse video
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PaneLine {
+    pub text: String,
+    pub highlight: bool,
+}
+
+impl PaneLine {
+    fn plain(text: String) -> PaneLine {
+        return PaneLine {
+            text,
+            highlight: false,
+        };
+    }
+}
+
+// Breakpoints live in the CPU's pre-step hook, the rest is what the panes show and where
+pub struct Debugger {
+    breakpoints: Rc<RefCell<BreakpointList>>,
+    // Which breakpoint the hook last stopped at
+    hit: Rc<Cell<Option<usize>>>,
+    watches: Vec<Watch>,
+    focus: Focus,
+    // Disassembly cursor, None follows the PC
+    cursor: Option<u16>,
+    memory_start: u16,
+    // Hex digits typed after g
+    goto: Option<String>,
+    // A command typed after :
+    command: Option<String>,
+    message: String,
+    program_len: usize,
+}
+
+impl Debugger {
+    pub fn new(program_len: usize) -> Debugger {
+        return Debugger {
+            breakpoints: Rc::new(RefCell::new(BreakpointList::default())),
+            hit: Rc::new(Cell::new(None)),
+            watches: vec![],
+            focus: Focus::Game,
+            cursor: None,
+            memory_start: PGRM_LOAD_START_ADDR,
+            goto: None,
+            command: None,
+            message: String::new(),
+            program_len,
+        };
+    }
+
+    pub fn install<TScreen: Chip8Screen, TInput: Chip8Input>(
+        &self,
+        cpu: &mut CPU<'_, TScreen, TInput>,
+    ) {
+        let breakpoints = self.breakpoints.clone();
+        let hit = self.hit.clone();
+        cpu.set_pre_step_hook(Box::new(move |view| {
+            hit.set(breakpoints.borrow().check(view));
+            if hit.get().is_some() {
+                return HookAction::Pause;
+            }
+            return HookAction::Continue;
+        }));
+    }
+
+    pub fn focus(&self) -> Focus {
+        return self.focus;
+    }
+
+    // The addresses with a breakpoint, conditional or not
+    pub fn breakpoints(&self) -> Vec<u16> {
+        let mut addresses = self
+            .breakpoints
+            .borrow()
+            .iter()
+            .filter_map(|breakpoint| breakpoint.at)
+            .collect::<Vec<_>>();
+        addresses.sort();
+        addresses.dedup();
+        return addresses;
+    }
+
+    // Only plain breakpoints, conditional ones at the same address stay
+    pub fn toggle_breakpoint(&self, addr: u16) {
+        let mut breakpoints = self.breakpoints.borrow_mut();
+        if !breakpoints.remove_at(addr) {
+            breakpoints.add(Breakpoint::at(addr));
+        }
+    }
+
+    pub fn add_breakpoint(&self, breakpoint: Breakpoint) {
+        self.breakpoints.borrow_mut().add(breakpoint);
+    }
+
+    pub fn watches(&self) -> &[Watch] {
+        return &self.watches;
+    }
+
+    // Changes conditions start watching from here rather than from before the debugger stepped
+    fn rebase<TScreen: Chip8Screen, TInput: Chip8Input>(&self, cpu: &CPU<'_, TScreen, TInput>) {
+        let state = cpu.state();
+        self.breakpoints
+            .borrow()
+            .rebase(state.pc, state.i, &state.v);
+    }
+
+    // break ADDRESS [when CONDITION], break when CONDITION, delete N, list, watch EXPRESSION and
+    // unwatch N, or their first letters. The result ends up in the message.
+    pub fn run_command(&mut self, command: &str) {
+        let command = command.trim();
+        let (name, rest) = command
+            .split_once(char::is_whitespace)
+            .unwrap_or((command, ""));
+        let rest = rest.trim();
+        let result = match name {
+            "break" | "b" => Breakpoint::parse(rest).map(|breakpoint| {
+                self.add_breakpoint(breakpoint);
+                return format!(
+                    "Breakpoint {} {}",
+                    self.breakpoints.borrow().len(),
+                    breakpoint
+                );
+            }),
+            "delete" | "d" => parse_number(rest).and_then(|number| {
+                let removed = self.breakpoints.borrow_mut().remove(number - 1);
+                return removed
+                    .map(|breakpoint| format!("Deleted breakpoint {}", breakpoint))
+                    .ok_or_else(|| format!("No breakpoint {}", number));
+            }),
+            "list" | "l" => {
+                let breakpoints = self.breakpoints.borrow();
+                let listed = breakpoints
+                    .iter()
+                    .enumerate()
+                    .map(|(index, breakpoint)| format!("{}: {}", index + 1, breakpoint))
+                    .collect::<Vec<_>>();
+                Ok(match listed.is_empty() {
+                    true => "No breakpoints".to_string(),
+                    false => listed.join("  "),
+                })
+            }
+            "watch" | "w" if self.watches.len() == MAX_WATCHES => {
+                Err(format!("At most {} watches fit", MAX_WATCHES))
+            }
+            "watch" | "w" => Watch::parse(rest).map(|watch| {
+                self.watches.push(watch);
+                return format!("Watch {}", self.watches.len());
+            }),
+            "unwatch" | "u" => parse_number(rest).and_then(|number| {
+                if number > self.watches.len() {
+                    return Err(format!("No watch {}", number));
+                }
+                self.watches.remove(number - 1);
+                return Ok(format!("Deleted watch {}", number));
+            }),
+            "" => Ok(String::new()),
+            _ => Err(format!("Unknown command {}", name)),
+        };
+        self.message = result.unwrap_or_else(|err| err);
+    }
+
+    pub fn break_in<TScreen: Chip8Screen, TInput: Chip8Input>(
+        &mut self,
+        cpu: &mut CPU<'_, TScreen, TInput>,
+    ) {
+        cpu.pause();
+        self.focus = Focus::Debugger;
+        self.cursor = None;
+        self.message = format!("Paused at 0x{:03X}", cpu.pc());
+    }
+
+    // Runs a frame unless paused. Stopping at a breakpoint hands the keys to the debugger.
+    pub fn run_frame<TScreen: Chip8Screen, TInput: Chip8Input>(
+        &mut self,
+        cpu: &mut CPU<'_, TScreen, TInput>,
+        instructions: usize,
+    ) -> Result<(), Chip8Error> {
+        let was_running = !cpu.is_paused();
+        cpu.step_frame(instructions)?;
+        if was_running && cpu.is_paused() {
+            self.focus = Focus::Debugger;
+            self.cursor = None;
+            let breakpoint = self
+                .hit
+                .get()
+                .and_then(|index| self.breakpoints.borrow().iter().nth(index).copied());
+            self.message = match breakpoint.and_then(|breakpoint| breakpoint.when) {
+                Some(when) => format!("Breakpoint at 0x{:03X}, {}", cpu.pc(), when),
+                None => format!("Breakpoint at 0x{:03X}", cpu.pc()),
+            };
+        }
+        return Ok(());
+    }
+
+    pub fn handle_key<TScreen: Chip8Screen, TInput: Chip8Input>(
+        &mut self,
+        code: KeyCode,
+        cpu: &mut CPU<'_, TScreen, TInput>,
+    ) -> Result<(), Chip8Error> {
+        if let Some(command) = self.command.as_mut() {
+            match code {
+                KeyCode::Char(c) if command.len() < PANE_WIDTH => command.push(c),
+                KeyCode::Backspace => {
+                    command.pop();
+                }
+                KeyCode::Enter => {
+                    let command = self.command.take().unwrap();
+                    self.run_command(&command);
+                }
+                KeyCode::Esc => self.command = None,
+                _ => {}
+            }
+            return Ok(());
+        }
+        if let Some(goto) = self.goto.as_mut() {
+            match code {
+                KeyCode::Char(c) if c.is_ascii_hexdigit() && goto.len() < 3 => goto.push(c),
+                KeyCode::Backspace => {
+                    goto.pop();
+                }
+                KeyCode::Enter => {
+                    if let Ok(addr) = u16::from_str_radix(goto, 16) {
+                        self.cursor = Some(addr & !1);
+                        self.memory_start = addr & !0xF;
+                    }
+                    self.goto = None;
+                }
+                KeyCode::Esc => self.goto = None,
+                _ => {}
+            }
+            return Ok(());
+        }
+        self.message.clear();
+        match code {
+            KeyCode::Char('s') => {
+                // Resuming skips the breakpoint hook once so a step can leave a breakpoint
+                cpu.resume();
+                let result = cpu.step();
+                cpu.pause();
+                self.cursor = None;
+                self.rebase(cpu);
+                result?;
+            }
+            KeyCode::Char('c') => {
+                cpu.resume();
+                self.cursor = None;
+                self.rebase(cpu);
+            }
+            KeyCode::Char('b') => self.toggle_breakpoint(self.cursor.unwrap_or(cpu.pc())),
+            KeyCode::Char('g') => self.goto = Some(String::new()),
+            KeyCode::Char(':') => self.command = Some(String::new()),
+            KeyCode::Char('i') => self.memory_start = cpu.state().i & !0xF,
+            KeyCode::Up => self.cursor = Some(self.cursor.unwrap_or(cpu.pc()).saturating_sub(2)),
+            KeyCode::Down => {
+                self.cursor = Some((self.cursor.unwrap_or(cpu.pc()) + 2).min(MEMORY_END - 2))
+            }
+            KeyCode::PageUp => {
+                self.memory_start = self.memory_start.saturating_sub(MEMORY_ROWS * 16)
+            }
+            KeyCode::PageDown => {
+                self.memory_start =
+                    (self.memory_start + MEMORY_ROWS * 16).min(MEMORY_END - MEMORY_ROWS * 16)
+            }
+            KeyCode::Esc => {
+                cpu.resume();
+                self.focus = Focus::Game;
+                self.cursor = None;
+                self.rebase(cpu);
+            }
+            _ => {}
+        }
+        return Ok(());
+    }
+
+    pub fn registers_pane(&self, state: &CpuState) -> Vec<PaneLine> {
+        let mut lines = vec![PaneLine::plain(format!(
+            "PC 0x{:03X}  I 0x{:03X}",
+            state.pc, state.i
+        ))];
+        for (row, values) in state.v.chunks(4).enumerate() {
+            let registers = values
+                .iter()
+                .enumerate()
+                .map(|(col, value)| format!("V{:X} {:02X}", row * 4 + col, value))
+                .collect::<Vec<_>>();
+            lines.push(PaneLine::plain(registers.join("  ")));
+        }
+        lines.push(PaneLine::plain(format!(
+            "DT {:02X}  ST {:02X}",
+            state.delay_timer, state.sound_timer
+        )));
+        let stack = state
+            .stack
+            .iter()
+            .rev()
+            .map(|addr| format!("0x{:03X}", addr))
+            .collect::<Vec<_>>();
+        lines.push(PaneLine::plain(format!("Stack {}", stack.join(" "))));
+        for (number, watch) in self.watches.iter().enumerate() {
+            lines.push(PaneLine::plain(format!(
+                "W{} {}",
+                number + 1,
+                watch.show(state.pc, state.i, &state.v)
+            )));
+        }
+        return lines;
+    }
+
+    // Lines around the cursor, or the PC when there's no cursor. > marks the cursor, * a
+    // breakpoint and the instruction at the PC is highlighted.
+    pub fn disassembly_pane<TScreen: Chip8Screen, TInput: Chip8Input>(
+        &self,
+        cpu: &CPU<'_, TScreen, TInput>,
+        rows: usize,
+    ) -> Vec<PaneLine> {
+        let pc = cpu.pc();
+        let center = self.cursor.unwrap_or(pc);
+        let len = self
+            .program_len
+            .min(usize::from(MEMORY_END - PGRM_LOAD_START_ADDR));
+        // Read from memory rather than the ROM so patches and self-modifying code show up
+        let program = cpu
+            .memory_snapshot(PGRM_LOAD_START_ADDR, len)
+            .unwrap_or_default();
+        let lines = disassemble(&program, PGRM_LOAD_START_ADDR);
+        let Some(index) = lines.iter().position(|line| line.address == center) else {
+            return vec![PaneLine::plain(format!(
+                "0x{:03X} is outside the program",
+                center
+            ))];
+        };
+        let start = index.saturating_sub(rows / 2);
+        let breakpoints = self.breakpoints();
+        return lines
+            .iter()
+            .skip(start)
+            .take(rows)
+            .map(|line| {
+                let cursor = if self.focus == Focus::Debugger && line.address == center {
+                    '>'
+                } else {
+                    ' '
+                };
+                let breakpoint = if breakpoints.contains(&line.address) {
+                    '*'
+                } else {
+                    ' '
+                };
+                return PaneLine {
+                    text: format!("{}{} {}", cursor, breakpoint, line),
+                    highlight: line.address == pc,
+                };
+            })
+            .collect();
+    }
+
+    // 16 bytes a row, the byte at I is bracketed
+    pub fn memory_pane<TScreen: Chip8Screen, TInput: Chip8Input>(
+        &self,
+        cpu: &CPU<'_, TScreen, TInput>,
+    ) -> Vec<PaneLine> {
+        let i = cpu.state().i;
+        return (0..MEMORY_ROWS)
+            .map(|row| self.memory_start + row * 16)
+            .filter(|addr| *addr < MEMORY_END)
+            .map(|addr| {
+                let bytes = cpu.memory_snapshot(addr, 16).unwrap_or_default();
+                let mut text = format!("0x{:03X}", addr);
+                let mut after_i = false;
+                for (offset, byte) in (0u16..).zip(bytes.iter()) {
+                    let at_i = addr + offset == i;
+                    text.push(match (at_i, after_i) {
+                        (true, _) => '[',
+                        (false, true) => ']',
+                        (false, false) => ' ',
+                    });
+                    text.push_str(&format!("{:02X}", byte));
+                    after_i = at_i;
+                }
+                if after_i {
+                    text.push(']');
+                }
+                return PaneLine::plain(text);
+            })
+            .collect();
+    }
+
+    pub fn status_line(&self, paused: bool) -> String {
+        if let Some(goto) = &self.goto {
+            return format!("Go to address: 0x{}_", goto);
+        }
+        if let Some(command) = &self.command {
+            return format!(":{}_", command);
+        }
+        let help = match (self.focus, paused) {
+            (Focus::Game, _) => "Enter debugger",
+            (Focus::Debugger, true) => {
+                "s step  c continue  b breakpoint  g go to  i memory at I  : command  Esc run"
+            }
+            (Focus::Debugger, false) => "Running  s step  b breakpoint  Esc back to the game",
+        };
+        if self.message.is_empty() {
+            return help.to_string();
+        }
+        return format!("{}  {}", self.message, help);
+    }
+
+    pub fn draw<TScreen: Chip8Screen, TInput: Chip8Input, W: Write>(
+        &self,
+        cpu: &CPU<'_, TScreen, TInput>,
+        controls: &str,
+        out: &mut W,
+    ) -> std::io::Result<()> {
+        let state = cpu.state();
+        let status = match controls {
+            "" => self.status_line(cpu.is_paused()),
+            _ => format!("{}  {}", controls, self.status_line(cpu.is_paused())),
+        };
+        let panes = [
+            (PANE_COLUMN, 0, self.registers_pane(&state), PANE_WIDTH),
+            (
+                PANE_COLUMN,
+                DISASM_ROW,
+                self.disassembly_pane(cpu, DISASM_ROWS),
+                PANE_WIDTH,
+            ),
+            (
+                0,
+                STATUS_ROW,
+                vec![PaneLine::plain(status)],
+                SCREEN_WIDTH as usize + PANE_WIDTH,
+            ),
+            (0, MEMORY_ROW, self.memory_pane(cpu), SCREEN_WIDTH as usize),
+        ];
+        for (column, row, lines, width) in panes {
+            let height = if row == DISASM_ROW {
+                DISASM_ROWS
+            } else {
+                lines.len()
+            };
+            for index in 0..height {
+                let line = lines
+                    .get(index)
+                    .cloned()
+                    .unwrap_or(PaneLine::plain(String::new()));
+                // Padding to the full width clears whatever was drawn there last frame
+                let text = format!("{:<width$.width$}", line.text, width = width);
+                let attribute = if line.highlight {
+                    Attribute::Reverse
+                } else {
+                    Attribute::NoReverse
+                };
+                queue!(
+                    out,
+                    MoveTo(column, row + index as u16),
+                    SetAttribute(attribute),
+                    Print(text),
+                    SetAttribute(Attribute::NoReverse),
+                )?;
+            }
+        }
+        return out.flush();
+    }
+}
+
+// Breakpoints and watches are numbered from 1, as list shows them
+fn parse_number(text: &str) -> Result<usize, String> {
+    return match text.parse() {
+        Ok(number) if number > 0 => Ok(number),
+        _ => Err(format!("Expected a number from list, got {}", text)),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use chip8_core::{OpCodes, ProgramBuilder, TestCPU};
+
+    use super::*;
+
+    fn program() -> Vec<u8> {
+        let mut builder = ProgramBuilder::new();
+        let top = builder.label("top");
+        builder
+            .push(OpCodes::_6XNN { x: 0x0, nn: 0x00 })
+            .bind(top)
+            .push(OpCodes::_7XNN { x: 0x0, nn: 0x01 })
+            .push(OpCodes::_ANNN { nnn: 0x210 })
+            .push(OpCodes::_7XNN { x: 0x1, nn: 0x02 })
+            .jump_to(top);
+        return builder.finish();
+    }
+
+    fn debugger_for(cpu: &mut TestCPU) -> Debugger {
+        let program = program();
+        cpu.load_program(&program).unwrap();
+        let debugger = Debugger::new(program.len());
+        debugger.install(cpu);
+        return debugger;
+    }
+
+    #[test]
+    fn breakpoint_set_in_the_disassembly_stops_execution() {
+        let mut cpu = TestCPU::default();
+        let mut debugger = debugger_for(&mut cpu);
+        debugger.break_in(&mut cpu);
+        assert_eq!(debugger.focus(), Focus::Debugger);
+
+        // Move the cursor to the ADD V1 and set a breakpoint there
+        for _ in 0..3 {
+            debugger.handle_key(KeyCode::Down, &mut cpu).unwrap();
+        }
+        debugger.handle_key(KeyCode::Char('b'), &mut cpu).unwrap();
+        assert_eq!(debugger.breakpoints(), [0x206]);
+        let pane = debugger.disassembly_pane(&cpu, 5);
+        assert!(pane.iter().any(|line| line.text.starts_with(">*")));
+
+        // Continue, then let the game have the keys and run a frame
+        debugger.handle_key(KeyCode::Esc, &mut cpu).unwrap();
+        assert_eq!(debugger.focus(), Focus::Game);
+        debugger.run_frame(&mut cpu, 100).unwrap();
+        assert!(cpu.is_paused());
+        assert_eq!(cpu.pc(), 0x206);
+        assert_eq!(cpu.state().v[1], 0);
+        assert_eq!(debugger.focus(), Focus::Debugger);
+        assert!(debugger
+            .status_line(true)
+            .starts_with("Breakpoint at 0x206"));
+
+        // Stepping leaves the breakpoint, continuing stops at it again one loop later
+        debugger.handle_key(KeyCode::Char('s'), &mut cpu).unwrap();
+        assert_eq!((cpu.pc(), cpu.state().v[1]), (0x208, 2));
+        assert!(cpu.is_paused());
+        debugger.handle_key(KeyCode::Char('c'), &mut cpu).unwrap();
+        debugger.run_frame(&mut cpu, 100).unwrap();
+        assert_eq!((cpu.pc(), cpu.state().v[0]), (0x206, 2));
+    }
+
+    #[test]
+    fn panes_follow_each_step() {
+        let mut cpu = TestCPU::default();
+        let mut debugger = debugger_for(&mut cpu);
+        debugger.break_in(&mut cpu);
+        let highlighted = |debugger: &Debugger, cpu: &TestCPU| {
+            let pane = debugger.disassembly_pane(cpu, 9);
+            let lines = pane
+                .iter()
+                .filter(|line| line.highlight)
+                .collect::<Vec<_>>();
+            assert_eq!(lines.len(), 1);
+            return lines[0].text.clone();
+        };
+        assert_eq!(
+            highlighted(&debugger, &cpu),
+            ">  0x0200: 6000  LOAD V0 0x00"
+        );
+
+        debugger.handle_key(KeyCode::Char('s'), &mut cpu).unwrap();
+        debugger.handle_key(KeyCode::Char('s'), &mut cpu).unwrap();
+        debugger.handle_key(KeyCode::Char('s'), &mut cpu).unwrap();
+        assert_eq!(highlighted(&debugger, &cpu), ">  0x0206: 7102  ADD V1 0x02");
+        let registers = debugger.registers_pane(&cpu.state());
+        assert_eq!(registers[0].text, "PC 0x206  I 0x210");
+        assert_eq!(registers[1].text, "V0 01  V1 00  V2 00  V3 00");
+
+        // The memory view jumps to I with that byte bracketed
+        debugger.handle_key(KeyCode::Char('i'), &mut cpu).unwrap();
+        let memory = debugger.memory_pane(&cpu);
+        assert_eq!(
+            memory[0].text,
+            "0x210[00]00 00 00 00 00 00 00 00 00 00 00 00 00 00 00"
+        );
+    }
+
+    #[test]
+    fn goto_moves_both_panes() {
+        let mut cpu = TestCPU::default();
+        let mut debugger = debugger_for(&mut cpu);
+        debugger.break_in(&mut cpu);
+        for key in [KeyCode::Char('g'), KeyCode::Char('2'), KeyCode::Char('0')] {
+            debugger.handle_key(key, &mut cpu).unwrap();
+        }
+        assert_eq!(debugger.status_line(true), "Go to address: 0x20_");
+        for key in [KeyCode::Char('8'), KeyCode::Char('x'), KeyCode::Enter] {
+            debugger.handle_key(key, &mut cpu).unwrap();
+        }
+        let pane = debugger.disassembly_pane(&cpu, 3);
+        assert!(pane[1].text.starts_with("> "));
+        assert!(pane[1].text.contains("0x0208"));
+        assert!(debugger.memory_pane(&cpu)[0].text.starts_with("0x200 60"));
+
+        // Breakpoints go where the cursor is, not the PC
+        debugger.handle_key(KeyCode::Char('b'), &mut cpu).unwrap();
+        assert_eq!(debugger.breakpoints(), [0x208]);
+        debugger.handle_key(KeyCode::Char('b'), &mut cpu).unwrap();
+        assert!(debugger.breakpoints().is_empty());
+    }
+
+    fn type_command(debugger: &mut Debugger, cpu: &mut TestCPU, command: &str) {
+        debugger.handle_key(KeyCode::Char(':'), cpu).unwrap();
+        for c in command.chars() {
+            debugger.handle_key(KeyCode::Char(c), cpu).unwrap();
+        }
+        debugger.handle_key(KeyCode::Enter, cpu).unwrap();
+    }
+
+    #[test]
+    fn commands_add_conditional_breakpoints_and_watches() {
+        let mut cpu = TestCPU::default();
+        let mut debugger = debugger_for(&mut cpu);
+        debugger.break_in(&mut cpu);
+        debugger.handle_key(KeyCode::Char(':'), &mut cpu).unwrap();
+        debugger.handle_key(KeyCode::Char('l'), &mut cpu).unwrap();
+        assert_eq!(debugger.status_line(true), ":l_");
+        debugger.handle_key(KeyCode::Enter, &mut cpu).unwrap();
+        assert!(debugger.status_line(true).starts_with("No breakpoints"));
+
+        type_command(&mut debugger, &mut cpu, "break 0x206 when V0 == 3");
+        type_command(&mut debugger, &mut cpu, "b when I changes");
+        type_command(&mut debugger, &mut cpu, "watch V0");
+        type_command(&mut debugger, &mut cpu, "w V1 >= 4");
+        assert!(debugger.status_line(true).starts_with("Watch 2"));
+        type_command(&mut debugger, &mut cpu, "list");
+        assert!(debugger
+            .status_line(true)
+            .starts_with("1: 0x206 when V0 == 0x3  2: when I changes"));
+        assert_eq!(debugger.breakpoints(), [0x206]);
+        let registers = debugger.registers_pane(&cpu.state());
+        assert_eq!(registers[7].text, "W1 V0 = 00");
+        assert_eq!(registers[8].text, "W2 V1 >= 0x4: no");
+
+        // I is set by the first pass through the loop
+        debugger.handle_key(KeyCode::Char('c'), &mut cpu).unwrap();
+        debugger.run_frame(&mut cpu, 100).unwrap();
+        assert_eq!(cpu.pc(), 0x206);
+        assert!(debugger
+            .status_line(true)
+            .starts_with("Breakpoint at 0x206, I changes"));
+        type_command(&mut debugger, &mut cpu, "delete 2");
+        assert!(debugger
+            .status_line(true)
+            .starts_with("Deleted breakpoint when I changes"));
+
+        // The address alone doesn't stop it, V0 has to reach 3 too
+        debugger.handle_key(KeyCode::Char('c'), &mut cpu).unwrap();
+        debugger.run_frame(&mut cpu, 100).unwrap();
+        assert_eq!((cpu.pc(), cpu.state().v[0]), (0x206, 3));
+        assert!(debugger
+            .status_line(true)
+            .starts_with("Breakpoint at 0x206, V0 == 0x3"));
+        let registers = debugger.registers_pane(&cpu.state());
+        assert_eq!(registers[7].text, "W1 V0 = 03");
+        assert_eq!(registers[8].text, "W2 V1 >= 0x4: yes");
+
+        for (command, message) in [
+            ("delete 5", "No breakpoint 5"),
+            ("d x", "Expected a number from list, got x"),
+            ("unwatch 1", "Deleted watch 1"),
+            ("u 2", "No watch 2"),
+            (
+                "b 0x206 when V0 = 3",
+                "expected one of == != < <= > >=, got =",
+            ),
+            ("jump 0x200", "Unknown command jump"),
+        ] {
+            type_command(&mut debugger, &mut cpu, command);
+            assert!(
+                debugger.status_line(true).starts_with(message),
+                "{}: {}",
+                command,
+                debugger.status_line(true)
+            );
+        }
+        assert_eq!(debugger.watches().len(), 1);
+    }
+}
@@ -0,0 +1,188 @@
+use std::{fs::File, io::BufWriter};
+
+use chip8_core::{rom::Rom, Chip8Error, CpuState, HookAction, NoopInput, Screen};
+
+use crate::{
+    args::Args,
+    condition::BreakpointList,
+    input_log::{InputLog, LoggedKeypad},
+    setup::new_cpu,
+    watchdog::Watchdog,
+};
+
+// Enough for most ROMs to get through their title screen
+pub const DEFAULT_CYCLES: u64 = 1_000_000;
+// CXNN gives the same results every run so the output can be compared against a golden copy
+pub const HEADLESS_SEED: u64 = 0;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    // Ran every cycle
+    Finished,
+    // Reached 00FD
+    Exited,
+    // The watchdog found the CPU stuck at this PC, only with --exit-on-halt
+    Stuck(u16),
+    // A --break breakpoint stopped it before the instruction at this PC
+    Break(u16),
+    Error(Chip8Error),
+}
+
+impl Outcome {
+    fn name(&self) -> &'static str {
+        return match self {
+            Outcome::Finished => "finished",
+            Outcome::Exited => "exited",
+            Outcome::Stuck(_) => "stuck",
+            Outcome::Break(_) => "break",
+            Outcome::Error(_) => "error",
+        };
+    }
+}
+
+pub struct HeadlessRun {
+    pub outcome: Outcome,
+    // Instruction slots used, steps counts the ones that executed
+    pub cycles: u64,
+    pub steps: u64,
+    pub state: CpuState,
+    pub screen: Screen,
+}
+
+// Runs --cycles instructions in frames of --speed, ticking the timers after each frame like the
+// interactive mode does, without a terminal or keyboard. A --replay-input log drives the keypad
+// and brings its own seed and speed. Errors while running end up in the outcome, only loading
+// the ROM fails outright.
+pub fn run_headless(
+    args: &Args,
+    rom: &Rom,
+    replay: Option<&InputLog>,
+) -> Result<HeadlessRun, Chip8Error> {
+    let screen = Screen::new();
+    let (keypad, seed, speed) = match replay {
+        Some(log) => (
+            LoggedKeypad::replaying(&NoopInput, &log.events),
+            log.seed,
+            log.speed,
+        ),
+        None => (LoggedKeypad::live(&NoopInput), HEADLESS_SEED, args.speed),
+    };
+    let breakpoints = BreakpointList::new(&args.breakpoints);
+    let (outcome, cycles, steps, state) = {
+        let mut cpu = new_cpu(&screen, &keypad, args, rom, Some(seed))?;
+        cpu.set_pre_step_hook(Box::new(|view| {
+            if breakpoints.check(view).is_some() {
+                return HookAction::Pause;
+            }
+            return HookAction::Continue;
+        }));
+        let mut watchdog = Watchdog::new(args.watchdog_interval, args.watchdog_threshold);
+        let mut cycles = 0;
+        let outcome = loop {
+            if cycles >= args.cycles {
+                break Outcome::Finished;
+            }
+            let instructions = (args.cycles - cycles).min(speed as u64);
+            cycles += instructions;
+            match cpu.step_frame(instructions as usize) {
+                Ok(()) => {}
+                Err(Chip8Error::ExitRequested) => break Outcome::Exited,
+                Err(err) => break Outcome::Error(err),
+            }
+            // Only breakpoints pause
+            if cpu.is_paused() {
+                break Outcome::Break(cpu.pc());
+            }
+            let stuck_at = watchdog.frame(cpu.step_count(), cpu.pc(), cpu.stats().last_opcode);
+            if let (Some(pc), true) = (stuck_at, args.exit_on_halt) {
+                break Outcome::Stuck(pc);
+            }
+        };
+        (outcome, cycles, cpu.step_count(), cpu.state())
+    };
+    return Ok(HeadlessRun {
+        outcome,
+        cycles,
+        steps,
+        state,
+        screen,
+    });
+}
+
+impl HeadlessRun {
+    // Non-zero only when the program hit an error, halting or stopping after --cycles is a clean
+    // exit
+    pub fn exit_code(&self) -> i32 {
+        return match self.outcome {
+            Outcome::Error(_) => 1,
+            _ => 0,
+        };
+    }
+
+    // Writes the screen to --dump-screen as text and to --dump-ppm as an image
+    pub fn write_dumps(&self, args: &Args) -> Result<(), String> {
+        if let Some(path) = &args.dump_screen {
+            std::fs::write(path, self.screen.draw_as_string())
+                .map_err(|err| format!("Could not write {}: {}", path.display(), err))?;
+        }
+        if let Some(path) = &args.dump_ppm {
+            File::create(path)
+                .and_then(|file| self.screen.write_ppm(&mut BufWriter::new(file)))
+                .map_err(|err| format!("Could not write {}: {}", path.display(), err))?;
+        }
+        return Ok(());
+    }
+
+    // One line, e.g. {"outcome":"exited","cycles":11,"steps":10,"pc":512,...}
+    pub fn to_json(&self) -> String {
+        let state = &self.state;
+        let (stuck_at, error) = match &self.outcome {
+            Outcome::Stuck(pc) => (pc.to_string(), "null".to_string()),
+            Outcome::Error(err) => ("null".to_string(), json_string(&err.to_string())),
+            _ => ("null".to_string(), "null".to_string()),
+        };
+        return format!(
+            concat!(
+                "{{\"outcome\":\"{}\",\"cycles\":{},\"steps\":{},\"pc\":{},\"i\":{},\"v\":{:?},",
+                "\"delay_timer\":{},\"sound_timer\":{},\"stack\":{:?},\"stuck_at\":{},",
+                "\"error\":{}}}"
+            ),
+            self.outcome.name(),
+            self.cycles,
+            self.steps,
+            state.pc,
+            state.i,
+            state.v,
+            state.delay_timer,
+            state.sound_timer,
+            state.stack,
+            stuck_at,
+            error,
+        );
+    }
+}
+
+fn json_string(value: &str) -> String {
+    let mut out = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    return out;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_strings_are_escaped() {
+        assert_eq!(json_string(r#"a "b" \c"#), r#""a \"b\" \\c""#);
+        assert_eq!(json_string("tab\there"), "\"tab\\u0009here\"");
+    }
+}
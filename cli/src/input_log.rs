@@ -0,0 +1,363 @@
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use chip8_core::{Chip8Input, ReplayKeypad, KEY_RELEASED};
+
+const MAGIC: &[u8; 4] = b"C8IN";
+// Bump whenever the layout in to_bytes changes, older files are rejected rather than misread
+pub const INPUT_LOG_VERSION: u8 = 1;
+// Magic, version, seed, speed, ROM checksum, event count
+const HEADER_SIZE: usize = MAGIC.len() + 1 + 8 + 4 + 4 + 4;
+// Step count and key value
+const EVENT_SIZE: usize = 8 + 1;
+
+// Everything a run needs to play out the same way again: the CXNN seed, the instructions per
+// frame that decide when the timers tick, and every key press and release stamped with the step
+// count it happened before, as in core's Chip8Replay
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InputLog {
+    pub seed: u64,
+    pub speed: usize,
+    // Rom::checksum of the ROM it was recorded with
+    pub rom_checksum: u32,
+    pub events: Vec<(u64, u8)>,
+}
+
+impl InputLog {
+    // A seed for a new recording, any value works as long as it's saved
+    pub fn new_seed() -> u64 {
+        return SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_nanos() as u64);
+    }
+
+    // Big-endian, starts with "C8IN" and the format version
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(HEADER_SIZE + self.events.len() * EVENT_SIZE);
+        out.extend_from_slice(MAGIC);
+        out.push(INPUT_LOG_VERSION);
+        out.extend_from_slice(&self.seed.to_be_bytes());
+        out.extend_from_slice(&(self.speed as u32).to_be_bytes());
+        out.extend_from_slice(&self.rom_checksum.to_be_bytes());
+        out.extend_from_slice(&(self.events.len() as u32).to_be_bytes());
+        for (step, event) in &self.events {
+            out.extend_from_slice(&step.to_be_bytes());
+            out.push(*event);
+        }
+        return out;
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<InputLog, String> {
+        if !bytes.starts_with(MAGIC) {
+            return Err("not an input recording".to_string());
+        }
+        // The version comes first so a newer file reports that instead of a size mismatch
+        match bytes.get(MAGIC.len()) {
+            Some(&INPUT_LOG_VERSION) => {}
+            Some(&found) => {
+                return Err(format!(
+                    "input recording version {} is not supported, expected version {}",
+                    found, INPUT_LOG_VERSION
+                ));
+            }
+            None => return Err("input recording is truncated".to_string()),
+        }
+        let header = bytes
+            .get(MAGIC.len() + 1..HEADER_SIZE)
+            .ok_or("input recording is truncated")?;
+        let (seed, rest) = header.split_at(8);
+        let (speed, rest) = rest.split_at(4);
+        let (rom_checksum, count) = rest.split_at(4);
+        let count = u32::from_be_bytes(count.try_into().unwrap()) as usize;
+        let body = &bytes[HEADER_SIZE..];
+        if body.len() != count * EVENT_SIZE {
+            return Err(format!(
+                "input recording has {} bytes of events but should have {}",
+                body.len(),
+                count * EVENT_SIZE
+            ));
+        }
+        let speed = u32::from_be_bytes(speed.try_into().unwrap()) as usize;
+        if speed == 0 {
+            return Err("input recording has a speed of 0".to_string());
+        }
+        let events = body
+            .chunks_exact(EVENT_SIZE)
+            .map(|event| {
+                let (step, key) = event.split_at(8);
+                return (u64::from_be_bytes(step.try_into().unwrap()), key[0]);
+            })
+            .collect();
+        return Ok(InputLog {
+            seed: u64::from_be_bytes(seed.try_into().unwrap()),
+            speed,
+            rom_checksum: u32::from_be_bytes(rom_checksum.try_into().unwrap()),
+            events,
+        });
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        return std::fs::write(path, self.to_bytes())
+            .map_err(|err| format!("Could not save {}: {}", path.display(), err));
+    }
+
+    pub fn load(path: &Path) -> Result<InputLog, String> {
+        let bytes = std::fs::read(path)
+            .map_err(|err| format!("Could not read {}: {}", path.display(), err))?;
+        return InputLog::from_bytes(&bytes)
+            .map_err(|err| format!("Could not load {}: {}", path.display(), err));
+    }
+
+    // Replaying against another ROM still runs, it just won't do what was recorded
+    pub fn mismatch_warning(&self, path: &Path, rom_checksum: u32) -> Option<String> {
+        if self.rom_checksum == rom_checksum {
+            return None;
+        }
+        return Some(format!(
+            "Warning: {} was recorded with a different ROM (CRC-32 {:08X}, this one is {:08X})",
+            path.display(),
+            self.rom_checksum,
+            rom_checksum
+        ));
+    }
+}
+
+enum Mode {
+    // Straight through to the keyboard
+    Live,
+    // The CPU sees the keyboard one step at a time, each change is kept for an InputLog
+    Recording(RefCell<Vec<(u64, u8)>>),
+    // The CPU only sees the log, the keyboard is left to the hotkeys and the debugger
+    Replaying(RefCell<VecDeque<(u64, u8)>>),
+}
+
+// The CPU's keypad in the interactive mode, so --record-input and --replay-input can sit between
+// it and the keyboard
+pub struct LoggedKeypad<'a, TInput: Chip8Input> {
+    live: &'a TInput,
+    keypad: ReplayKeypad,
+    mode: Mode,
+}
+
+impl<'a, TInput: Chip8Input> LoggedKeypad<'a, TInput> {
+    pub fn live(live: &'a TInput) -> Self {
+        return LoggedKeypad {
+            live,
+            keypad: ReplayKeypad::default(),
+            mode: Mode::Live,
+        };
+    }
+
+    pub fn recording(live: &'a TInput) -> Self {
+        return LoggedKeypad {
+            mode: Mode::Recording(RefCell::new(vec![])),
+            ..LoggedKeypad::live(live)
+        };
+    }
+
+    pub fn replaying(live: &'a TInput, events: &[(u64, u8)]) -> Self {
+        return LoggedKeypad {
+            mode: Mode::Replaying(RefCell::new(events.iter().copied().collect())),
+            ..LoggedKeypad::live(live)
+        };
+    }
+
+    pub fn is_live(&self) -> bool {
+        return matches!(self.mode, Mode::Live);
+    }
+
+    // What was recorded so far, empty unless recording
+    pub fn events(&self) -> Vec<(u64, u8)> {
+        return match &self.mode {
+            Mode::Recording(events) => events.borrow().clone(),
+            _ => vec![],
+        };
+    }
+
+    // Replaying and every event has been fed in
+    pub fn replay_finished(&self) -> bool {
+        return match &self.mode {
+            Mode::Replaying(pending) => pending.borrow().is_empty(),
+            _ => false,
+        };
+    }
+
+    fn input(&self) -> &dyn Chip8Input {
+        return match self.mode {
+            Mode::Live => self.live,
+            _ => &self.keypad,
+        };
+    }
+
+    // Brings the keypad up to date with the keyboard, a press and release both between two steps
+    // still counts so a quick tap isn't lost
+    fn record(&self, step: u64, events: &RefCell<Vec<(u64, u8)>>) {
+        let record = |event: u8| {
+            self.keypad.apply(event);
+            events.borrow_mut().push((step, event));
+        };
+        if let Some(key) = self.live.was_key_released() {
+            if !self.keypad.is_key_held(key) {
+                record(key);
+            }
+            record(key | KEY_RELEASED);
+        }
+        for key in 0..16u8 {
+            let held = self.live.is_key_held(key);
+            if held != self.keypad.is_key_held(key) {
+                record(if held { key } else { key | KEY_RELEASED });
+            }
+        }
+    }
+}
+
+impl<TInput: Chip8Input> Chip8Input for LoggedKeypad<'_, TInput> {
+    fn get_key(&self) -> Option<u8> {
+        return self.input().get_key();
+    }
+
+    fn was_key_released(&self) -> Option<u8> {
+        return self.input().was_key_released();
+    }
+
+    fn is_key_held(&self, key: u8) -> bool {
+        return self.input().is_key_held(key);
+    }
+
+    fn before_step(&self, step: u64) {
+        match &self.mode {
+            Mode::Live => self.live.before_step(step),
+            Mode::Recording(events) => self.record(step, events),
+            Mode::Replaying(pending) => {
+                let mut pending = pending.borrow_mut();
+                while let Some((_, event)) = pending.front().filter(|(at, _)| *at <= step) {
+                    self.keypad.apply(*event);
+                    pending.pop_front();
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+
+    // One bit per held key, set from the test
+    #[derive(Default)]
+    struct Keyboard {
+        held: Cell<u16>,
+        released: Cell<Option<u8>>,
+    }
+
+    impl Chip8Input for Keyboard {
+        fn get_key(&self) -> Option<u8> {
+            return (0..16).find(|key| self.is_key_held(*key));
+        }
+
+        fn was_key_released(&self) -> Option<u8> {
+            return self.released.take();
+        }
+
+        fn is_key_held(&self, key: u8) -> bool {
+            return key <= 0xF && self.held.get() & 1 << key != 0;
+        }
+    }
+
+    #[test]
+    fn round_trips_through_bytes() {
+        let log = InputLog {
+            seed: 0x0123_4567_89AB_CDEF,
+            speed: 11,
+            rom_checksum: 0xCAFE_F00D,
+            events: vec![(0, 0x5), (120, 0x5 | KEY_RELEASED), (u64::MAX, 0xF)],
+        };
+        let bytes = log.to_bytes();
+        assert!(bytes.starts_with(b"C8IN\x01"));
+        assert_eq!(InputLog::from_bytes(&bytes), Ok(log));
+
+        assert_eq!(
+            InputLog::from_bytes(b"CH8S\x01"),
+            Err("not an input recording".to_string())
+        );
+        let mut newer = bytes.clone();
+        newer[4] = 9;
+        assert!(InputLog::from_bytes(&newer)
+            .unwrap_err()
+            .starts_with("input recording version 9 is not supported"));
+        assert!(InputLog::from_bytes(&bytes[..bytes.len() - 1])
+            .unwrap_err()
+            .contains("bytes of events"));
+        assert!(InputLog::from_bytes(&bytes[..10])
+            .unwrap_err()
+            .ends_with("truncated"));
+    }
+
+    #[test]
+    fn records_keyboard_changes_at_the_step() {
+        let keyboard = Keyboard::default();
+        let keypad = LoggedKeypad::recording(&keyboard);
+        keypad.before_step(0);
+        keyboard.held.set(1 << 0x4);
+        keypad.before_step(1);
+        assert!(keypad.is_key_held(0x4));
+        // Pressed and released before the next step
+        keyboard.held.set(0);
+        keyboard.released.set(Some(0x4));
+        keypad.before_step(2);
+        keyboard.released.set(Some(0xA));
+        keypad.before_step(3);
+        assert_eq!(keypad.was_key_released(), Some(0xA));
+        assert_eq!(
+            keypad.events(),
+            [
+                (1, 0x4),
+                (2, 0x4 | KEY_RELEASED),
+                (3, 0xA),
+                (3, 0xA | KEY_RELEASED)
+            ]
+        );
+    }
+
+    #[test]
+    fn replays_ignoring_the_keyboard() {
+        let keyboard = Keyboard::default();
+        keyboard.held.set(1 << 0x1);
+        let keypad = LoggedKeypad::replaying(&keyboard, &[(2, 0x7), (4, 0x7 | KEY_RELEASED)]);
+        keypad.before_step(1);
+        assert_eq!(keypad.get_key(), None);
+        keypad.before_step(2);
+        assert!(keypad.is_key_held(0x7));
+        assert!(!keypad.replay_finished());
+        // Steps can be skipped over, the events still apply in order
+        keypad.before_step(10);
+        assert_eq!(keypad.was_key_released(), Some(0x7));
+        assert!(keypad.replay_finished());
+
+        let live = LoggedKeypad::live(&keyboard);
+        assert_eq!(live.get_key(), Some(0x1));
+        assert!(live.is_live());
+    }
+
+    #[test]
+    fn warns_about_another_rom() {
+        let log = InputLog {
+            seed: 0,
+            speed: 11,
+            rom_checksum: 0x1234_5678,
+            events: vec![],
+        };
+        let path = Path::new("pong.c8in");
+        assert_eq!(log.mismatch_warning(path, 0x1234_5678), None);
+        assert_eq!(
+            log.mismatch_warning(path, 0xFFFF_0000).unwrap(),
+            "Warning: pong.c8in was recorded with a different ROM (CRC-32 12345678, this one is FFFF0000)"
+        );
+    }
+}
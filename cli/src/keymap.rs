@@ -0,0 +1,130 @@
+use std::collections::HashMap;
+
+use crossterm::event::KeyCode;
+
+// The left 4x4 block of a QWERTY keyboard laid over the CHIP-8 keypad
+//   1 2 3 4      1 2 3 C
+//   q w e r  ->  4 5 6 D
+//   a s d f      7 8 9 E
+//   z x c v      A 0 B F
+const DEFAULT_LAYOUT: [(char, u8); 16] = [
+    ('1', 0x1),
+    ('2', 0x2),
+    ('3', 0x3),
+    ('4', 0xC),
+    ('q', 0x4),
+    ('w', 0x5),
+    ('e', 0x6),
+    ('r', 0xD),
+    ('a', 0x7),
+    ('s', 0x8),
+    ('d', 0x9),
+    ('f', 0xE),
+    ('z', 0xA),
+    ('x', 0x0),
+    ('c', 0xB),
+    ('v', 0xF),
+];
+
+// Translates terminal keys to keypad values, letters match regardless of case
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyMap {
+    bindings: HashMap<char, u8>,
+}
+
+impl Default for KeyMap {
+    fn default() -> Self {
+        return KeyMap {
+            bindings: DEFAULT_LAYOUT.into_iter().collect(),
+        };
+    }
+}
+
+impl KeyMap {
+    pub fn key(&self, code: KeyCode) -> Option<u8> {
+        let KeyCode::Char(c) = code else {
+            return None;
+        };
+        return self.bindings.get(&c.to_ascii_lowercase()).copied();
+    }
+
+    pub fn bind(&mut self, c: char, key: u8) {
+        self.bindings.insert(c.to_ascii_lowercase(), key & 0xF);
+    }
+
+    // Applies a comma separated list of overrides like "x=0,1=1,p=f" on top of this map
+    pub fn with_overrides(mut self, overrides: &str) -> Result<KeyMap, String> {
+        for binding in overrides
+            .split(',')
+            .map(str::trim)
+            .filter(|b| !b.is_empty())
+        {
+            let parsed = binding.split_once('=').and_then(|(c, key)| {
+                let mut chars = c.trim().chars();
+                let c = chars.next().filter(|_| chars.next().is_none())?;
+                let key = u8::from_str_radix(key.trim(), 16)
+                    .ok()
+                    .filter(|k| *k <= 0xF)?;
+                return Some((c, key));
+            });
+            let Some((c, key)) = parsed else {
+                return Err(format!(
+                    "expected <key>=<hex digit> like x=0, got {}",
+                    binding
+                ));
+            };
+            self.bind(c, key);
+        }
+        return Ok(self);
+    }
+}
+
+// For clap, overrides apply to the default layout
+pub fn parse_keymap(overrides: &str) -> Result<KeyMap, String> {
+    return KeyMap::default().with_overrides(overrides);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_layout() {
+        let keymap = KeyMap::default();
+        let rows = ["1234", "qwer", "asdf", "zxcv"].map(|row| {
+            row.chars()
+                .map(|c| keymap.key(KeyCode::Char(c)).unwrap())
+                .collect::<Vec<_>>()
+        });
+        assert_eq!(
+            rows,
+            [
+                [0x1, 0x2, 0x3, 0xC],
+                [0x4, 0x5, 0x6, 0xD],
+                [0x7, 0x8, 0x9, 0xE],
+                [0xA, 0x0, 0xB, 0xF]
+            ]
+            .map(Vec::from)
+        );
+        assert_eq!(keymap.key(KeyCode::Char('Q')), Some(0x4));
+        assert_eq!(keymap.key(KeyCode::Char('p')), None);
+        assert_eq!(keymap.key(KeyCode::Char('5')), None);
+        assert_eq!(keymap.key(KeyCode::Enter), None);
+    }
+
+    #[test]
+    fn overrides() {
+        let keymap = parse_keymap("x=0, 1=1,p=F,5=a").unwrap();
+        assert_eq!(keymap.key(KeyCode::Char('x')), Some(0x0));
+        assert_eq!(keymap.key(KeyCode::Char('1')), Some(0x1));
+        assert_eq!(keymap.key(KeyCode::Char('P')), Some(0xF));
+        assert_eq!(keymap.key(KeyCode::Char('5')), Some(0xA));
+        // Keys that weren't overridden keep their default
+        assert_eq!(keymap.key(KeyCode::Char('v')), Some(0xF));
+        assert_eq!(parse_keymap(""), Ok(KeyMap::default()));
+
+        for bad in ["x", "x=", "xy=1", "=1", "x=10", "x=g"] {
+            assert!(parse_keymap(bad).is_err(), "{}", bad);
+        }
+    }
+}
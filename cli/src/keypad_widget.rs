@@ -0,0 +1,124 @@
+use std::io::Write;
+
+use crossterm::{
+    cursor::MoveTo,
+    queue,
+    style::{Attribute, Print, SetAttribute},
+};
+
+// The COSMAC VIP keypad as it's laid out on the device
+pub const KEYPAD_LAYOUT: [[u8; 4]; 4] = [
+    [0x1, 0x2, 0x3, 0xC],
+    [0x4, 0x5, 0x6, 0xD],
+    [0x7, 0x8, 0x9, 0xE],
+    [0xA, 0x0, 0xB, 0xF],
+];
+// Each key is drawn as " 1 "
+const CELL_WIDTH: u16 = 3;
+
+// (row, column) of a keypad key in KEYPAD_LAYOUT
+pub fn grid_position(key: u8) -> (u16, u16) {
+    for (row, keys) in (0..).zip(KEYPAD_LAYOUT.iter()) {
+        if let Some(column) = keys.iter().position(|k| *k == key & 0xF) {
+            return (row, column as u16);
+        }
+    }
+    unreachable!("every key from 0 to F is in the layout");
+}
+
+// The 16 keys in a 4x4 grid with the held ones in reverse video, for checking a keymap. It
+// remembers what it drew so a frame where nothing changed draws nothing.
+pub struct KeypadWidget {
+    column: u16,
+    row: u16,
+    // One bit per key, None until the first draw
+    shown: Option<u16>,
+}
+
+impl KeypadWidget {
+    pub fn new(column: u16, row: u16) -> KeypadWidget {
+        return KeypadWidget {
+            column,
+            row,
+            shown: None,
+        };
+    }
+
+    // The keys whose highlight differs from what's on screen, every key the first time
+    pub fn changed_keys(&self, held: u16) -> Vec<u8> {
+        let changed = match self.shown {
+            Some(shown) => shown ^ held,
+            None => u16::MAX,
+        };
+        return (0..16u8).filter(|key| changed & 1 << key != 0).collect();
+    }
+
+    // held has bit n set while key n is down
+    pub fn draw<W: Write>(&mut self, held: u16, out: &mut W) -> std::io::Result<()> {
+        let changed = self.changed_keys(held);
+        if changed.is_empty() {
+            return Ok(());
+        }
+        for key in changed {
+            let (row, column) = grid_position(key);
+            let attribute = if held & 1 << key != 0 {
+                Attribute::Reverse
+            } else {
+                Attribute::NoReverse
+            };
+            queue!(
+                out,
+                MoveTo(self.column + column * CELL_WIDTH, self.row + row),
+                SetAttribute(attribute),
+                Print(format!(" {:X} ", key)),
+                SetAttribute(Attribute::NoReverse),
+            )?;
+        }
+        self.shown = Some(held);
+        return out.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_sit_where_the_vip_has_them() {
+        assert_eq!(grid_position(0x1), (0, 0));
+        assert_eq!(grid_position(0xC), (0, 3));
+        assert_eq!(grid_position(0x5), (1, 1));
+        assert_eq!(grid_position(0xE), (2, 3));
+        assert_eq!(grid_position(0xA), (3, 0));
+        assert_eq!(grid_position(0x0), (3, 1));
+        assert_eq!(grid_position(0xF), (3, 3));
+        // Every key has its own cell
+        let mut cells = (0..16).map(grid_position).collect::<Vec<_>>();
+        cells.sort();
+        cells.dedup();
+        assert_eq!(cells.len(), 16);
+    }
+
+    #[test]
+    fn only_changed_keys_are_redrawn() {
+        let mut widget = KeypadWidget::new(66, 0);
+        assert_eq!(widget.changed_keys(0).len(), 16);
+        let mut out = vec![];
+        widget.draw(1 << 0x5, &mut out).unwrap();
+        assert!(!out.is_empty());
+
+        // Nothing changed, nothing drawn
+        let mut out = vec![];
+        widget.draw(1 << 0x5, &mut out).unwrap();
+        assert!(out.is_empty());
+
+        // 5 released and A pressed
+        assert_eq!(widget.changed_keys(1 << 0xA), [0x5, 0xA]);
+        let mut out = vec![];
+        widget.draw(1 << 0xA, &mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.contains(" 5 ") && text.contains(" A "));
+        assert!(!text.contains(" 1 "));
+        assert!(widget.changed_keys(1 << 0xA).is_empty());
+    }
+}
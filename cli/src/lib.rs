@@ -1 +1,18 @@
+pub mod args;
+pub mod browser;
+pub mod capture;
 pub mod cli;
+pub mod condition;
+pub mod config;
+pub mod debugger;
+pub mod headless;
+pub mod input_log;
+pub mod keymap;
+pub mod keypad_widget;
+pub mod renderer;
+pub mod save_state;
+pub mod setup;
+pub mod sound;
+pub mod status;
+pub mod terminal;
+pub mod watchdog;
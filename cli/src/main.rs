@@ -1,61 +1,548 @@
-use std::{fs::File, io::Read, thread::sleep, time::Duration};
+use std::{
+    io::Stdout,
+    path::{Path, PathBuf},
+    sync::mpsc::Receiver,
+    thread::sleep,
+    time::{Duration, Instant, SystemTime},
+};
 
-use chip8_cli::cli::CLIEvent;
-use chip8_core::Chip8CPU;
+use chip8_cli::{
+    args::Args,
+    browser::{display_name, RomBrowser},
+    capture::{recording_dir, save_screenshot, screenshot_path, Recorder},
+    cli::{CLIEvent, CLIManager, Hotkey},
+    config::{merged_args, Config},
+    debugger::{Debugger, Focus},
+    headless::{self, Outcome},
+    input_log::{InputLog, LoggedKeypad},
+    keypad_widget::KeypadWidget,
+    save_state::{load_state, save_state, state_path, DEFAULT_SLOT},
+    setup::{new_cpu, read_rom},
+    sound::{sound_for, MutableSound},
+    status::{IpsCounter, StatusLine, IPS_SAMPLE_FRAMES},
+    terminal::{install_panic_hook, TerminalGuard},
+    watchdog::Watchdog,
+};
+use chip8_core::{rom::Rom, Chip8CPU, Chip8Error, Chip8Screen, Chip8Sound, OpCodes, Palette};
+use clap::{ArgMatches, CommandFactory, FromArgMatches};
 use crossterm::{
-    event::{KeyboardEnhancementFlags, PushKeyboardEnhancementFlags},
     execute,
-    style::Print,
-    terminal::{disable_raw_mode, enable_raw_mode, Clear},
+    terminal::{size, supports_keyboard_enhancement, Clear, ClearType},
 };
 
+// Timers tick at 60Hz, the instructions per frame come from --speed
+const FRAME_DURATION: Duration = Duration::from_nanos(16_666_667);
+
+// Ctrl reaches the hotkeys whose letter the keymap uses, R is keypad D by default
+const HOTKEY_HELP: &str = concat!(
+    "P pause  Ctrl-R reset  +/- speed  M mute  F5 save  F7 load  Alt-1..9 slot  ",
+    "F12 screenshot  F9 record"
+);
+
 fn main() {
-    enable_raw_mode().unwrap();
-    execute!(
-        std::io::stdout(),
-        crossterm::cursor::Hide,
-        crossterm::terminal::Clear(crossterm::terminal::ClearType::All),
-    )
-    .unwrap();
-    let args: Vec<String> = std::env::args().collect();
-    let filename = args.get(1).expect("No filename provided");
-    let cli_manager = chip8_cli::cli::CLIManager::new();
-    let rx = cli_manager.watch_for_key();
-    let mut cpu = chip8_core::CPU::new(&cli_manager, &cli_manager);
-    let data = File::open(filename).expect(format!("Could not open file {}", filename).as_str());
-    let mut data = std::io::BufReader::new(data);
-    let mut buffer = vec![];
-    data.read_to_end(&mut buffer).unwrap();
-    cpu.load_program(&buffer.as_slice()).unwrap();
-    let mut last_pressed_key = cli_manager.pressed_key.read().unwrap().clone();
+    // The matches say which settings came from the command line, those win over config files
+    let matches = Args::command().get_matches();
+    let args = Args::from_arg_matches(&matches).unwrap_or_else(|err| err.exit());
+    let exit_on_error = |err: String| -> ! {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    };
+    let global = Config::load_global().unwrap_or_else(|err| exit_on_error(err));
+    let Some(path) = args.rom.clone() else {
+        run_browser(&args, &matches, &global);
+        return;
+    };
+    let args =
+        merged_args(&args, &matches, &global, Some(&path)).unwrap_or_else(|err| exit_on_error(err));
+    let rom = read_rom(&path).unwrap_or_else(|err| exit_on_error(err));
+    if args.headless {
+        run_headless(&args, &path, &rom);
+    } else {
+        run_interactive(&args, &path, &rom);
+    }
+}
+
+// Prints the final state as JSON on stdout, errors go to stderr
+fn run_headless(args: &Args, path: &Path, rom: &Rom) {
+    let replay = args.replay_input.as_ref().map(|log_path| {
+        let log = InputLog::load(log_path).unwrap_or_else(|err| {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        });
+        if let Some(warning) = log.mismatch_warning(log_path, rom.checksum()) {
+            eprintln!("{}", warning);
+        }
+        return log;
+    });
+    let run = headless::run_headless(args, rom, replay.as_ref()).unwrap_or_else(|err| {
+        eprintln!("Could not load {}: {}", path.display(), err);
+        std::process::exit(1);
+    });
+    if let Err(err) = run.write_dumps(args) {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
+    println!("{}", run.to_json());
+    if let Outcome::Error(err) = &run.outcome {
+        report_error(err);
+    }
+    std::process::exit(run.exit_code());
+}
+
+fn run_interactive(args: &Args, path: &Path, rom: &Rom) {
+    let cli_manager = new_cli_manager(args);
+    let sound = sound_for(args);
+    let (terminal, rx) = start_terminal(&cli_manager);
+    let ended = play(args, path, rom, &cli_manager, &sound, &rx, false);
+    sound.stop();
+    // Restore the terminal before printing, exit below would skip the guard's drop
+    drop(terminal);
+    match ended {
+        Ended::Stopped(Stopped::Error(err)) => report_error(&err),
+        Ended::Stopped(Stopped::Stuck(pc)) => {
+            eprintln!("\nCPU stuck at PC=0x{:04X}, exiting", pc)
+        }
+        Ended::Stopped(Stopped::File(err)) => eprintln!("{}", err),
+        Ended::Quit | Ended::Menu | Ended::Exited => return,
+    }
+    std::process::exit(1);
+}
+
+// Without a ROM the user picks one from the current directory and --rom-dir. Esc or a game that
+// stops comes back to the list, the terminal stays set up the whole time.
+fn run_browser(cli_args: &Args, matches: &ArgMatches, global: &Config) {
+    let args = merged_args(cli_args, matches, global, None).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    });
+    let mut dirs = vec![PathBuf::from(".")];
+    dirs.extend(args.rom_dir.iter().cloned());
+    let mut browser = RomBrowser::new(dirs);
+    let cli_manager = new_cli_manager(&args);
+    let sound = sound_for(&args);
+    let (terminal, rx) = start_terminal(&cli_manager);
     loop {
-        cpu.step().unwrap();
-        let did_draw = cli_manager.draw_if_needed();
-        if let Ok(CLIEvent::Sigint) = rx.try_recv() {
+        cli_manager.set_capture_keys(true);
+        let Some(path) = browser.pick(&rx, &mut std::io::stdout()).unwrap() else {
             break;
+        };
+        let name = display_name(&path);
+        // Each ROM gets its own config file's settings
+        let loaded = merged_args(cli_args, matches, global, Some(&path))
+            .and_then(|args| read_rom(&path).map(|rom| (args, rom)));
+        let (args, rom) = match loaded {
+            Ok(loaded) => loaded,
+            Err(err) => {
+                browser.set_message(err);
+                continue;
+            }
+        };
+        configure(&cli_manager, &args);
+        // Starts on a blank screen, without the list or the last game showing through
+        execute!(std::io::stdout(), Clear(ClearType::All)).unwrap();
+        cli_manager.clear();
+        cli_manager.set_capture_keys(false);
+        let ended = play(&args, &path, &rom, &cli_manager, &sound, &rx, true);
+        sound.stop();
+        let message = match ended {
+            Ended::Quit => break,
+            Ended::Menu => String::new(),
+            Ended::Exited => format!("{} exited", name),
+            Ended::Stopped(Stopped::Error(err)) => format!("{} stopped: {}", name, err),
+            Ended::Stopped(Stopped::Stuck(pc)) => format!("{} stuck at PC=0x{:04X}", name, pc),
+            Ended::Stopped(Stopped::File(err)) => err,
+        };
+        browser.set_message(message);
+    }
+    drop(terminal);
+}
+
+fn new_cli_manager(args: &Args) -> CLIManager {
+    let cli_manager = CLIManager::with_keymap(args.keymap.clone());
+    configure(&cli_manager, args);
+    return cli_manager;
+}
+
+// The settings that can change from one ROM to the next
+fn configure(cli_manager: &CLIManager, args: &Args) {
+    let default_palette = Palette::classic();
+    cli_manager.set_palette(Palette::monochrome(
+        args.bg.unwrap_or(default_palette.off()),
+        args.fg.unwrap_or(default_palette.on()),
+    ));
+    cli_manager.set_keymap(args.keymap.clone());
+    cli_manager.set_renderer(args.renderer);
+}
+
+fn start_terminal(cli_manager: &CLIManager) -> (TerminalGuard<Stdout>, Receiver<CLIEvent>) {
+    // Real key up events where the terminal supports them, EXA1 polling loops need held keys
+    let key_releases = supports_keyboard_enhancement().unwrap_or(false);
+    install_panic_hook(key_releases);
+    let terminal = TerminalGuard::new(std::io::stdout(), key_releases).unwrap_or_else(|err| {
+        eprintln!("Could not set up the terminal: {}", err);
+        std::process::exit(1);
+    });
+    return (terminal, cli_manager.watch_for_key(key_releases));
+}
+
+// Runs one ROM on a fresh CPU until the user quits or it stops. from_menu lets Esc go back to
+// the ROM list. --record-input saves the keypad however the run ends.
+fn play(
+    args: &Args,
+    path: &Path,
+    rom: &Rom,
+    cli_manager: &CLIManager,
+    sound: &MutableSound,
+    rx: &Receiver<CLIEvent>,
+    from_menu: bool,
+) -> Ended {
+    let game = Game {
+        args,
+        path,
+        rom,
+        cli_manager,
+        sound,
+        rx,
+        from_menu,
+    };
+    if let Some(log_path) = &args.replay_input {
+        let log = match InputLog::load(log_path) {
+            Ok(log) => log,
+            Err(err) => return Ended::Stopped(Stopped::File(err)),
+        };
+        let keypad = LoggedKeypad::replaying(cli_manager, &log.events);
+        let warning = log.mismatch_warning(log_path, rom.checksum());
+        return run_game(&game, &keypad, Some(log.seed), log.speed, warning);
+    }
+    let Some(log_path) = &args.record_input else {
+        return run_game(
+            &game,
+            &LoggedKeypad::live(cli_manager),
+            None,
+            args.speed,
+            None,
+        );
+    };
+    let keypad = LoggedKeypad::recording(cli_manager);
+    let seed = InputLog::new_seed();
+    let ended = run_game(&game, &keypad, Some(seed), args.speed, None);
+    let log = InputLog {
+        seed,
+        speed: args.speed,
+        rom_checksum: rom.checksum(),
+        events: keypad.events(),
+    };
+    return match (log.save(log_path), ended) {
+        (Err(err), Ended::Quit | Ended::Menu | Ended::Exited) => Ended::Stopped(Stopped::File(err)),
+        (_, ended) => ended,
+    };
+}
+
+// What play was called with, handed on to run_game
+struct Game<'g> {
+    args: &'g Args,
+    path: &'g Path,
+    rom: &'g Rom,
+    cli_manager: &'g CLIManager,
+    sound: &'g MutableSound,
+    rx: &'g Receiver<CLIEvent>,
+    from_menu: bool,
+}
+
+// The keypad's seed and speed replace the random CXNN results and --speed, so a log plays back
+// the way it was recorded. The message is shown in the status line until the next one.
+fn run_game(
+    game: &Game,
+    keypad: &LoggedKeypad<CLIManager>,
+    seed: Option<u64>,
+    speed: usize,
+    message: Option<String>,
+) -> Ended {
+    let Game {
+        args,
+        path,
+        rom,
+        cli_manager,
+        sound,
+        rx,
+        from_menu,
+    } = *game;
+    let mut cpu = match new_cpu(cli_manager, keypad, args, rom, seed) {
+        Ok(cpu) => cpu,
+        Err(err) => {
+            let err = format!("Could not load {}: {}", path.display(), err);
+            return Ended::Stopped(Stopped::File(err));
+        }
+    };
+    cpu.set_sound_output(sound);
+    let mut debugger = args.debug.then(|| Debugger::new(rom.len()));
+    if let Some(debugger) = debugger.as_mut() {
+        debugger.install(&mut cpu);
+        for breakpoint in &args.breakpoints {
+            debugger.add_breakpoint(*breakpoint);
+        }
+        // --step with --debug starts paused in the debugger
+        if args.step {
+            debugger.break_in(&mut cpu);
+        }
+    }
+    // The debugger takes over Enter, step mode is only the plain status line version
+    let step_mode = args.step && debugger.is_none();
+    let hotkey_help = if from_menu {
+        format!("{}  Esc menu", HOTKEY_HELP)
+    } else {
+        HOTKEY_HELP.to_string()
+    };
+    let mut watchdog = Watchdog::new(args.watchdog_interval, args.watchdog_threshold);
+    let mut next_frame = Instant::now();
+    let mut last_diff = None;
+    let mut speed = speed;
+    let mut paused = false;
+    let mut slot = DEFAULT_SLOT;
+    // Result of the last save, load or capture, shown until the next one
+    let mut state_message = message.unwrap_or_default();
+    // Changing the speed or the CPU state would throw a recording or replay off
+    let locked = if keypad.is_live() {
+        None
+    } else {
+        Some("Not while recording or replaying input".to_string())
+    };
+    let mut recorder: Option<Recorder> = None;
+    let mut keypad_widget = args
+        .show_keypad
+        .then(|| KeypadWidget::new(cli_manager.side_column(), 0));
+    let rom_name = path.file_name().map_or_else(
+        || path.display().to_string(),
+        |name| name.to_string_lossy().into_owned(),
+    );
+    let mut status_line = StatusLine::new();
+    let mut ips_counter = IpsCounter::new();
+    let mut frames = 0u64;
+    let mut controls = String::new();
+    let mut last_key = None;
+    loop {
+        let mut step_requests = 0;
+        let mut keys = vec![];
+        for event in rx.try_iter() {
+            match event {
+                CLIEvent::Sigint => return Ended::Quit,
+                CLIEvent::Hotkey(Hotkey::Menu) if from_menu => return Ended::Menu,
+                CLIEvent::Hotkey(Hotkey::Menu) => {}
+                CLIEvent::Step => step_requests += 1,
+                CLIEvent::Key(code) => keys.push(code),
+                CLIEvent::Hotkey(Hotkey::Pause) => paused = !paused,
+                CLIEvent::Hotkey(Hotkey::Reset | Hotkey::Faster | Hotkey::Slower)
+                | CLIEvent::Hotkey(Hotkey::LoadState)
+                    if locked.is_some() =>
+                {
+                    state_message = locked.clone().unwrap();
+                }
+                CLIEvent::Hotkey(Hotkey::Reset) => {
+                    Chip8CPU::reset(&mut cpu);
+                    if let Err(err) = cpu.load_rom(rom) {
+                        return Ended::Stopped(Stopped::Error(err));
+                    }
+                    watchdog = Watchdog::new(args.watchdog_interval, args.watchdog_threshold);
+                    last_diff = None;
+                }
+                // 10% of the speed, but always at least one instruction
+                CLIEvent::Hotkey(Hotkey::Faster) => speed += (speed / 10).max(1),
+                CLIEvent::Hotkey(Hotkey::Slower) => speed = (speed - (speed / 10).max(1)).max(1),
+                CLIEvent::Hotkey(Hotkey::Mute) => sound.set_muted(!sound.is_muted()),
+                CLIEvent::Hotkey(Hotkey::Slot(n)) => slot = n,
+                CLIEvent::Hotkey(Hotkey::SaveState) => {
+                    let path = state_path(path, slot);
+                    let snapshot = cpu.snapshot(cli_manager.screen_buffer());
+                    state_message = match save_state(&path, &snapshot) {
+                        Ok(()) => format!("Saved slot {}", slot),
+                        Err(err) => err,
+                    };
+                }
+                CLIEvent::Hotkey(Hotkey::Screenshot) => {
+                    let shot = screenshot_path(path, SystemTime::now());
+                    state_message = match save_screenshot(&shot, &cli_manager.frame()) {
+                        Ok(()) => format!("Saved {}", shot.display()),
+                        Err(err) => err,
+                    };
+                }
+                CLIEvent::Hotkey(Hotkey::Record) => {
+                    state_message = match recorder.take() {
+                        Some(recording) => match recording.stop() {
+                            Ok(done) => format!(
+                                "Recorded {} frames to {} ({} dropped)",
+                                done.frames,
+                                done.dir.display(),
+                                done.dropped
+                            ),
+                            Err(err) => err,
+                        },
+                        None => {
+                            let now = SystemTime::now();
+                            match Recorder::start(recording_dir(path, now), now) {
+                                Ok(started) => {
+                                    // The screen as it is now, later frames only when drawn
+                                    started.push(cli_manager.frame(), now);
+                                    let message =
+                                        format!("Recording to {}", started.dir().display());
+                                    recorder = Some(started);
+                                    message
+                                }
+                                Err(err) => err,
+                            }
+                        }
+                    };
+                }
+                CLIEvent::Hotkey(Hotkey::LoadState) => {
+                    // A bad file leaves the running program alone
+                    state_message = match load_state(&state_path(path, slot)) {
+                        Ok(snapshot) => {
+                            cpu.restore(&snapshot);
+                            cli_manager.set_screen_buffer(snapshot.screen);
+                            watchdog =
+                                Watchdog::new(args.watchdog_interval, args.watchdog_threshold);
+                            last_diff = None;
+                            format!("Loaded slot {}", slot)
+                        }
+                        Err(err) => err,
+                    };
+                }
+            }
+        }
+        let result = if paused {
+            // The screen keeps drawing, only the CPU stops
+            Ok(())
+        } else if let Some(debugger) = debugger.as_mut() {
+            if step_requests > 0 && debugger.focus() == Focus::Game {
+                debugger.break_in(&mut cpu);
+            }
+            keys.into_iter()
+                .try_for_each(|code| debugger.handle_key(code, &mut cpu))
+                .and_then(|_| debugger.run_frame(&mut cpu, speed))
+        } else if step_mode {
+            // Step mode only runs instructions on Enter and leaves the timers alone
+            (0..step_requests).try_for_each(|_| {
+                let before = cpu.state_with_memory();
+                cpu.step()?;
+                last_diff = Some(before.diff(&cpu.state_with_memory()));
+                return Ok(());
+            })
+        } else {
+            cpu.step_frame(speed)
+        };
+        match result {
+            Ok(()) => {}
+            Err(Chip8Error::ExitRequested) => return Ended::Exited,
+            Err(err) => return Ended::Stopped(Stopped::Error(err)),
+        }
+        let did_draw = cli_manager.draw_if_needed();
+        if let (true, Some(recorder)) = (did_draw, recorder.as_ref()) {
+            recorder.push(cli_manager.frame(), SystemTime::now());
+        }
+        if let Some(keypad_widget) = keypad_widget.as_mut() {
+            keypad_widget
+                .draw(cli_manager.held_keys(), &mut std::io::stdout())
+                .unwrap();
         }
-        if let Some(key) = cli_manager.pressed_key.read().unwrap().clone() {
-            last_pressed_key.replace(key);
-        }
-        execute!(
-            std::io::stdout(),
-            crossterm::cursor::MoveToColumn(0),
-            Clear(crossterm::terminal::ClearType::CurrentLine),
-            Print(format!(
-                "{:?} {:?} {:?}",
-                cli_manager.pressed_key.read().unwrap(),
-                last_pressed_key,
-                &cpu
-            ),),
-        )
-        .unwrap();
-        // execute!(
-        //     std::io::stdout(),
-        //     Print(format!("{:?}", cli_manager.pressed_key.read().unwrap()))
-        // )
-        // .unwrap();
-        sleep(Duration::from_micros(500));
-    }
-    execute!(std::io::stdout(), crossterm::cursor::Show,).unwrap();
-    disable_raw_mode().unwrap();
+        // A paused CPU isn't stuck, it's waiting on whoever is debugging it
+        let stuck_at = if paused || step_mode || cpu.is_paused() {
+            None
+        } else {
+            watchdog.frame(cpu.step_count(), cpu.pc(), cpu.stats().last_opcode)
+        };
+        if let (Some(pc), true) = (stuck_at, args.watchdog_exit) {
+            return Ended::Stopped(Stopped::Stuck(pc));
+        }
+        last_key = cli_manager.pressed_key().or(last_key);
+        if frames.is_multiple_of(IPS_SAMPLE_FRAMES) {
+            ips_counter.sample(cpu.step_count(), Instant::now());
+        }
+        frames += 1;
+        let status_due = status_line.due(Instant::now());
+        if status_due {
+            controls = format!(
+                "{}  {}  IPS: {}  FPS: {}  DT {}  ST {}  Key {}  Speed {}  Slot {}",
+                rom_name,
+                if paused { "PAUSED" } else { "RUNNING" },
+                ips_counter.ips(),
+                ips_counter.fps(),
+                cpu.delay_timer(),
+                cpu.sound_timer(),
+                last_key.map_or("-".to_string(), |key| format!("{:X}", key)),
+                speed,
+                slot,
+            );
+            if sound.is_muted() {
+                controls.push_str("  Muted");
+            }
+            if args.record_input.is_some() {
+                controls.push_str("  Recording input");
+            } else if keypad.replay_finished() {
+                controls.push_str("  Replay finished");
+            } else if !keypad.is_live() {
+                controls.push_str("  Replaying input");
+            }
+            if !state_message.is_empty() {
+                controls = format!("{}  {}", state_message, controls);
+            }
+        }
+        if let Some(debugger) = debugger.as_ref() {
+            // The debugger shows the registers, so it redraws every frame
+            cli_manager.set_capture_keys(debugger.focus() == Focus::Debugger);
+            debugger
+                .draw(&cpu, &controls, &mut std::io::stdout())
+                .unwrap();
+        } else if status_due {
+            let status = match (stuck_at, &last_diff) {
+                (Some(pc), _) => format!("CPU appears stuck at PC=0x{:04X}", pc),
+                (None, Some(diff)) if step_mode => format!("PC={:04X} {}", cpu.pc(), diff),
+                (None, None) if step_mode => format!("PC={:04X} press Enter to step", cpu.pc()),
+                (None, _) => format!("{}  {}", controls, hotkey_help),
+            };
+            // Some terminals report no width, those get the whole line
+            let width = match size() {
+                Ok((columns, _)) if columns > 0 => usize::from(columns),
+                _ => usize::MAX,
+            };
+            status_line
+                .write(
+                    cli_manager.status_row(),
+                    width,
+                    &status,
+                    &mut std::io::stdout(),
+                )
+                .unwrap();
+        }
+        next_frame += FRAME_DURATION;
+        sleep(next_frame.saturating_duration_since(Instant::now()));
+    }
+}
+
+enum Ended {
+    // Ctrl-C
+    Quit,
+    // Esc, only when the ROM came from the list
+    Menu,
+    // The program ran 00FD
+    Exited,
+    Stopped(Stopped),
+}
+
+enum Stopped {
+    Error(Chip8Error),
+    Stuck(u16),
+    // The ROM doesn't fit in memory, or an input log couldn't be read or saved
+    File(String),
+}
+
+fn report_error(err: &Chip8Error) {
+    eprintln!("\nError: {}", err);
+    if let Chip8Error::InvalidOpcodeError(word) = err {
+        let matches = OpCodes::closest_matches(*word);
+        if !matches.is_empty() {
+            eprintln!("Did you mean:");
+            for (opcode, pattern) in matches {
+                eprintln!("  {} ({})", opcode, pattern);
+            }
+        }
+    }
 }
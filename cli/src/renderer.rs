@@ -0,0 +1,140 @@
+use chip8_core::{PixelBuffer, SCREEN_HEIGHT, SCREEN_WIDTH};
+use clap::ValueEnum;
+
+// How screen pixels are packed into terminal cells. Lit pixels use the foreground color and unlit
+// ones the background, so a blank cell is a space.
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Renderer {
+    // One cell per pixel, 64x32 cells
+    Block,
+    // Two pixels stacked in each cell, 64x16 cells. Terminal cells are about twice as tall as
+    // they're wide, so this gives square pixels.
+    #[default]
+    Halfblock,
+    // A 2x4 braille dot pattern per cell, 32x8 cells
+    Braille,
+}
+
+// Braille dot bits, indexed [y][x] within the 2x4 cell
+const BRAILLE_DOTS: [[u32; 2]; 4] = [[0x01, 0x08], [0x02, 0x10], [0x04, 0x20], [0x40, 0x80]];
+
+impl Renderer {
+    // Pixels per cell, (width, height)
+    fn cell_size(self) -> (u8, u8) {
+        return match self {
+            Renderer::Block => (1, 1),
+            Renderer::Halfblock => (1, 2),
+            Renderer::Braille => (2, 4),
+        };
+    }
+
+    pub fn columns(self) -> u16 {
+        return u16::from(SCREEN_WIDTH / self.cell_size().0);
+    }
+
+    pub fn lines(self) -> u16 {
+        return u16::from(SCREEN_HEIGHT / self.cell_size().1);
+    }
+
+    // Terminal lines showing any of the pixel rows set in dirty_rows, see Screen::take_dirty_rows
+    pub fn dirty_lines(self, dirty_rows: u32) -> impl Iterator<Item = u16> {
+        let height = u32::from(self.cell_size().1);
+        let mask = (1u32 << height) - 1;
+        return (0..self.lines()).filter(move |line| {
+            return (dirty_rows >> (u32::from(*line) * height)) & mask != 0;
+        });
+    }
+
+    pub fn line(self, buffer: &PixelBuffer, line: u16) -> String {
+        let (width, height) = self.cell_size();
+        let top = line as u8 * height;
+        return (0..SCREEN_WIDTH / width)
+            .map(|column| {
+                let left = column * width;
+                let lit = |dx: u8, dy: u8| buffer.get_pixel(left + dx, top + dy);
+                return match self {
+                    Renderer::Block => cell_char(lit(0, 0), lit(0, 0)),
+                    Renderer::Halfblock => cell_char(lit(0, 0), lit(0, 1)),
+                    Renderer::Braille => {
+                        let mut dots = 0;
+                        for (dy, row) in BRAILLE_DOTS.iter().enumerate() {
+                            for (dx, bit) in row.iter().enumerate() {
+                                if lit(dx as u8, dy as u8) {
+                                    dots |= bit;
+                                }
+                            }
+                        }
+                        char::from_u32(0x2800 + dots).unwrap()
+                    }
+                };
+            })
+            .collect();
+    }
+}
+
+fn cell_char(top: bool, bottom: bool) -> char {
+    return match (top, bottom) {
+        (true, true) => '█',
+        (true, false) => '▀',
+        (false, true) => '▄',
+        (false, false) => ' ',
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Lights the pixels marked # in rows drawn from the top left corner
+    fn buffer(rows: &[&str]) -> PixelBuffer {
+        let mut buffer = PixelBuffer::new();
+        for (y, row) in rows.iter().enumerate() {
+            for (x, pixel) in row.chars().enumerate() {
+                buffer.set_pixel(x as u8, y as u8, pixel == '#');
+            }
+        }
+        return buffer;
+    }
+
+    // The first len cells of a line, checking the line covers the whole screen width
+    fn cells(renderer: Renderer, pixels: &PixelBuffer, line: u16, len: usize) -> String {
+        let line = renderer.line(pixels, line);
+        assert_eq!(line.chars().count(), usize::from(renderer.columns()));
+        return line.chars().take(len).collect();
+    }
+
+    #[test]
+    fn block_is_one_cell_per_pixel() {
+        let pixels = buffer(&["#.#", ".#."]);
+        assert_eq!(Renderer::Block.lines(), 32);
+        assert_eq!(cells(Renderer::Block, &pixels, 0, 4), "█ █ ");
+        assert_eq!(cells(Renderer::Block, &pixels, 1, 4), " █  ");
+    }
+
+    #[test]
+    fn halfblock_stacks_two_rows() {
+        let pixels = buffer(&["##..", "#.#.", "", "#"]);
+        assert_eq!(Renderer::Halfblock.lines(), 16);
+        assert_eq!(cells(Renderer::Halfblock, &pixels, 0, 4), "█▀▄ ");
+        assert_eq!(cells(Renderer::Halfblock, &pixels, 1, 2), "▄ ");
+    }
+
+    #[test]
+    fn braille_packs_two_by_four() {
+        let pixels = buffer(&["#.##", ".#..", "..#.", "#..#", "##"]);
+        assert_eq!(Renderer::Braille.lines(), 8);
+        // Dots 1, 5 and 7, then 1, 4, 3 and 8
+        assert_eq!(cells(Renderer::Braille, &pixels, 0, 3), "⡑⢍⠀");
+        assert_eq!(cells(Renderer::Braille, &pixels, 1, 2), "⠉⠀");
+    }
+
+    #[test]
+    fn dirty_rows_map_to_lines() {
+        let rows = 1 << 0 | 1 << 5 | 1 << 31;
+        let lines = |renderer: Renderer| renderer.dirty_lines(rows).collect::<Vec<_>>();
+        assert_eq!(lines(Renderer::Block), [0, 5, 31]);
+        assert_eq!(lines(Renderer::Halfblock), [0, 2, 15]);
+        assert_eq!(lines(Renderer::Braille), [0, 1, 7]);
+        assert_eq!(Renderer::Braille.dirty_lines(0).count(), 0);
+    }
+}
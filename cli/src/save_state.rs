@@ -0,0 +1,52 @@
+use std::path::{Path, PathBuf};
+
+use chip8_core::Snapshot;
+
+pub const DEFAULT_SLOT: u8 = 1;
+
+// Save states live next to the ROM, slot 1 of game.ch8 is game.ch8.state1
+pub fn state_path(rom: &Path, slot: u8) -> PathBuf {
+    let mut path = rom.as_os_str().to_owned();
+    path.push(format!(".state{}", slot));
+    return PathBuf::from(path);
+}
+
+pub fn save_state(path: &Path, snapshot: &Snapshot) -> Result<(), String> {
+    return std::fs::write(path, snapshot.to_bytes())
+        .map_err(|err| format!("Could not save {}: {}", path.display(), err));
+}
+
+pub fn load_state(path: &Path) -> Result<Snapshot, String> {
+    let bytes =
+        std::fs::read(path).map_err(|err| format!("Could not read {}: {}", path.display(), err))?;
+    return Snapshot::from_bytes(&bytes)
+        .map_err(|err| format!("Could not load {}: {}", path.display(), err));
+}
+
+#[cfg(test)]
+mod tests {
+    use chip8_core::{PixelBuffer, TestCPU};
+
+    use super::*;
+
+    #[test]
+    fn saves_next_to_the_rom() {
+        assert_eq!(
+            state_path(Path::new("roms/pong.ch8"), 3),
+            PathBuf::from("roms/pong.ch8.state3")
+        );
+
+        let dir = std::env::temp_dir().join(format!("chip8-save-state-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = state_path(&dir.join("game.ch8"), DEFAULT_SLOT);
+        let snapshot = TestCPU::default().snapshot(PixelBuffer::new());
+        save_state(&path, &snapshot).unwrap();
+        assert_eq!(load_state(&path), Ok(snapshot));
+
+        std::fs::write(&path, b"garbage").unwrap();
+        let err = load_state(&path).unwrap_err();
+        assert!(err.ends_with("not a save state"), "{}", err);
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(load_state(&path).unwrap_err().starts_with("Could not read"));
+    }
+}
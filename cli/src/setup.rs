@@ -0,0 +1,69 @@
+use std::path::Path;
+
+use chip8_core::{rom::Rom, Chip8Error, Chip8Input, Chip8Screen, CPU};
+
+use crate::args::Args;
+
+// Everything that can fail on bad input happens here, before either mode touches the terminal
+pub fn read_rom(path: &Path) -> Result<Rom, String> {
+    let rom = match std::fs::read(path) {
+        Ok(data) => Rom::parse(&data).map_err(|err| err.to_string()),
+        Err(err) => Err(err.to_string()),
+    };
+    // The CPU refuses these as well, here there's a file to suggest fixing. Plenty of ROMs in
+    // circulation end in a stray padding byte.
+    let rom = rom.and_then(|rom| match rom.trailing_byte() {
+        Some(_) => Err(format!(
+            "{}. If the last byte is padding, append a zero byte to the file to run it",
+            Chip8Error::OddLengthRomError { len: rom.len() }
+        )),
+        None => Ok(rom),
+    });
+    return rom.map_err(|err| format!("Could not load {}: {}", path.display(), err));
+}
+
+// A CPU with the quirks from args and the ROM loaded. seed fixes the CXNN results, for runs whose
+// output gets compared against a previous one.
+pub fn new_cpu<'a, TScreen: Chip8Screen, TInput: Chip8Input>(
+    screen: &'a TScreen,
+    input: &'a TInput,
+    args: &Args,
+    rom: &Rom,
+    seed: Option<u64>,
+) -> Result<CPU<'a, TScreen, TInput>, Chip8Error> {
+    let mut cpu = match seed {
+        Some(seed) => CPU::new_seeded(screen, input, seed),
+        None => CPU::new(screen, input),
+    };
+    cpu.set_quirks(args.cpu_quirks());
+    cpu.load_rom(rom)?;
+    return Ok(cpu);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn odd_length_rom_suggests_padding() {
+        let dir = std::env::temp_dir().join(format!("chip8-setup-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("odd.ch8");
+        std::fs::write(&path, [0x60, 0x2A, 0x12]).unwrap();
+        let err = read_rom(&path).unwrap_err();
+        assert!(
+            err.starts_with(&format!(
+                "Could not load {}: ROM is 3 bytes",
+                path.display()
+            )),
+            "{}",
+            err
+        );
+        assert!(err.contains("append a zero byte"), "{}", err);
+
+        // Padded it loads
+        std::fs::write(&path, [0x60, 0x2A, 0x12, 0x00]).unwrap();
+        assert_eq!(read_rom(&path).unwrap().len(), 4);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
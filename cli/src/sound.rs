@@ -0,0 +1,269 @@
+use std::{
+    cell::Cell,
+    io::Write,
+    sync::mpsc::{self, RecvTimeoutError, Sender},
+    thread,
+    time::Duration,
+};
+
+use chip8_core::Chip8Sound;
+
+use crate::args::Args;
+
+// How often the bell rings again while a long tone keeps playing
+pub const BELL_REPEAT: Duration = Duration::from_millis(250);
+
+// The terminal bell is the only sound every terminal has. A thread rings it when a tone starts and
+// keeps ringing it until the tone stops, so long tones don't sound like short ones.
+pub struct BellSound {
+    tx: Sender<bool>,
+}
+
+impl BellSound {
+    pub fn new<W: Write + Send + 'static>(mut out: W) -> BellSound {
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let mut playing = false;
+            loop {
+                let next = if playing {
+                    rx.recv_timeout(BELL_REPEAT)
+                } else {
+                    rx.recv().map_err(|_| RecvTimeoutError::Disconnected)
+                };
+                let ring = match next {
+                    Ok(on) => {
+                        let started = on && !playing;
+                        playing = on;
+                        started
+                    }
+                    Err(RecvTimeoutError::Timeout) => true,
+                    Err(RecvTimeoutError::Disconnected) => return,
+                };
+                if ring {
+                    // Nothing useful to do if the terminal is gone
+                    let _ = out.write_all(b"\x07").and_then(|_| out.flush());
+                }
+            }
+        });
+        return BellSound { tx };
+    }
+}
+
+impl Chip8Sound for BellSound {
+    fn play(&self) {
+        let _ = self.tx.send(true);
+    }
+
+    fn stop(&self) {
+        let _ = self.tx.send(false);
+    }
+}
+
+// Passes play and stop through unless muted. Muting mid-tone stops it, unmuting mid-tone starts it.
+pub struct MutableSound {
+    inner: Box<dyn Chip8Sound>,
+    muted: Cell<bool>,
+    playing: Cell<bool>,
+}
+
+impl MutableSound {
+    pub fn new(inner: Box<dyn Chip8Sound>, muted: bool) -> MutableSound {
+        return MutableSound {
+            inner,
+            muted: Cell::new(muted),
+            playing: Cell::new(false),
+        };
+    }
+
+    pub fn is_muted(&self) -> bool {
+        return self.muted.get();
+    }
+
+    pub fn set_muted(&self, muted: bool) {
+        if self.muted.replace(muted) == muted || !self.playing.get() {
+            return;
+        }
+        if muted {
+            self.inner.stop();
+        } else {
+            self.inner.play();
+        }
+    }
+}
+
+impl Chip8Sound for MutableSound {
+    fn play(&self) {
+        self.playing.set(true);
+        if !self.muted.get() {
+            self.inner.play();
+        }
+    }
+
+    fn stop(&self) {
+        self.playing.set(false);
+        self.inner.stop();
+    }
+}
+
+// A square wave when built with the audio feature and an output device is available, the bell
+// otherwise. --mute starts it muted.
+pub fn sound_for(args: &Args) -> MutableSound {
+    return MutableSound::new(backend(args), args.mute);
+}
+
+fn backend(args: &Args) -> Box<dyn Chip8Sound> {
+    #[cfg(feature = "audio")]
+    match square_wave::SquareWave::new(args.beep_freq) {
+        Ok(wave) => return Box::new(wave),
+        Err(err) => eprintln!("No audio ({}), using the terminal bell", err),
+    }
+    #[cfg(not(feature = "audio"))]
+    let _ = args;
+    return Box::new(BellSound::new(std::io::stdout()));
+}
+
+#[cfg(feature = "audio")]
+mod square_wave {
+    use std::sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    };
+
+    use chip8_core::Chip8Sound;
+    use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+
+    // Full scale square waves are painfully loud
+    const AMPLITUDE: f32 = 0.1;
+
+    pub struct SquareWave {
+        playing: Arc<AtomicBool>,
+        // The stream keeps running, silent, until this is dropped
+        _stream: cpal::Stream,
+    }
+
+    impl SquareWave {
+        pub fn new(freq: u32) -> Result<SquareWave, String> {
+            let device = cpal::default_host()
+                .default_output_device()
+                .ok_or("no output device")?;
+            let config = device
+                .default_output_config()
+                .map_err(|err| err.to_string())?;
+            if config.sample_format() != cpal::SampleFormat::F32 {
+                return Err(format!(
+                    "unsupported sample format {:?}",
+                    config.sample_format()
+                ));
+            }
+            let channels = usize::from(config.channels());
+            // Fraction of a wave period per sample
+            let step = freq as f32 / config.sample_rate().0 as f32;
+            let playing = Arc::new(AtomicBool::new(false));
+            let on = playing.clone();
+            let mut phase = 0.0f32;
+            let stream = device
+                .build_output_stream(
+                    &config.config(),
+                    move |data: &mut [f32], _: &cpal::OutputCallbackInfo| {
+                        let on = on.load(Ordering::Relaxed);
+                        for frame in data.chunks_mut(channels) {
+                            phase = (phase + step) % 1.0;
+                            let sample = match (on, phase < 0.5) {
+                                (false, _) => 0.0,
+                                (true, true) => AMPLITUDE,
+                                (true, false) => -AMPLITUDE,
+                            };
+                            frame.fill(sample);
+                        }
+                    },
+                    |err| eprintln!("Audio error: {}", err),
+                    None,
+                )
+                .map_err(|err| err.to_string())?;
+            stream.play().map_err(|err| err.to_string())?;
+            return Ok(SquareWave {
+                playing,
+                _stream: stream,
+            });
+        }
+    }
+
+    impl Chip8Sound for SquareWave {
+        fn play(&self) {
+            self.playing.store(true, Ordering::Relaxed);
+        }
+
+        fn stop(&self) {
+            self.playing.store(false, Ordering::Relaxed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Reports every bell on a channel
+    struct Bells(Sender<()>);
+
+    impl Write for Bells {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            assert_eq!(buf, b"\x07");
+            self.0.send(()).unwrap();
+            return Ok(buf.len());
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            return Ok(());
+        }
+    }
+
+    #[derive(Default)]
+    struct Log(std::rc::Rc<std::cell::RefCell<Vec<&'static str>>>);
+
+    impl Chip8Sound for Log {
+        fn play(&self) {
+            self.0.borrow_mut().push("play");
+        }
+
+        fn stop(&self) {
+            self.0.borrow_mut().push("stop");
+        }
+    }
+
+    #[test]
+    fn muting_follows_the_tone() {
+        let log = Log::default();
+        let calls = log.0.clone();
+        let sound = MutableSound::new(Box::new(log), true);
+        sound.play();
+        assert!(calls.borrow().is_empty());
+        sound.set_muted(false);
+        sound.set_muted(false);
+        assert_eq!(*calls.borrow(), ["play"]);
+        sound.set_muted(true);
+        sound.stop();
+        sound.set_muted(false);
+        assert_eq!(*calls.borrow(), ["play", "stop", "stop"]);
+        assert!(!sound.is_muted());
+    }
+
+    #[test]
+    fn bell_rings_until_stopped() {
+        let (tx, bells) = mpsc::channel();
+        let sound = BellSound::new(Bells(tx));
+        assert!(bells.recv_timeout(BELL_REPEAT).is_err());
+
+        sound.play();
+        bells.recv_timeout(Duration::from_secs(5)).unwrap();
+        // A second play while ringing doesn't ring again straight away
+        sound.play();
+        assert!(bells.recv_timeout(BELL_REPEAT / 2).is_err());
+        bells.recv_timeout(Duration::from_secs(5)).unwrap();
+
+        sound.stop();
+        thread::sleep(BELL_REPEAT / 5);
+        bells.try_iter().count();
+        assert!(bells.recv_timeout(BELL_REPEAT * 3).is_err());
+    }
+}
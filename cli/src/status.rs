@@ -0,0 +1,184 @@
+use std::{
+    collections::VecDeque,
+    io::Write,
+    time::{Duration, Instant},
+};
+
+use crossterm::{
+    cursor::MoveTo,
+    queue,
+    style::Print,
+    terminal::{Clear, ClearType},
+};
+
+// Rewriting the status every frame flickers and costs more than running the CPU, a few times a
+// second is enough to read it
+pub const STATUS_INTERVAL: Duration = Duration::from_millis(250);
+// The instruction rate is sampled once a second at 60 frames a second and averaged over the last
+// few seconds, so one slow frame doesn't make it jump
+pub const IPS_SAMPLE_FRAMES: u64 = 60;
+pub const IPS_WINDOW: usize = 5;
+
+// Instructions per second from step counts sampled every IPS_SAMPLE_FRAMES frames. Knowing the
+// frames between samples also gives the frame rate, which drops when drawing can't keep up.
+#[derive(Default)]
+pub struct IpsCounter {
+    window: VecDeque<(u64, Instant)>,
+}
+
+impl IpsCounter {
+    pub fn new() -> IpsCounter {
+        return IpsCounter::default();
+    }
+
+    // steps is the CPU's step count, which starts over on a reset
+    pub fn sample(&mut self, steps: u64, now: Instant) {
+        if self.window.back().is_some_and(|(last, _)| steps < *last) {
+            self.window.clear();
+        }
+        if self.window.len() == IPS_WINDOW {
+            self.window.pop_front();
+        }
+        self.window.push_back((steps, now));
+    }
+
+    pub fn ips(&self) -> u64 {
+        let Some((steps, seconds)) = self.span() else {
+            return 0;
+        };
+        return (steps as f64 / seconds).round() as u64;
+    }
+
+    pub fn fps(&self) -> u64 {
+        let Some((_, seconds)) = self.span() else {
+            return 0;
+        };
+        let frames = (self.window.len() as u64 - 1) * IPS_SAMPLE_FRAMES;
+        return (frames as f64 / seconds).round() as u64;
+    }
+
+    // Steps and seconds between the oldest and newest sample
+    fn span(&self) -> Option<(u64, f64)> {
+        let ((first_steps, first_at), (last_steps, last_at)) =
+            (self.window.front()?, self.window.back()?);
+        let seconds = last_at.saturating_duration_since(*first_at).as_secs_f64();
+        if seconds == 0.0 {
+            return None;
+        }
+        return Some((last_steps - first_steps, seconds));
+    }
+}
+
+// The line under the screen. It only changes every STATUS_INTERVAL and only when the text differs
+// from what's already showing.
+#[derive(Default)]
+pub struct StatusLine {
+    shown: Option<String>,
+    updated_at: Option<Instant>,
+}
+
+impl StatusLine {
+    pub fn new() -> StatusLine {
+        return StatusLine::default();
+    }
+
+    // True when the status may change
+    pub fn due(&mut self, now: Instant) -> bool {
+        if let Some(updated_at) = self.updated_at {
+            if now.saturating_duration_since(updated_at) < STATUS_INTERVAL {
+                return false;
+            }
+        }
+        self.updated_at = Some(now);
+        return true;
+    }
+
+    // Writes text on row, cut to width so it never wraps onto the next line. False when the same
+    // text is already showing.
+    pub fn write<W: Write>(
+        &mut self,
+        row: u16,
+        width: usize,
+        text: &str,
+        out: &mut W,
+    ) -> std::io::Result<bool> {
+        let text = text.chars().take(width).collect::<String>();
+        if self.shown.as_ref() == Some(&text) {
+            return Ok(false);
+        }
+        queue!(
+            out,
+            MoveTo(0, row),
+            Clear(ClearType::CurrentLine),
+            Print(&text)
+        )?;
+        out.flush()?;
+        self.shown = Some(text);
+        return Ok(true);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn updates_a_few_times_a_second() {
+        let start = Instant::now();
+        let mut status = StatusLine::new();
+        assert!(status.due(start));
+        assert!(!status.due(start + Duration::from_millis(100)));
+        assert!(status.due(start + Duration::from_millis(250)));
+        assert!(!status.due(start + Duration::from_millis(400)));
+    }
+
+    fn assert_within_1_percent(actual: u64, expected: f64) {
+        let error = (actual as f64 - expected).abs() / expected;
+        assert!(error <= 0.01, "{} is not within 1% of {}", actual, expected);
+    }
+
+    #[test]
+    fn ips_averages_the_window() {
+        let start = Instant::now();
+        let mut counter = IpsCounter::new();
+        assert_eq!((counter.ips(), counter.fps()), (0, 0));
+        // --speed 11 at 60 frames a second, sampled every 60 frames
+        let second = Duration::from_secs(1);
+        for n in 0..10 {
+            counter.sample(n * 660, start + second * n as u32);
+        }
+        assert_within_1_percent(counter.ips(), 660.0);
+        assert_within_1_percent(counter.fps(), 60.0);
+
+        // Drawing falls behind, 60 frames take 1.2s from here on
+        let slow = start + second * 9;
+        for n in 1..=IPS_WINDOW as u32 {
+            counter.sample((9 + n as u64) * 660, slow + second * n * 6 / 5);
+        }
+        assert_within_1_percent(counter.ips(), 550.0);
+        assert_within_1_percent(counter.fps(), 50.0);
+
+        // A reset starts over rather than counting backwards
+        counter.sample(0, slow + second * 10);
+        assert_eq!(counter.ips(), 0);
+        counter.sample(700, slow + second * 11);
+        assert_within_1_percent(counter.ips(), 700.0);
+    }
+
+    #[test]
+    fn skips_redundant_writes() {
+        let mut status = StatusLine::new();
+        let mut out = vec![];
+        assert!(status.write(17, 80, "pong.ch8  RUNNING", &mut out).unwrap());
+        let written = out.len();
+        assert!(!status.write(17, 80, "pong.ch8  RUNNING", &mut out).unwrap());
+        assert_eq!(out.len(), written);
+        assert!(status.write(17, 80, "pong.ch8  PAUSED", &mut out).unwrap());
+
+        // Cut to the terminal width, so a longer line that only differs past it isn't rewritten
+        let mut out = vec![];
+        assert!(status.write(17, 4, "pong.ch8", &mut out).unwrap());
+        assert!(String::from_utf8(out).unwrap().ends_with("pong"));
+        assert!(!status.write(17, 4, "pong.c8", &mut vec![]).unwrap());
+    }
+}
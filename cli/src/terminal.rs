@@ -0,0 +1,151 @@
+use std::io::Write;
+
+use crossterm::{
+    cursor::{Hide, Show},
+    event::{KeyboardEnhancementFlags, PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags},
+    execute,
+    terminal::{disable_raw_mode, enable_raw_mode, Clear, ClearType},
+};
+
+// Where the guard sends its commands, raw mode isn't a command so it gets its own call
+pub trait TerminalSink: Write {
+    fn set_raw_mode(&mut self, enabled: bool) -> std::io::Result<()>;
+}
+
+impl TerminalSink for std::io::Stdout {
+    fn set_raw_mode(&mut self, enabled: bool) -> std::io::Result<()> {
+        if enabled {
+            return enable_raw_mode();
+        }
+        return disable_raw_mode();
+    }
+}
+
+// Puts the terminal in raw mode with a hidden cursor and a clear screen, and puts it back when
+// dropped, so every way out of main leaves a usable terminal. process::exit skips destructors,
+// drop the guard before calling it.
+pub struct TerminalGuard<S: TerminalSink> {
+    sink: S,
+    key_releases: bool,
+}
+
+impl<S: TerminalSink> TerminalGuard<S> {
+    // key_releases pushes the keyboard enhancement flags so the terminal reports key releases
+    pub fn new(mut sink: S, key_releases: bool) -> std::io::Result<TerminalGuard<S>> {
+        sink.set_raw_mode(true)?;
+        // From here on a failure still has to undo the raw mode, which the guard's drop does
+        let mut guard = TerminalGuard {
+            sink,
+            key_releases: false,
+        };
+        if key_releases {
+            execute!(
+                guard.sink,
+                PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::REPORT_EVENT_TYPES)
+            )?;
+            guard.key_releases = true;
+        }
+        execute!(guard.sink, Hide, Clear(ClearType::All))?;
+        return Ok(guard);
+    }
+}
+
+impl<S: TerminalSink> Drop for TerminalGuard<S> {
+    fn drop(&mut self) {
+        restore(&mut self.sink, self.key_releases);
+    }
+}
+
+// Best effort, there's nowhere left to report a failure while restoring
+fn restore<S: TerminalSink>(sink: &mut S, key_releases: bool) {
+    if key_releases {
+        let _ = execute!(sink, PopKeyboardEnhancementFlags);
+    }
+    let _ = execute!(sink, Show);
+    let _ = sink.set_raw_mode(false);
+}
+
+// Restores the terminal before the panic message is printed, otherwise raw mode mangles it and
+// leaves the shell unusable. The guard still runs while unwinding, restoring twice is harmless.
+pub fn install_panic_hook(key_releases: bool) {
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let mut stdout = std::io::stdout();
+        restore(&mut stdout, key_releases);
+        // Start the message on a fresh line below the screen
+        let _ = writeln!(stdout);
+        previous(info);
+    }));
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use crossterm::Command;
+
+    use super::*;
+
+    #[derive(Clone, Default)]
+    struct RecordingSink {
+        raw_mode: Rc<RefCell<Vec<bool>>>,
+        written: Rc<RefCell<String>>,
+    }
+
+    impl Write for RecordingSink {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.written
+                .borrow_mut()
+                .push_str(std::str::from_utf8(buf).unwrap());
+            return Ok(buf.len());
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            return Ok(());
+        }
+    }
+
+    impl TerminalSink for RecordingSink {
+        fn set_raw_mode(&mut self, enabled: bool) -> std::io::Result<()> {
+            self.raw_mode.borrow_mut().push(enabled);
+            return Ok(());
+        }
+    }
+
+    fn ansi(command: impl Command) -> String {
+        let mut out = String::new();
+        command.write_ansi(&mut out).unwrap();
+        return out;
+    }
+
+    #[test]
+    fn guard_restores_what_it_changed() {
+        for key_releases in [false, true] {
+            let sink = RecordingSink::default();
+            let guard = TerminalGuard::new(sink.clone(), key_releases).unwrap();
+            assert_eq!(*sink.raw_mode.borrow(), [true]);
+            drop(guard);
+            assert_eq!(*sink.raw_mode.borrow(), [true, false]);
+
+            let push = ansi(PushKeyboardEnhancementFlags(
+                KeyboardEnhancementFlags::REPORT_EVENT_TYPES,
+            ));
+            let pop = ansi(PopKeyboardEnhancementFlags);
+            let mut expected = vec![];
+            if key_releases {
+                expected.push(push);
+            }
+            expected.extend([ansi(Hide), ansi(Clear(ClearType::All))]);
+            if key_releases {
+                expected.push(pop);
+            }
+            expected.push(ansi(Show));
+            assert_eq!(
+                *sink.written.borrow(),
+                expected.concat(),
+                "{}",
+                key_releases
+            );
+        }
+    }
+}
@@ -0,0 +1,115 @@
+use chip8_core::OpCodes;
+
+pub const DEFAULT_INTERVAL_FRAMES: u64 = 300;
+pub const DEFAULT_THRESHOLD: u32 = 1;
+
+// Samples the CPU every `interval` frames and reports it as stuck once `threshold` samples in a
+// row found it either not executing anything or spinning on a jump to itself.
+pub struct Watchdog {
+    interval: u64,
+    threshold: u32,
+    frames: u64,
+    last_sample: Option<(u64, u16)>,
+    stuck_samples: u32,
+}
+
+impl Default for Watchdog {
+    fn default() -> Self {
+        Self::new(DEFAULT_INTERVAL_FRAMES, DEFAULT_THRESHOLD)
+    }
+}
+
+impl Watchdog {
+    pub fn new(interval: u64, threshold: u32) -> Watchdog {
+        return Watchdog {
+            interval: interval.max(1),
+            threshold: threshold.max(1),
+            frames: 0,
+            last_sample: None,
+            stuck_samples: 0,
+        };
+    }
+
+    // Call once per frame, returns the PC the CPU is stuck at
+    pub fn frame(&mut self, step_count: u64, pc: u16, last_opcode: Option<OpCodes>) -> Option<u16> {
+        self.frames += 1;
+        if !self.frames.is_multiple_of(self.interval) {
+            return self.stuck_at(pc);
+        }
+        let sample = (step_count, pc);
+        let stuck = match self.last_sample {
+            Some((last_steps, last_pc)) => {
+                let halted = last_steps == step_count;
+                let spinning = last_pc == pc && last_opcode == Some(OpCodes::_1NNN { nnn: pc });
+                halted || spinning
+            }
+            None => false,
+        };
+        self.last_sample = Some(sample);
+        self.stuck_samples = if stuck { self.stuck_samples + 1 } else { 0 };
+        return self.stuck_at(pc);
+    }
+
+    fn stuck_at(&self, pc: u16) -> Option<u16> {
+        if self.stuck_samples >= self.threshold {
+            return Some(pc);
+        }
+        return None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_jump_to_self_after_threshold() {
+        let mut watchdog = Watchdog::new(2, 2);
+        let spin = Some(OpCodes::_1NNN { nnn: 0x204 });
+        let mut steps = 0;
+        let mut reports = vec![];
+        for _ in 0..8 {
+            steps += 10;
+            reports.push(watchdog.frame(steps, 0x204, spin));
+        }
+        // First sample has nothing to compare with, then two stuck samples are needed
+        assert_eq!(
+            reports,
+            vec![
+                None,
+                None,
+                None,
+                None,
+                None,
+                Some(0x204),
+                Some(0x204),
+                Some(0x204)
+            ]
+        );
+    }
+
+    #[test]
+    fn busy_loops_are_not_stuck() {
+        let mut watchdog = Watchdog::new(1, 1);
+        let mut steps = 0;
+        for pc in [0x200, 0x202, 0x204, 0x200, 0x202] {
+            steps += 10;
+            assert_eq!(
+                watchdog.frame(steps, pc, Some(OpCodes::_7XNN { x: 0, nn: 1 })),
+                None
+            );
+        }
+        // Waiting on a key sits on one PC but isn't a jump to itself
+        let wait = Some(OpCodes::_FX0A { x: 0 });
+        assert_eq!(watchdog.frame(steps + 10, 0x206, wait), None);
+        assert_eq!(watchdog.frame(steps + 20, 0x206, wait), None);
+    }
+
+    #[test]
+    fn no_progress_is_stuck() {
+        let mut watchdog = Watchdog::new(1, 1);
+        assert_eq!(watchdog.frame(50, 0x210, None), None);
+        assert_eq!(watchdog.frame(50, 0x210, None), Some(0x210));
+        assert_eq!(watchdog.frame(60, 0x212, None), None);
+    }
+}
@@ -0,0 +1,133 @@
+// Runs tiny ROMs through the headless mode the way `chip8-cli --headless` does
+use chip8_cli::{
+    args::Args,
+    headless::{run_headless, HeadlessRun, Outcome},
+};
+use chip8_core::{rom::Rom, Chip8Error};
+use clap::Parser;
+
+fn rom(words: &[u16]) -> Rom {
+    let bytes = words
+        .iter()
+        .flat_map(|word| word.to_be_bytes())
+        .collect::<Vec<_>>();
+    return Rom::parse(&bytes).unwrap();
+}
+
+fn run(words: &[u16], options: &[&str]) -> HeadlessRun {
+    let args = ["chip8-cli", "--headless"]
+        .iter()
+        .chain(options)
+        .chain(&["test.ch8"]);
+    let args = Args::try_parse_from(args).unwrap();
+    return run_headless(&args, &rom(words), None).unwrap();
+}
+
+#[test]
+fn draws_and_exits_on_00fd() {
+    // V1 = 5, draw the 5 glyph at (0, 0), exit. The 1 after the exit never runs.
+    let run = run(&[0x6105, 0xF129, 0xD005, 0x00FD, 0x7101], &[]);
+    assert_eq!(run.outcome, Outcome::Exited);
+    assert_eq!(run.exit_code(), 0);
+    assert_eq!(run.steps, 3);
+    assert_eq!(run.state.v[1], 5);
+    assert_eq!(run.state.pc, 0x206);
+    let lines = run.screen.draw_as_string();
+    let lines = lines.lines().take(5).map(str::trim_end).collect::<Vec<_>>();
+    assert_eq!(lines, ["████", "█", "████", "   █", "████"]);
+    assert!(run
+        .to_json()
+        .starts_with(r#"{"outcome":"exited","cycles":11,"steps":3,"pc":518,"i":"#));
+}
+
+#[test]
+fn stops_after_the_cycle_count() {
+    // V0 += 1 forever
+    let run = run(&[0x7001, 0x1200], &["--cycles", "25", "--speed", "10"]);
+    assert_eq!(run.outcome, Outcome::Finished);
+    assert_eq!((run.cycles, run.steps), (25, 25));
+    assert_eq!(run.state.v[0], 13);
+    assert_eq!(run.exit_code(), 0);
+}
+
+#[test]
+fn exit_on_halt_stops_at_a_jump_to_itself() {
+    let options = [
+        "--exit-on-halt",
+        "--watchdog-interval",
+        "2",
+        "--cycles",
+        "1000",
+    ];
+    let run = run(&[0x6001, 0x1202], &options);
+    assert_eq!(run.outcome, Outcome::Stuck(0x202));
+    assert!(run.cycles < 1000);
+    assert!(run.to_json().contains(r#""stuck_at":514,"error":null}"#));
+    assert_eq!(run.exit_code(), 0);
+}
+
+#[test]
+fn errors_exit_non_zero() {
+    let run = run(&[0x6001, 0xFFFF], &[]);
+    assert_eq!(
+        run.outcome,
+        Outcome::Error(Chip8Error::InvalidOpcodeError(0xFFFF))
+    );
+    assert_eq!(run.exit_code(), 1);
+    assert_eq!(run.state.pc, 0x202);
+    assert!(run
+        .to_json()
+        .ends_with(r#""error":"Invalid opcode: 0xFFFF"}"#));
+}
+
+#[test]
+fn dumps_the_screen() {
+    let dir = std::env::temp_dir().join(format!("chip8-headless-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let text = dir.join("screen.txt");
+    let ppm = dir.join("screen.ppm");
+    let args = Args::try_parse_from([
+        "chip8-cli".as_ref(),
+        "--headless".as_ref(),
+        "--dump-screen".as_ref(),
+        text.as_os_str(),
+        "--dump-ppm".as_ref(),
+        ppm.as_os_str(),
+        "test.ch8".as_ref(),
+    ])
+    .unwrap();
+    let run = run_headless(&args, &rom(&[0xD005, 0x00FD]), None).unwrap();
+    run.write_dumps(&args).unwrap();
+    assert_eq!(
+        std::fs::read_to_string(&text).unwrap(),
+        run.screen.draw_as_string()
+    );
+    assert!(std::fs::read(&ppm)
+        .unwrap()
+        .starts_with(b"P6\n64 32\n255\n"));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn stops_at_a_conditional_breakpoint() {
+    // V0 += 1, I = V0's glyph, forever
+    let words = [0x7001, 0xF029, 0x1200];
+    let stopped = run(&words, &["--break", "0x202 when V0 == 5"]);
+    assert_eq!(stopped.outcome, Outcome::Break(0x202));
+    assert_eq!((stopped.state.pc, stopped.state.v[0]), (0x202, 5));
+    assert_eq!(stopped.exit_code(), 0);
+    assert!(stopped.to_json().starts_with(r#"{"outcome":"break","#));
+
+    // I first changes once V0 is 1, it was 0 before the first F029
+    let stopped = run(
+        &words,
+        &["--break", "when I changes", "--break", "when V0 > 9"],
+    );
+    assert_eq!(stopped.outcome, Outcome::Break(0x204));
+    assert_eq!(stopped.state.v[0], 1);
+    let stopped = run(&words, &["--break", "when V0 > 9"]);
+    assert_eq!(
+        (stopped.outcome, stopped.state.v[0]),
+        (Outcome::Break(0x202), 10)
+    );
+}
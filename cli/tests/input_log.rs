@@ -0,0 +1,91 @@
+// Records a scripted run the way --record-input does, then plays the log back through
+// `chip8-cli --headless --replay-input` and checks it ends on the same screen
+use chip8_cli::{
+    args::Args,
+    headless::{run_headless, Outcome},
+    input_log::{InputLog, LoggedKeypad},
+    setup::new_cpu,
+};
+use chip8_core::{rom::Rom, ReplayKeypad, Screen, KEY_RELEASED};
+use clap::Parser;
+
+const SPEED: usize = 7;
+const FRAMES: usize = 40;
+
+// Waits for a key, then draws its glyph at a random X below the last one, forever
+const WAIT_AND_DRAW: [u16; 6] = [0xF00A, 0xF029, 0xC138, 0xD125, 0x7205, 0x1200];
+
+fn rom() -> Rom {
+    let bytes = WAIT_AND_DRAW
+        .iter()
+        .flat_map(|word| word.to_be_bytes())
+        .collect::<Vec<_>>();
+    return Rom::parse(&bytes).unwrap();
+}
+
+// Keyboard events before each frame, including a press and release within one frame
+fn script(frame: usize) -> &'static [u8] {
+    return match frame {
+        5 => &[0x3],
+        8 => &[0x3 | KEY_RELEASED],
+        15 => &[0xA, 0xA | KEY_RELEASED],
+        25 => &[0x7],
+        30 => &[0x7 | KEY_RELEASED],
+        _ => &[],
+    };
+}
+
+#[test]
+fn replaying_a_recording_ends_on_the_same_screen() {
+    let args = Args::try_parse_from(["chip8-cli", "test.ch8"]).unwrap();
+    let rom = rom();
+    let keyboard = ReplayKeypad::default();
+    let keypad = LoggedKeypad::recording(&keyboard);
+    let screen = Screen::new();
+    let seed = 0x5EED;
+    {
+        let mut cpu = new_cpu(&screen, &keypad, &args, &rom, Some(seed)).unwrap();
+        for frame in 0..FRAMES {
+            script(frame)
+                .iter()
+                .for_each(|event| keyboard.apply(*event));
+            cpu.step_frame(SPEED).unwrap();
+        }
+        assert_eq!(cpu.step_count(), (FRAMES * SPEED) as u64);
+    }
+    let recorded = screen.draw_as_string();
+    assert!(recorded.contains('█'));
+    assert_eq!(keypad.events().len(), 6);
+
+    let path = std::env::temp_dir().join(format!("chip8-input-log-{}.c8in", std::process::id()));
+    InputLog {
+        seed,
+        speed: SPEED,
+        rom_checksum: rom.checksum(),
+        events: keypad.events(),
+    }
+    .save(&path)
+    .unwrap();
+    let log = InputLog::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(log.mismatch_warning(&path, rom.checksum()), None);
+
+    let cycles = (FRAMES * SPEED).to_string();
+    let args = Args::try_parse_from([
+        "chip8-cli".as_ref(),
+        "--headless".as_ref(),
+        "--cycles".as_ref(),
+        cycles.as_ref(),
+        "--replay-input".as_ref(),
+        path.as_os_str(),
+        "test.ch8".as_ref(),
+    ])
+    .unwrap();
+    let run = run_headless(&args, &rom, Some(&log)).unwrap();
+    assert_eq!(run.outcome, Outcome::Finished);
+    assert_eq!(run.screen.draw_as_string(), recorded);
+
+    // Without the log nothing is ever pressed
+    let run = run_headless(&args, &rom, None).unwrap();
+    assert_ne!(run.screen.draw_as_string(), recorded);
+}
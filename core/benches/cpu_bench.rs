@@ -0,0 +1,48 @@
+use std::hint::black_box;
+
+use chip8_core::{convert_u8_into_opcodes, Chip8Screen, Screen};
+use criterion::{criterion_group, criterion_main, Criterion};
+
+const SPRITE: [u8; 8] = [0xFF, 0x81, 0xBD, 0xA5, 0xA5, 0xBD, 0x81, 0xFF];
+
+fn draw_sprite(c: &mut Criterion) {
+    let screen = Screen::new();
+    // Moving one pixel each time covers the unaligned byte case
+    let mut x = 0u8;
+    c.bench_function("Screen::draw_sprite 8 rows", |b| {
+        b.iter(|| {
+            x = x.wrapping_add(1) % 64;
+            black_box(screen.draw_sprite(black_box(x), 12, black_box(&SPRITE)));
+        })
+    });
+}
+
+fn draw_as_string(c: &mut Criterion) {
+    let screen = Screen::new();
+    // A diagonal of sprites, so most lines mix lit and unlit cells
+    for n in 0..8 {
+        screen.draw_sprite(n * 8, n * 3, &SPRITE);
+    }
+    c.bench_function("Screen::draw_as_string", |b| {
+        b.iter(|| black_box(screen.draw_as_string()))
+    });
+}
+
+fn convert_4kb(c: &mut Criterion) {
+    // Every word decodes, 6XNN with X and NN varying
+    let buffer = (0..4096)
+        .map(|n| {
+            if n % 2 == 0 {
+                0x60 | (n / 2 % 16) as u8
+            } else {
+                n as u8
+            }
+        })
+        .collect::<Vec<_>>();
+    c.bench_function("convert_u8_into_opcodes 4KB", |b| {
+        b.iter(|| black_box(convert_u8_into_opcodes(black_box(&buffer)).unwrap()))
+    });
+}
+
+criterion_group!(benches, draw_sprite, draw_as_string, convert_4kb);
+criterion_main!(benches);
@@ -0,0 +1,70 @@
+use std::hint::black_box;
+
+use chip8_core::{Chip8CPU, NoopInput, OpCodes, ProgramBuilder, Screen, CPU};
+use criterion::{criterion_group, criterion_main, Criterion};
+
+const STEPS: usize = 1000;
+
+fn alu_loop() -> Vec<u8> {
+    let mut builder = ProgramBuilder::new();
+    let top = builder.label("top");
+    builder
+        .push(OpCodes::_6XNN { x: 1, nn: 0x03 })
+        .bind(top)
+        .push(OpCodes::_7XNN { x: 0, nn: 0x01 })
+        .push(OpCodes::_8XY4 { x: 2, y: 0 })
+        .push(OpCodes::_8XY3 { x: 3, y: 2 })
+        .push(OpCodes::_8XY5 { x: 4, y: 1 })
+        .push(OpCodes::_8XYE { x: 5, y: 3 })
+        .jump_to(top);
+    return builder.finish();
+}
+
+fn draw_loop() -> Vec<u8> {
+    let mut builder = ProgramBuilder::new();
+    let top = builder.label("top");
+    builder
+        .push(OpCodes::_ANNN { nnn: 0x050 })
+        .bind(top)
+        .push(OpCodes::_7XNN { x: 0, nn: 0x03 })
+        .push(OpCodes::_7XNN { x: 1, nn: 0x05 })
+        .push(OpCodes::_DXYN { x: 0, y: 1, n: 5 })
+        .push(OpCodes::_DXYN { x: 1, y: 0, n: 5 })
+        .jump_to(top);
+    return builder.finish();
+}
+
+fn bench_program(c: &mut Criterion, name: &str, program: &[u8]) {
+    let screen = Screen::new();
+    let mut cpu = CPU::new(&screen, &NoopInput);
+    cpu.load_program(program).unwrap();
+    c.bench_function(name, |b| {
+        b.iter(|| {
+            for _ in 0..STEPS {
+                cpu.step().unwrap();
+            }
+        })
+    });
+}
+
+fn step_alu(c: &mut Criterion) {
+    bench_program(c, "step alu loop x1000", &alu_loop());
+}
+
+fn step_draw(c: &mut Criterion) {
+    bench_program(c, "step draw loop x1000", &draw_loop());
+}
+
+fn decode(c: &mut Criterion) {
+    c.bench_function("decode every word", |b| {
+        b.iter(|| {
+            for word in 0..=u16::MAX {
+                let [op1, op2] = word.to_be_bytes();
+                let _ = black_box(OpCodes::try_from(black_box((op1, op2))));
+            }
+        })
+    });
+}
+
+criterion_group!(benches, step_alu, step_draw, decode);
+criterion_main!(benches);
@@ -1,5 +1,4 @@
-use std::io::Write;
-use std::{thread::sleep, time::Duration};
+use std::time::Duration;
 
 use crossterm::execute;
 use crossterm::{
@@ -22,7 +21,7 @@ fn main() {
                     break;
                 }
                 crossterm::event::Event::Key(KeyEvent { code, .. }) => {
-                    write!(std::io::stdout(), "{:?}\n", code).unwrap();
+                    println!("{:?}", code);
                     execute!(
                         std::io::stdout(),
                         crossterm::cursor::MoveToColumn(0),
@@ -1,29 +1,24 @@
 use std::io::Read;
 
-use chip8_core::OpCodes;
+use chip8_core::{Chip8Error, OpCodeIter, PGRM_LOAD_START_ADDR};
 
 fn main() {
     let mut file = std::fs::File::open("test.ch8").unwrap();
     let mut buffer = vec![];
     file.read_to_end(&mut buffer).unwrap();
     // hexdump::hexdump(buffer.as_slice());
-    buffer
-        .chunks(2)
-        .filter_map(|chunk| {
-            // Convert chunk to tuple if it has 2 elements
-            if chunk.len() == 2 {
-                if let Ok(opcode) = OpCodes::try_from((chunk[0], chunk[1])) {
-                    Some(format!("{:?}", opcode))
-                } else {
-                    Some(format!("0x{:02X}{:02X}", chunk[0], chunk[1]))
-                }
-            } else {
-                None // Ignore incomplete chunks
+    let mut words = OpCodeIter::new(buffer.as_slice(), PGRM_LOAD_START_ADDR);
+    for (address, opcode) in words.by_ref() {
+        match opcode {
+            Ok(opcode) => println!("0x{:04X}: {}", address, opcode),
+            Err(Chip8Error::InvalidOpcodeError(word)) => {
+                println!("0x{:04X}: 0x{:04X}", address, word)
             }
-        })
-        .enumerate()
-        .for_each(|(i, f)| {
-            let i = i + 0x200;
-            println!("0x{:04X}: {}", i, f)
-        });
+            Err(err) => println!("0x{:04X}: {}", address, err),
+        }
+    }
+    if let Some(byte) = words.trailing_byte() {
+        let address = PGRM_LOAD_START_ADDR as usize + buffer.len() - 1;
+        println!("0x{:04X}: 0x{:02X}", address, byte);
+    }
 }
@@ -1,24 +1,22 @@
-use std::{
-    f32::consts::E,
-    fmt::Debug,
-    io::Write,
-    mem, thread,
-    time::{Duration, Instant},
-};
+use std::{fmt::Debug, ops::Range};
 
-use rand::Rng;
+use rand::{rngs::SmallRng, Rng, SeedableRng};
 
 use crate::{
     opcodes::{Chip8Error, OpCodes},
-    Chip8Input, Chip8Screen,
+    rom::Rom,
+    Chip8Input, Chip8Quirks, Chip8Screen, Chip8Sound, CpuState, MachineCodePolicy, NoopInput,
+    NoopScreen, PixelBuffer, Snapshot,
 };
 
-const PGRM_LOAD_START_ADDR: u16 = 0x200;
+pub const PGRM_LOAD_START_ADDR: u16 = 0x200;
 const FONT_START_ADDR: u16 = 0x50;
+// Straight after the small font
+const LARGE_FONT_START_ADDR: u16 = FONT_START_ADDR + FONT_BUFFER.len() as u16;
 
 trait RegistryUtils {
     fn nth(&self, n: u8) -> u8;
-    fn set(&mut self, index: u8, value: u8) -> ();
+    fn set(&mut self, index: u8, value: u8);
 }
 
 impl RegistryUtils for [u8] {
@@ -26,7 +24,7 @@ impl RegistryUtils for [u8] {
         return self[n as usize];
     }
 
-    fn set(&mut self, index: u8, value: u8) -> () {
+    fn set(&mut self, index: u8, value: u8) {
         self[index as usize] = value;
     }
 }
@@ -51,14 +49,84 @@ const FONT_BUFFER : [u8; 80] = [
     0xF0, 0x80, 0xF0, 0x80, 0x80,
 ];
 
+// SUPER-CHIP's 8x10 digits 0-9 for FX30
+#[rustfmt::skip]
+const LARGE_FONT_BUFFER : [u8; 100] = [
+    0x3C, 0x7E, 0xE7, 0xC3, 0xC3, 0xC3, 0xC3, 0xE7, 0x7E, 0x3C,
+    0x18, 0x38, 0x58, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x3C,
+    0x3E, 0x7F, 0xC3, 0x06, 0x0C, 0x18, 0x30, 0x60, 0xFF, 0xFF,
+    0x3C, 0x7E, 0xC3, 0x03, 0x0E, 0x0E, 0x03, 0xC3, 0x7E, 0x3C,
+    0x06, 0x0E, 0x1E, 0x36, 0x66, 0xC6, 0xFF, 0xFF, 0x06, 0x06,
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFC, 0xFE, 0x03, 0xC3, 0x7E, 0x3C,
+    0x3E, 0x7C, 0xC0, 0xC0, 0xFC, 0xFE, 0xC3, 0xC3, 0x7E, 0x3C,
+    0xFF, 0xFF, 0x03, 0x06, 0x0C, 0x18, 0x30, 0x60, 0x60, 0x60,
+    0x3C, 0x7E, 0xC3, 0xC3, 0x7E, 0x7E, 0xC3, 0xC3, 0x7E, 0x3C,
+    0x3C, 0x7E, 0xC3, 0xC3, 0x7F, 0x3F, 0x03, 0x03, 0x3E, 0x7C,
+];
+
+// Both fonts, loads can't write over them
+const FONT_AREA: Range<u16> =
+    FONT_START_ADDR..LARGE_FONT_START_ADDR + LARGE_FONT_BUFFER.len() as u16;
+// Where the stack grows down from 0xFFF, only load_at_program_counter checks it
+#[cfg(test)]
+const STACK_AREA: Range<u16> = 0xECF..0x1000;
+
 pub trait Chip8CPU {
-    fn step(&mut self) -> Result<(), Chip8Error>;
+    #[must_use = "step errors indicate a halted CPU; ignoring them masks bugs"]
+    fn step(&mut self) -> Result<StepResult, Chip8Error>;
+
+    // Back to power-on state with the program cleared
+    fn reset(&mut self);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepResult {
+    Executed,
+    // The pre-step hook skipped the instruction, the PC moved past it without running it
+    Skipped,
+    // Nothing runs until resume() is called
+    Paused,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookAction {
+    Continue,
+    SkipInstruction,
+    Pause,
+}
+
+// Read-only state handed to the pre-step hook, next_opcode is the instruction about to run
+#[derive(Debug)]
+pub struct CpuView<'v> {
+    pub pc: u16,
+    pub i: u16,
+    pub v: &'v [u8; 16],
+    pub next_opcode: OpCodes,
+}
+
+pub type PreStepHook<'a> = Box<dyn FnMut(&CpuView) -> HookAction + 'a>;
+
+// Receives NNN of a 0NNN when the machine_code quirk is MachineCodePolicy::Trap
+pub type MachineCodeTrap<'a> = Box<dyn FnMut(u16, &CpuView) + 'a>;
+
+// Bytes written over memory after every load and reset, e.g. to keep a lives counter from changing
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Patch {
+    pub addr: u16,
+    pub bytes: Vec<u8>,
+}
+
+impl Patch {
+    fn range(&self) -> Range<usize> {
+        let start = self.addr as usize;
+        return start..start + self.bytes.len();
+    }
 }
 
 pub struct CPU<'a, TScreen, TInput>
 where
-    TScreen: Chip8Screen,
-    TInput: Chip8Input,
+    TScreen: Chip8Screen + ?Sized,
+    TInput: Chip8Input + ?Sized,
 {
     // memory: Box<[u8; 65536]>,
     memory: Box<[u8; 4096]>,
@@ -70,15 +138,48 @@ where
     stack_ptr: u16,
     screen: &'a TScreen,
     input: &'a TInput,
-    last_decrement: Instant,
+    quirks: Chip8Quirks,
+    rng: SmallRng,
+    stats: CpuStats,
+    pre_step_hook: Option<PreStepHook<'a>>,
+    paused: bool,
+    // Set by resume() so the instruction the hook paused on runs instead of pausing again
+    skip_hook_once: bool,
+    patches: Vec<Patch>,
+    machine_code_trap: Option<MachineCodeTrap<'a>>,
+    skip_non_fatal_errors: bool,
+    sound_output: Option<&'a dyn Chip8Sound>,
+    // SUPER-CHIP's RPL user flags, FX75 / FX85. They survive reset() like they survived power
+    // cycles on the HP-48.
+    rpl_flags: [u8; 8],
+    // SUPER-CHIP high resolution mode, where DXY0 draws a 16x16 sprite
+    hires: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CpuStats {
+    // Successfully executed instructions
+    pub steps: u64,
+    // Timer ticks, one per 60Hz frame
+    pub frames: u64,
+    pub last_opcode: Option<OpCodes>,
 }
 
 impl<'a, TScreen, TInput> CPU<'a, TScreen, TInput>
 where
-    TScreen: Chip8Screen,
-    TInput: Chip8Input,
+    TScreen: Chip8Screen + ?Sized,
+    TInput: Chip8Input + ?Sized,
 {
     pub fn new(screen: &'a TScreen, input: &'a TInput) -> Self {
+        return CPU::with_rng(screen, input, SmallRng::from_entropy());
+    }
+
+    // Same seed, same CXNN results, for tests and replays
+    pub fn new_seeded(screen: &'a TScreen, input: &'a TInput, seed: u64) -> Self {
+        return CPU::with_rng(screen, input, SmallRng::seed_from_u64(seed));
+    }
+
+    fn with_rng(screen: &'a TScreen, input: &'a TInput, rng: SmallRng) -> Self {
         let mut cpu = CPU {
             // memory: Box::new([0; 65536]),
             memory: Box::new([0; 4096]),
@@ -90,17 +191,33 @@ where
             stack_ptr: 0xFFF,
             screen,
             input,
-            last_decrement: Instant::now(),
+            quirks: Chip8Quirks::default(),
+            rng,
+            stats: CpuStats::default(),
+            pre_step_hook: None,
+            paused: false,
+            skip_hook_once: false,
+            patches: vec![],
+            machine_code_trap: None,
+            skip_non_fatal_errors: false,
+            sound_output: None,
+            rpl_flags: [0; 8],
+            hires: false,
         };
 
-        cpu.memory[0x50..]
-            .as_mut()
-            .write_all(&FONT_BUFFER)
-            .expect("Failed to write font data into memory");
+        cpu.load_font();
 
         return cpu;
     }
 
+    fn load_font(&mut self) {
+        let font_start = FONT_START_ADDR as usize;
+        self.memory[font_start..font_start + FONT_BUFFER.len()].copy_from_slice(&FONT_BUFFER);
+        let large_font_start = LARGE_FONT_START_ADDR as usize;
+        self.memory[large_font_start..large_font_start + LARGE_FONT_BUFFER.len()]
+            .copy_from_slice(&LARGE_FONT_BUFFER);
+    }
+
     pub fn reset(&mut self) {
         self.pc = 0x200;
         self.stack_ptr = 0xFFF;
@@ -108,53 +225,379 @@ where
         self.v.fill(0);
         self.i = 0;
         self.timer = 0;
-        self.sound = 0;
+        self.set_sound_timer(0);
+        self.stats = CpuStats::default();
+        // The hook stays installed, only the pause is cleared
+        self.paused = false;
+        self.skip_hook_once = false;
+        self.hires = false;
         self.screen.clear();
+        // The font is part of the interpreter, not the program
+        self.load_font();
+        self.apply_patches();
     }
 
-    pub fn load_into_memory(&mut self, start_addr: u16, data: &[u8]) -> Result<(), std::io::Error> {
-        self.memory[start_addr as usize..start_addr as usize + data.len()]
-            .as_mut()
-            .write_all(data)
+    #[must_use = "a failed load leaves memory untouched; ignoring it runs the wrong program"]
+    pub fn load_into_memory(&mut self, start_addr: u16, data: &[u8]) -> Result<(), Chip8Error> {
+        self.check_fits(start_addr, data.len())?;
+        check_unprotected(start_addr, data.len(), FONT_AREA)?;
+        let start = start_addr as usize;
+        self.memory[start..start + data.len()].copy_from_slice(data);
+        return Ok(());
     }
 
-    pub(crate) fn load_at_program_counter(&mut self, data: &[u8]) -> Result<(), std::io::Error> {
+    fn check_fits(&self, start_addr: u16, size: usize) -> Result<(), Chip8Error> {
+        let available = self.memory.len().saturating_sub(start_addr as usize);
+        if size > available {
+            return Err(Chip8Error::RomTooLargeError { size, available });
+        }
+        return Ok(());
+    }
+
+    // Drops code in at the PC mid-run, for tests that carry on from an earlier program's state
+    #[cfg(test)]
+    pub(crate) fn load_at_program_counter(&mut self, data: &[u8]) -> Result<(), Chip8Error> {
+        check_unprotected(self.pc, data.len(), STACK_AREA)?;
         self.load_into_memory(self.pc, data)
     }
 
-    pub fn load_program(&mut self, data: &[u8]) -> Result<(), std::io::Error> {
-        self.load_into_memory(PGRM_LOAD_START_ADDR, data)
+    pub fn quirks(&self) -> Chip8Quirks {
+        return self.quirks;
+    }
+
+    pub fn set_quirks(&mut self, quirks: Chip8Quirks) {
+        self.quirks = quirks;
+    }
+
+    pub fn pc(&self) -> u16 {
+        return self.pc;
+    }
+
+    pub fn delay_timer(&self) -> u8 {
+        return self.timer;
+    }
+
+    pub fn sound_timer(&self) -> u8 {
+        return self.sound;
+    }
+
+    // Timers count down at 60Hz, the caller is responsible for calling this (or step_frame) once per frame
+    pub fn tick_timers(&mut self) {
+        self.timer = self.timer.saturating_sub(1);
+        self.set_sound_timer(self.sound.saturating_sub(1));
+        self.stats.frames += 1;
+    }
+
+    // Told when the sound timer starts and stops, without one the timer still runs silently
+    pub fn set_sound_output(&mut self, sound: &'a dyn Chip8Sound) {
+        self.sound_output = Some(sound);
+    }
+
+    fn set_sound_timer(&mut self, value: u8) {
+        let was_playing = self.sound > 0;
+        self.sound = value;
+        match (&self.sound_output, was_playing, value > 0) {
+            (Some(sound), false, true) => sound.play(),
+            (Some(sound), true, false) => sound.stop(),
+            _ => {}
+        }
+    }
+
+    pub fn stats(&self) -> CpuStats {
+        return self.stats;
+    }
+
+    // Registers, timers and the call stack, diff two of these to see what an instruction changed
+    pub fn state(&self) -> CpuState {
+        let mut stack = vec![];
+        let mut addr = 0xFFE;
+        while addr > self.stack_ptr {
+            let addr_usize = addr as usize;
+            stack.push(u16::from_be_bytes([
+                self.memory[addr_usize],
+                self.memory[addr_usize + 1],
+            ]));
+            addr -= 2;
+        }
+        return CpuState {
+            v: self.v,
+            i: self.i,
+            pc: self.pc,
+            delay_timer: self.timer,
+            sound_timer: self.sound,
+            stack,
+            memory: None,
+        };
+    }
+
+    // Like state() but with a copy of memory so diffs include the changed ranges
+    pub fn state_with_memory(&self) -> CpuState {
+        return CpuState {
+            memory: Some(self.memory.to_vec()),
+            ..self.state()
+        };
+    }
+
+    // The CPU only draws to its screen, the caller passes in what's on it
+    pub fn snapshot(&self, screen: PixelBuffer) -> Snapshot {
+        return Snapshot {
+            v: self.v,
+            i: self.i,
+            pc: self.pc,
+            stack_ptr: self.stack_ptr,
+            delay_timer: self.timer,
+            sound_timer: self.sound,
+            hires: self.hires,
+            rpl_flags: self.rpl_flags,
+            memory: self.memory.clone(),
+            screen,
+        };
+    }
+
+    // Restores everything but the screen, which the caller puts back from snapshot.screen
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        self.v = snapshot.v;
+        self.i = snapshot.i;
+        self.pc = snapshot.pc;
+        self.stack_ptr = snapshot.stack_ptr;
+        self.timer = snapshot.delay_timer;
+        self.set_sound_timer(snapshot.sound_timer);
+        self.hires = snapshot.hires;
+        self.rpl_flags = snapshot.rpl_flags;
+        self.memory.copy_from_slice(&snapshot.memory[..]);
+        self.stats.last_opcode = None;
+        self.paused = false;
+        self.skip_hook_once = false;
+    }
+
+    // Instructions executed since creation or the last reset, if this stops moving the CPU is stuck
+    pub fn step_count(&self) -> u64 {
+        return self.stats.steps;
+    }
+
+    // Runs one 60Hz frame: the given number of instructions followed by a single timer tick
+    // A pause ends the frame early without ticking the timers
+    pub fn step_frame(&mut self, instructions: usize) -> Result<(), Chip8Error> {
+        for _ in 0..instructions {
+            match self.step() {
+                Ok(StepResult::Paused) => return Ok(()),
+                Ok(_) => {}
+                // Non-fatal errors come from the instruction at PC, skip past it
+                Err(err) if self.skip_non_fatal_errors && !err.is_fatal() => self.pc += 2,
+                Err(err) => return Err(err),
+            }
+        }
+        self.tick_timers();
+        return Ok(());
+    }
+
+    // Only DXY0 looks at this for now, there's no 128x64 screen to switch to
+    pub fn set_hires(&mut self, hires: bool) {
+        self.hires = hires;
+    }
+
+    pub fn is_hires(&self) -> bool {
+        return self.hires;
+    }
+
+    // When set step_frame skips instructions that fail with a non-fatal error instead of stopping
+    pub fn set_skip_non_fatal_errors(&mut self, skip: bool) {
+        self.skip_non_fatal_errors = skip;
+    }
+
+    // Called before every instruction, for tracing, trainers or breakpoints
+    pub fn set_pre_step_hook(&mut self, hook: PreStepHook<'a>) {
+        self.pre_step_hook = Some(hook);
+    }
+
+    pub fn clear_pre_step_hook(&mut self) {
+        self.pre_step_hook = None;
+    }
+
+    pub fn set_machine_code_trap(&mut self, trap: MachineCodeTrap<'a>) {
+        self.machine_code_trap = Some(trap);
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    pub fn resume(&mut self) {
+        if self.paused {
+            self.paused = false;
+            self.skip_hook_once = true;
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        return self.paused;
+    }
+
+    fn run_pre_step_hook(&mut self, opcode: OpCodes) -> HookAction {
+        let Some(hook) = self.pre_step_hook.as_mut() else {
+            return HookAction::Continue;
+        };
+        if self.skip_hook_once {
+            self.skip_hook_once = false;
+            return HookAction::Continue;
+        }
+        return hook(&CpuView {
+            pc: self.pc,
+            i: self.i,
+            v: &self.v,
+            next_opcode: opcode,
+        });
+    }
+
+    // Bounds checked reads and writes for debuggers, neither can touch memory past 0xFFF
+    pub fn memory_snapshot(&self, start: u16, len: usize) -> Result<Vec<u8>, Chip8Error> {
+        let range = self.memory_range(start, len)?;
+        return Ok(self.memory[range].to_vec());
+    }
+
+    pub fn memory_write(&mut self, addr: u16, data: &[u8]) -> Result<(), Chip8Error> {
+        let range = self.memory_range(addr, data.len())?;
+        self.memory[range].copy_from_slice(data);
+        return Ok(());
+    }
+
+    fn memory_range(&self, addr: u16, len: usize) -> Result<Range<usize>, Chip8Error> {
+        let start = addr as usize;
+        match start.checked_add(len) {
+            Some(end) if end <= self.memory.len() => Ok(start..end),
+            _ => Err(Chip8Error::MemoryOutOfBoundsError { addr, len }),
+        }
+    }
+
+    // Loads a ROM that has already been validated by Rom::parse
+    #[must_use = "a failed load leaves memory untouched; ignoring it runs the wrong program"]
+    pub fn load_rom(&mut self, rom: &Rom) -> Result<(), Chip8Error> {
+        self.load_program(rom.bytes())
+    }
+
+    #[must_use = "a failed load leaves memory untouched; ignoring it runs the wrong program"]
+    pub fn load_program(&mut self, data: &[u8]) -> Result<(), Chip8Error> {
+        // Too large is the more useful error for a ROM that is both
+        self.check_fits(PGRM_LOAD_START_ADDR, data.len())?;
+        if !data.len().is_multiple_of(2) {
+            return Err(Chip8Error::OddLengthRomError { len: data.len() });
+        }
+        self.load_into_memory(PGRM_LOAD_START_ADDR, data)?;
+        self.apply_patches();
+        return Ok(());
+    }
+
+    // Patches take effect immediately and again after every load_program and reset
+    pub fn add_patch(&mut self, addr: u16, bytes: Vec<u8>) -> Result<(), Chip8Error> {
+        self.memory_range(addr, bytes.len())?;
+        let patch = Patch { addr, bytes };
+        let range = patch.range();
+        if let Some(existing) = self.patches.iter().find(|existing| {
+            existing.range().start < range.end && range.start < existing.range().end
+        }) {
+            return Err(Chip8Error::PatchOverlapError {
+                addr,
+                existing: existing.addr,
+            });
+        }
+        self.memory[range].copy_from_slice(&patch.bytes);
+        self.patches.push(patch);
+        return Ok(());
+    }
+
+    // Memory keeps the patched bytes until the next load or reset
+    pub fn remove_patch(&mut self, addr: u16) -> Option<Patch> {
+        let index = self.patches.iter().position(|patch| patch.addr == addr)?;
+        return Some(self.patches.remove(index));
+    }
+
+    pub fn list_patches(&self) -> &[Patch] {
+        return &self.patches;
+    }
+
+    fn apply_patches(&mut self) {
+        for patch in self.patches.iter() {
+            self.memory[patch.range()].copy_from_slice(&patch.bytes);
+        }
+    }
+}
+
+fn check_unprotected(start_addr: u16, len: usize, area: Range<u16>) -> Result<(), Chip8Error> {
+    let start = usize::from(start_addr);
+    if len > 0 && start < usize::from(area.end) && usize::from(area.start) < start + len {
+        return Err(Chip8Error::ProtectedMemoryError {
+            addr: start_addr,
+            len,
+        });
+    }
+    return Ok(());
+}
+
+// CPU wired to the noop screen and input, handy for tests and headless tooling
+pub type TestCPU<'a> = CPU<'a, NoopScreen, NoopInput>;
+
+// CPU over trait objects, for frontends that pick their screen and input at runtime. The frontend
+// owns the Box<dyn Chip8Screen> and Box<dyn Chip8Input> and lends them, like any other CPU.
+pub type DynCPU<'a> = CPU<'a, dyn Chip8Screen + 'a, dyn Chip8Input + 'a>;
+
+impl Default for TestCPU<'static> {
+    fn default() -> Self {
+        CPU::new(&NoopScreen, &NoopInput)
     }
 }
 
 impl<TScreen, TInput> Chip8CPU for CPU<'_, TScreen, TInput>
 where
-    TScreen: Chip8Screen,
-    TInput: Chip8Input,
+    TScreen: Chip8Screen + ?Sized,
+    TInput: Chip8Input + ?Sized,
 {
-    fn step(&mut self) -> Result<(), Chip8Error> {
-        if self.last_decrement.elapsed().as_millis() >= 16 {
-            self.last_decrement = Instant::now();
-            if self.timer > 0 {
-                self.timer -= 1;
-            }
+    fn reset(&mut self) {
+        CPU::reset(self);
+    }
 
-            if self.sound > 0 {
-                self.sound -= 1;
-            }
+    fn step(&mut self) -> Result<StepResult, Chip8Error> {
+        if self.paused {
+            return Ok(StepResult::Paused);
         }
-
+        self.input.before_step(self.stats.steps);
         let op1 = self.memory[self.pc as usize];
         let op2 = self.memory[self.pc as usize + 1];
         let opcode = OpCodes::try_from((op1, op2))?;
+        match self.run_pre_step_hook(opcode) {
+            HookAction::Continue => {}
+            HookAction::SkipInstruction => {
+                self.pc += 2;
+                return Ok(StepResult::Skipped);
+            }
+            HookAction::Pause => {
+                self.paused = true;
+                return Ok(StepResult::Paused);
+            }
+        }
         // println!("PC: {:04X} INSTRUCTION: {:?}", self.pc, opcode);
 
         let res: Result<bool, _> = match opcode {
             // Execute machine language subroutine at address
-            OpCodes::_0NNN { .. } => {
-                Err(Chip8Error::UnimplementedOpcodeError(opcode))
-                // Ok(true)
-            }
+            OpCodes::_0NNN { nnn } => match (self.quirks.machine_code, &mut self.machine_code_trap)
+            {
+                (MachineCodePolicy::Ignore, _) => Ok(true),
+                (MachineCodePolicy::Trap, Some(trap)) => {
+                    trap(
+                        nnn,
+                        &CpuView {
+                            pc: self.pc,
+                            i: self.i,
+                            v: &self.v,
+                            next_opcode: opcode,
+                        },
+                    );
+                    Ok(true)
+                }
+                // Trapping without a handler is a setup bug, fail loudly rather than skip the call
+                (MachineCodePolicy::Error, _) | (MachineCodePolicy::Trap, None) => {
+                    Err(Chip8Error::UnimplementedOpcodeError(opcode))
+                }
+            },
             // Clear the screen
             OpCodes::_00E0 => {
                 self.screen.clear();
@@ -162,6 +605,9 @@ where
             }
             //Return from subroutine
             OpCodes::_00EE => {
+                if self.stack_ptr == 0xFFF {
+                    return Err(Chip8Error::StackUnderflowError);
+                }
                 let left = (self.memory[(self.stack_ptr + 1) as usize] as u16) << 8;
                 let right = self.memory[(self.stack_ptr + 2) as usize] as u16;
                 self.pc = left | right;
@@ -169,12 +615,11 @@ where
                 //     "Popping {:04X} onto stack as left: {:02X} and right: {:02X}",
                 //     self.pc, left, right
                 // );
-                if (self.stack_ptr as u16) == 0xFFF {
-                    return Err(Chip8Error::StackUnderflowError);
-                }
-                self.stack_ptr = self.stack_ptr + 2;
+                self.stack_ptr += 2;
                 Ok(false)
             }
+            // SUPER-CHIP exit, the PC stays on it so stepping again exits again
+            OpCodes::_00FD => Err(Chip8Error::ExitRequested),
             // Jump to address NNN
             OpCodes::_1NNN { nnn } => {
                 self.pc = nnn;
@@ -191,7 +636,7 @@ where
                 //     "Pushing {:04X} onto stack as left: {:02X} and right: {:02X}",
                 //     pc_to_push, left, right
                 // );
-                self.stack_ptr = self.stack_ptr - 2;
+                self.stack_ptr -= 2;
                 self.pc = nnn;
                 Ok(false)
             }
@@ -244,7 +689,9 @@ where
                 let xval = self.v.nth(x);
                 let yval = self.v.nth(y);
                 self.v.set(x, xval | yval);
-                self.v.set(0xF, 0);
+                if self.quirks.logic_resets_vf {
+                    self.v.set(0xF, 0);
+                }
                 Ok(true)
             }
             // Set VX to VX AND VY
@@ -252,7 +699,9 @@ where
                 let xval = self.v.nth(x);
                 let yval = self.v.nth(y);
                 self.v.set(x, xval & yval);
-                self.v.set(0xF, 0);
+                if self.quirks.logic_resets_vf {
+                    self.v.set(0xF, 0);
+                }
                 Ok(true)
             }
             // Set VX to VX XOR VY
@@ -260,7 +709,9 @@ where
                 let xval = self.v.nth(x);
                 let yval = self.v.nth(y);
                 self.v.set(x, xval ^ yval);
-                self.v.set(0xF, 0);
+                if self.quirks.logic_resets_vf {
+                    self.v.set(0xF, 0);
+                }
                 Ok(true)
             }
             // Add the value of register VY to register VX
@@ -300,7 +751,7 @@ where
             OpCodes::_8XY6 { x, y } => {
                 let yval = self.v.nth(y);
                 self.v.set(x, yval >> 1);
-                self.v.set(0xF, (yval & 0x01) as u8);
+                self.v.set(0xF, yval & 0x01);
                 Ok(true)
             }
             // Set register VX to the value of VY minus VX
@@ -321,7 +772,7 @@ where
             OpCodes::_8XYE { x, y } => {
                 let yval = self.v.nth(y);
                 self.v.set(x, yval << 1);
-                self.v.set(0xF, (yval >> 7) as u8);
+                self.v.set(0xF, yval >> 7);
                 Ok(true)
             }
             // Skip the following instruction if the value of register VX is not equal to the value of register VY
@@ -336,20 +787,38 @@ where
                 self.i = nnn;
                 Ok(true)
             }
-            // Jump to address NNN + V0
+            // Jump to address NNN + V0, or XNN + VX with the CHIP-48 quirk
             OpCodes::_BNNN { nnn } => {
-                self.pc = nnn + self.v[0] as u16;
-                Ok(true)
+                let x = if self.quirks.jump_uses_vx {
+                    (nnn >> 8) as u8
+                } else {
+                    0
+                };
+                self.pc = nnn + self.v.nth(x) as u16;
+                Ok(false)
             }
             // Set VX to a random number with a mask of NN
             OpCodes::_CXNN { x, nn } => {
-                let val = rand::thread_rng().gen_range(0x00..=0xFF);
+                let val = self.rng.gen_range(0x00..=0xFF);
                 self.v.set(x, val & nn);
 
                 Ok(true)
             }
             // Draw a sprite at position VX, VY with N bytes of sprite data starting at the address stored in I
             // Set VF to 01 if any set pixels are changed to unset, and 00 otherwise
+            // SUPER-CHIP, 16x16 from 32 bytes at I in high resolution mode
+            OpCodes::_DXYN { x, y, n: 0 } if self.hires => {
+                let addr = self.i;
+                let memslice = self
+                    .memory
+                    .get(usize::from(addr)..usize::from(addr) + 32)
+                    .ok_or(Chip8Error::MemoryOutOfBoundsError { addr, len: 32 })?;
+                let was_unset =
+                    self.screen
+                        .draw_wide_sprite(self.v[x as usize], self.v[y as usize], memslice);
+                self.v.set(0xF, was_unset as u8);
+                Ok(true)
+            }
             OpCodes::_DXYN { x, y, n } => {
                 let mem_start = self.i as usize;
                 let mem_end = mem_start + n as usize;
@@ -362,16 +831,14 @@ where
             }
             //Skip the following instruction if the key corresponding to the hex value currently stored in register VX is pressed
             OpCodes::_EX9E { x } => {
-                let key = self.input.get_key();
-                if key == Some(self.v[x as usize]) {
+                if self.input.is_key_held(self.v[x as usize]) {
                     self.pc += 2;
                 }
                 Ok(true)
             }
             // Skip the following instruction if the key corresponding to the hex value currently stored in register VX is not pressed
             OpCodes::_EXA1 { x } => {
-                let key = self.input.get_key();
-                if key != Some(self.v[x as usize]) {
+                if !self.input.is_key_held(self.v[x as usize]) {
                     self.pc += 2;
                 }
                 Ok(true)
@@ -383,9 +850,9 @@ where
             }
             // Wait for a keypress and store the result in register VX
             OpCodes::_FX0A { x } => {
-                let key = self.input.get_key();
-                if key.is_some() {
-                    self.v.set(x, key.unwrap());
+                // The original interpreter only stores the key once it's released
+                if let Some(key) = self.input.was_key_released() {
+                    self.v.set(x, key);
                     Ok(true)
                 } else {
                     Ok(false)
@@ -398,13 +865,17 @@ where
             }
             // Set the sound timer to the value of register VX
             OpCodes::_FX18 { x } => {
-                self.sound = self.v.nth(x);
+                self.set_sound_timer(self.v.nth(x));
                 Ok(true)
             }
 
             // Add the value stored in register VX to register I
             OpCodes::_FX1E { x } => {
-                self.i = self.i + self.v[x as usize] as u16;
+                self.i += self.v[x as usize] as u16;
+                if self.quirks.i_overflow_flag {
+                    self.v[0xF] = if self.i > 0x0FFF { 1 } else { 0 };
+                    self.i &= 0x0FFF;
+                }
                 Ok(true)
             }
 
@@ -412,7 +883,14 @@ where
             OpCodes::_FX29 { x } => {
                 let vs = self.v[x as usize] % 16;
                 self.i = FONT_START_ADDR + ((vs as u16) * 5);
-                self.pc += 2;
+                Ok(true)
+            }
+
+            // SUPER-CHIP, set I to the large 8x10 sprite for the decimal digit in VX. There are only
+            // digits 0-9, anything above wraps like FX29 does.
+            OpCodes::_FX30 { x } => {
+                let vs = self.v[x as usize] % 10;
+                self.i = LARGE_FONT_START_ADDR + ((vs as u16) * 10);
                 Ok(true)
             }
 
@@ -443,7 +921,19 @@ where
                 self.i = self.i + x as u16 + 1;
                 Ok(true)
             }
-            _ => Err(Chip8Error::UnknownOpcodeError(opcode)),
+
+            // SUPER-CHIP, store V0 to VX in the RPL flags, there are only 8 of them
+            OpCodes::_FX75 { x } => {
+                let count = usize::from(x.min(7)) + 1;
+                self.rpl_flags[..count].copy_from_slice(&self.v[..count]);
+                Ok(true)
+            }
+            // SUPER-CHIP, fill V0 to VX from the RPL flags
+            OpCodes::_FX85 { x } => {
+                let count = usize::from(x.min(7)) + 1;
+                self.v[..count].copy_from_slice(&self.rpl_flags[..count]);
+                Ok(true)
+            }
         };
         let Ok(increment_pc) = res else {
             return Err(res.unwrap_err());
@@ -451,14 +941,16 @@ where
         if increment_pc {
             self.pc += 2;
         }
-        return Ok(());
+        self.stats.steps += 1;
+        self.stats.last_opcode = Some(opcode);
+        return Ok(StepResult::Executed);
     }
 }
 
 impl<TScreen, TInput> Debug for CPU<'_, TScreen, TInput>
 where
-    TScreen: Chip8Screen,
-    TInput: Chip8Input,
+    TScreen: Chip8Screen + ?Sized,
+    TInput: Chip8Input + ?Sized,
 {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let mapped_registers = self
@@ -477,12 +969,31 @@ where
 
 #[cfg(test)]
 mod tests {
+    use std::cell::Cell;
+
     use super::*;
-    use crate::{test::NoopScreen, NoopInput};
+    use crate::{ProgramBuilder, Screen, StateDiff};
+
+    #[test]
+    fn dyn_cpu_uses_the_screen_picked_at_runtime() {
+        let screens: [(Box<dyn Chip8Screen>, u8); 2] =
+            [(Box::new(Screen::new()), 1), (Box::new(NoopScreen), 0)];
+        for (index, (screen, collision)) in screens.into_iter().enumerate() {
+            let input: Box<dyn Chip8Input> = Box::new(NoopInput);
+            let mut cpu = DynCPU::new(&*screen, &*input);
+            // Drawing the same glyph twice only collides on a screen that keeps pixels
+            cpu.load_program(&[0xA0, 0x50, 0xD0, 0x05, 0xD0, 0x05])
+                .unwrap();
+            for _ in 0..3 {
+                cpu.step().unwrap();
+            }
+            assert_eq!(cpu.v[0xF], collision, "screen {}", index);
+        }
+    }
 
     #[test]
     fn test_cpu() {
-        let cpu = CPU::new(&NoopScreen, &NoopInput);
+        let cpu = TestCPU::default();
         hexdump::hexdump(cpu.memory.as_ref());
         let first_font_char = cpu.memory[usize::from(FONT_START_ADDR)];
         assert_eq!(first_font_char, 0xF0);
@@ -490,16 +1001,696 @@ mod tests {
         assert_eq!(last_font_char, 0x80);
     }
 
+    #[test]
+    fn load_rom() {
+        let mut cpu = TestCPU::default();
+        let rom = Rom::parse(&[0x60, 0x2A]).unwrap();
+        assert_eq!(cpu.load_rom(&rom), Ok(()));
+        cpu.step().unwrap();
+        assert_eq!(cpu.v[0], 0x2A);
+        // Extended ROMs parse but don't fit the 4K CPU
+        let extended = Rom::parse_with(
+            &vec![0; 0x1000],
+            crate::rom::RomOptions {
+                extended_memory: true,
+            },
+        )
+        .unwrap();
+        assert!(cpu.load_rom(&extended).is_err());
+    }
+
+    #[test]
+    fn reset_keeps_font() {
+        let mut cpu = TestCPU::default();
+        cpu.load_program(&[0x12, 0x00]).unwrap();
+        cpu.reset();
+        assert_eq!(
+            cpu.memory_snapshot(FONT_START_ADDR, 5),
+            Ok(FONT_BUFFER[..5].to_vec())
+        );
+        assert_eq!(cpu.memory_snapshot(PGRM_LOAD_START_ADDR, 2), Ok(vec![0, 0]));
+    }
+
+    #[test]
+    fn reset_through_the_trait() {
+        let mut cpu = TestCPU::default();
+        // V3 = 7, I = 0x300
+        cpu.load_program(&[0x63, 0x07, 0xA3, 0x00]).unwrap();
+        let dyn_cpu: &mut dyn Chip8CPU = &mut cpu;
+        dyn_cpu.step().unwrap();
+        dyn_cpu.step().unwrap();
+        dyn_cpu.reset();
+        assert_eq!((cpu.pc, cpu.v[3], cpu.i), (0x200, 0, 0));
+        assert_eq!(cpu.memory_snapshot(PGRM_LOAD_START_ADDR, 2), Ok(vec![0, 0]));
+        assert_eq!(
+            cpu.memory_snapshot(FONT_START_ADDR, 5),
+            Ok(FONT_BUFFER[..5].to_vec())
+        );
+    }
+
+    #[test]
+    fn load_program_rejects_oversized_rom() {
+        let mut cpu = TestCPU::default();
+        let max_size = 4096 - PGRM_LOAD_START_ADDR as usize;
+        assert_eq!(cpu.load_program(&vec![0xAA; max_size]), Ok(()));
+        assert_eq!(cpu.memory[4095], 0xAA);
+        assert_eq!(
+            cpu.load_program(&vec![0xBB; max_size + 1]),
+            Err(Chip8Error::RomTooLargeError {
+                size: max_size + 1,
+                available: max_size,
+            })
+        );
+        // A rejected ROM must not be partially written
+        assert_eq!(cpu.memory[PGRM_LOAD_START_ADDR as usize], 0xAA);
+        assert_eq!(
+            cpu.load_into_memory(0x1000, &[0x01]),
+            Err(Chip8Error::RomTooLargeError {
+                size: 1,
+                available: 0,
+            })
+        );
+    }
+
+    #[test]
+    fn loads_cant_overwrite_the_font_or_stack() {
+        let mut cpu = TestCPU::default();
+        // Right up to the font and straight after it is fine
+        assert_eq!(cpu.load_into_memory(0x40, &[0xAA; 16]), Ok(()));
+        assert_eq!(cpu.load_into_memory(0x104, &[0xAA; 4]), Ok(()));
+        for (addr, len) in [(0x40, 17), (0x50, 1), (0x103, 1), (0x00, 0x200)] {
+            assert_eq!(
+                cpu.load_into_memory(addr, &vec![0xBB; len]),
+                Err(Chip8Error::ProtectedMemoryError { addr, len })
+            );
+        }
+        assert_eq!(
+            cpu.memory_snapshot(FONT_START_ADDR, 80),
+            Ok(FONT_BUFFER.to_vec())
+        );
+        assert_eq!(
+            cpu.memory_snapshot(LARGE_FONT_START_ADDR, 100),
+            Ok(LARGE_FONT_BUFFER.to_vec())
+        );
+
+        cpu.pc = 0xECB;
+        assert_eq!(
+            cpu.load_at_program_counter(&[0x60, 0x01, 0x60, 0x02]),
+            Ok(())
+        );
+        cpu.pc = 0xECD;
+        assert_eq!(
+            cpu.load_at_program_counter(&[0x60, 0x01, 0x60, 0x02]),
+            Err(Chip8Error::ProtectedMemoryError {
+                addr: 0xECD,
+                len: 4
+            })
+        );
+        // Still the first load's bytes
+        assert_eq!(cpu.memory_snapshot(0xECD, 2), Ok(vec![0x60, 0x02]));
+    }
+
+    #[test]
+    fn load_program_rejects_odd_length() {
+        let mut cpu = TestCPU::default();
+        assert_eq!(
+            cpu.load_program(&[0x60, 0x2A, 0x70]),
+            Err(Chip8Error::OddLengthRomError { len: 3 })
+        );
+        // Nothing is written
+        assert_eq!(cpu.memory[PGRM_LOAD_START_ADDR as usize], 0);
+    }
+
+    #[test]
+    fn memory_write_then_snapshot() {
+        let mut cpu = TestCPU::default();
+        assert_eq!(cpu.memory_write(0x300, &[0xDE, 0xAD, 0xBE, 0xEF]), Ok(()));
+        assert_eq!(
+            cpu.memory_snapshot(0x2FF, 6),
+            Ok(vec![0x00, 0xDE, 0xAD, 0xBE, 0xEF, 0x00])
+        );
+        // The font lives in memory too
+        assert_eq!(
+            cpu.memory_snapshot(FONT_START_ADDR, 5),
+            Ok(FONT_BUFFER[..5].to_vec())
+        );
+        assert_eq!(cpu.memory_write(0xFFE, &[0x12, 0x34]), Ok(()));
+        assert_eq!(cpu.memory_snapshot(0xFFE, 2), Ok(vec![0x12, 0x34]));
+    }
+
+    #[test]
+    fn memory_access_out_of_range() {
+        let mut cpu = TestCPU::default();
+        assert_eq!(
+            cpu.memory_write(0xFFF, &[0x01, 0x02]),
+            Err(Chip8Error::MemoryOutOfBoundsError {
+                addr: 0xFFF,
+                len: 2
+            })
+        );
+        // A rejected write must not be partially applied
+        assert_eq!(cpu.memory[0xFFF], 0x00);
+        assert_eq!(
+            cpu.memory_snapshot(0x1000, 1),
+            Err(Chip8Error::MemoryOutOfBoundsError {
+                addr: 0x1000,
+                len: 1
+            })
+        );
+        assert_eq!(
+            cpu.memory_snapshot(0, usize::MAX),
+            Err(Chip8Error::MemoryOutOfBoundsError {
+                addr: 0,
+                len: usize::MAX
+            })
+        );
+        assert_eq!(cpu.memory_snapshot(0x1000, 0), Ok(vec![]));
+    }
+
+    #[test]
+    fn step_frame_ticks_timers_once_per_frame() {
+        let mut cpu = TestCPU::default();
+        // Spin on a jump to self so every frame has something to execute
+        cpu.load_program(&[0x12, 0x00]).unwrap();
+        cpu.timer = 60;
+        cpu.sound = 30;
+        for _ in 0..59 {
+            cpu.step_frame(10).unwrap();
+        }
+        assert_eq!(cpu.delay_timer(), 1);
+        assert_eq!(cpu.sound_timer(), 0);
+        cpu.step_frame(10).unwrap();
+        assert_eq!(cpu.delay_timer(), 0);
+        cpu.step_frame(10).unwrap();
+        assert_eq!(cpu.delay_timer(), 0);
+    }
+
+    #[derive(Default)]
+    struct RecordingSound(std::cell::RefCell<Vec<&'static str>>);
+
+    impl Chip8Sound for RecordingSound {
+        fn play(&self) {
+            self.0.borrow_mut().push("play");
+        }
+
+        fn stop(&self) {
+            self.0.borrow_mut().push("stop");
+        }
+    }
+
+    #[test]
+    fn sound_plays_while_the_timer_runs() {
+        let sound = RecordingSound::default();
+        let mut cpu = TestCPU::default();
+        cpu.set_sound_output(&sound);
+        let program = ProgramBuilder::from(
+            &[
+                OpCodes::_6XNN { x: 0x0, nn: 2 },
+                OpCodes::_FX18 { x: 0x0 },
+                // Setting it again while it runs doesn't restart the tone
+                OpCodes::_FX18 { x: 0x0 },
+                OpCodes::_6XNN { x: 0x1, nn: 0 },
+            ][..],
+        )
+        .finish();
+        cpu.load_program(&program).unwrap();
+        for _ in 0..3 {
+            cpu.step().unwrap();
+        }
+        assert_eq!(*sound.0.borrow(), ["play"]);
+
+        cpu.tick_timers();
+        assert_eq!(*sound.0.borrow(), ["play"]);
+        cpu.tick_timers();
+        cpu.tick_timers();
+        assert_eq!(*sound.0.borrow(), ["play", "stop"]);
+
+        // FX18 with 0 silences it straight away, as does a reset
+        cpu.reset();
+        cpu.load_program(
+            &ProgramBuilder::from(
+                &[
+                    OpCodes::_6XNN { x: 0x0, nn: 9 },
+                    OpCodes::_FX18 { x: 0x0 },
+                    OpCodes::_FX18 { x: 0x1 },
+                    OpCodes::_FX18 { x: 0x0 },
+                ][..],
+            )
+            .finish(),
+        )
+        .unwrap();
+        for _ in 0..4 {
+            cpu.step().unwrap();
+        }
+        cpu.reset();
+        assert_eq!(
+            *sound.0.borrow(),
+            ["play", "stop", "play", "stop", "play", "stop"]
+        );
+    }
+
+    #[test]
+    fn stats_count_steps_and_frames() {
+        let mut cpu = TestCPU::default();
+        cpu.load_program(&[0x60, 0x01, 0x70, 0x01, 0x12, 0x02])
+            .unwrap();
+        assert_eq!(cpu.stats(), CpuStats::default());
+        for _ in 0..3 {
+            cpu.step_frame(7).unwrap();
+        }
+        assert_eq!(
+            cpu.stats(),
+            CpuStats {
+                steps: 21,
+                frames: 3,
+                last_opcode: Some(OpCodes::_1NNN { nnn: 0x202 }),
+            }
+        );
+        // Failed steps aren't counted
+        cpu.load_program(&[0x5A, 0xB1]).unwrap();
+        cpu.pc = 0x200;
+        assert!(cpu.step().is_err());
+        assert_eq!(cpu.stats().steps, 21);
+
+        cpu.reset();
+        assert_eq!(cpu.stats(), CpuStats::default());
+    }
+
+    #[test]
+    fn step_count_resets() {
+        let mut cpu = TestCPU::default();
+        cpu.load_program(&[0x12, 0x00]).unwrap();
+        for _ in 0..123 {
+            cpu.step().unwrap();
+        }
+        assert_eq!(cpu.step_count(), 123);
+        cpu.reset();
+        assert_eq!(cpu.step_count(), 0);
+    }
+
+    #[test]
+    fn infinite_loop_stays_on_jump() {
+        let mut cpu = TestCPU::default();
+        let mut builder = ProgramBuilder::new();
+        let here = builder.label("here");
+        builder
+            .push(OpCodes::_6XNN { x: 0, nn: 0x01 })
+            .bind(here)
+            .jump_to(here);
+        cpu.load_program(&builder.finish()).unwrap();
+        for _ in 0..1000 {
+            cpu.step().unwrap();
+        }
+        assert_eq!(cpu.pc(), 0x202);
+        assert_eq!(cpu.step_count(), 1000);
+    }
+
+    #[test]
+    fn step_does_not_tick_timers() {
+        let mut cpu = TestCPU::default();
+        cpu.load_program(&[0x12, 0x00]).unwrap();
+        cpu.timer = 5;
+        for _ in 0..1000 {
+            cpu.step().unwrap();
+        }
+        assert_eq!(cpu.delay_timer(), 5);
+    }
+
+    #[test]
+    fn patches_survive_reset() {
+        let mut cpu = TestCPU::default();
+        let program = ProgramBuilder::from(
+            &[
+                OpCodes::_6XNN { x: 0, nn: 0x03 },
+                OpCodes::_7XNN { x: 0, nn: 0xFF },
+            ][..],
+        )
+        .finish();
+        // Turn the decrement into a no-op add, the "infinite lives" cheat
+        cpu.add_patch(0x202, vec![0x70, 0x00]).unwrap();
+        cpu.load_program(&program).unwrap();
+        cpu.step().unwrap();
+        cpu.step().unwrap();
+        assert_eq!(cpu.v[0], 0x03);
+
+        cpu.reset();
+        assert_eq!(cpu.memory_snapshot(0x202, 2), Ok(vec![0x70, 0x00]));
+        cpu.load_program(&program).unwrap();
+        cpu.step().unwrap();
+        cpu.step().unwrap();
+        assert_eq!(cpu.v[0], 0x03);
+
+        assert_eq!(
+            cpu.remove_patch(0x202),
+            Some(Patch {
+                addr: 0x202,
+                bytes: vec![0x70, 0x00]
+            })
+        );
+        assert!(cpu.list_patches().is_empty());
+        cpu.reset();
+        cpu.load_program(&program).unwrap();
+        cpu.step().unwrap();
+        cpu.step().unwrap();
+        assert_eq!(cpu.v[0], 0x02);
+    }
+
+    #[test]
+    fn overlapping_patches_are_rejected() {
+        let mut cpu = TestCPU::default();
+        cpu.add_patch(0x3A4, vec![0x00, 0x00]).unwrap();
+        cpu.add_patch(0x3A6, vec![0x01]).unwrap();
+        assert_eq!(
+            cpu.add_patch(0x3A5, vec![0xFF]),
+            Err(Chip8Error::PatchOverlapError {
+                addr: 0x3A5,
+                existing: 0x3A4
+            })
+        );
+        assert_eq!(
+            cpu.add_patch(0x3A0, vec![0xFF; 8]),
+            Err(Chip8Error::PatchOverlapError {
+                addr: 0x3A0,
+                existing: 0x3A4
+            })
+        );
+        assert_eq!(
+            cpu.add_patch(0xFFF, vec![0x00, 0x00]),
+            Err(Chip8Error::MemoryOutOfBoundsError {
+                addr: 0xFFF,
+                len: 2
+            })
+        );
+        assert_eq!(cpu.list_patches().len(), 2);
+        assert_eq!(cpu.remove_patch(0x3A5), None);
+    }
+
+    #[test]
+    fn state_diff_after_add() {
+        let mut cpu = TestCPU::default();
+        cpu.load_program(&[0x70, 0x05]).unwrap();
+        let before = cpu.state();
+        cpu.step().unwrap();
+        let diff = before.diff(&cpu.state());
+        assert_eq!(
+            diff,
+            StateDiff {
+                registers: vec![(0, 0x00, 0x05)],
+                pc: Some((0x200, 0x202)),
+                ..Default::default()
+            }
+        );
+        assert_eq!(diff.to_string(), "V0 00->05 PC 0200->0202");
+        assert!(cpu.state().diff(&cpu.state()).is_empty());
+    }
+
+    #[test]
+    fn state_diff_memory_and_stack() {
+        let mut cpu = TestCPU::default();
+        let mut builder = ProgramBuilder::new();
+        let store = builder.label("store");
+        builder
+            .call(store)
+            .bind(store)
+            .push(OpCodes::_ANNN { nnn: 0x300 })
+            .push(OpCodes::_FX55 { x: 2 });
+        cpu.load_program(&builder.finish()).unwrap();
+        cpu.v[..3].copy_from_slice(&[1, 2, 3]);
+
+        let before = cpu.state_with_memory();
+        cpu.step().unwrap();
+        let after_call = cpu.state_with_memory();
+        assert_eq!(after_call.stack, vec![0x202]);
+        // Only state() skips memory, so comparing with it ignores memory changes
+        assert!(before.diff(&cpu.state()).memory.is_empty());
+        assert_eq!(before.diff(&after_call).memory, vec![0xFFE..0x1000]);
+
+        cpu.step().unwrap();
+        cpu.step().unwrap();
+        let diff = after_call.diff(&cpu.state_with_memory());
+        assert_eq!(diff.memory, vec![0x300..0x303]);
+        // FX55 leaves I past the stored registers
+        assert_eq!(diff.i, Some((0, 0x303)));
+        assert_eq!(diff.stack, None);
+    }
+
+    #[test]
+    fn dxy0_draws_16x16_in_hires() {
+        // A 16x16 box outline, drawn twice so the second draw collides and erases it
+        let mut sprite = vec![0xFF, 0xFF];
+        for _ in 0..14 {
+            sprite.extend([0x80, 0x01]);
+        }
+        sprite.extend([0xFF, 0xFF]);
+        let mut program = ProgramBuilder::new();
+        program
+            .push(OpCodes::_ANNN { nnn: 0x206 })
+            .push(OpCodes::_DXYN { x: 0, y: 1, n: 0 })
+            .push(OpCodes::_DXYN { x: 0, y: 1, n: 0 })
+            .data(&sprite);
+
+        let screen = crate::Screen::new();
+        let mut cpu = CPU::new(&screen, &NoopInput);
+        cpu.set_hires(true);
+        cpu.load_program(&program.finish()).unwrap();
+        cpu.step_frame(2).unwrap();
+        for (x, y) in [(0, 0), (15, 0), (0, 15), (15, 15), (15, 7)] {
+            assert!(screen.get_pixel(x, y), "({}, {})", x, y);
+        }
+        for (x, y) in [(16, 0), (0, 16), (7, 7), (14, 1)] {
+            assert!(!screen.get_pixel(x, y), "({}, {})", x, y);
+        }
+        assert_eq!(cpu.v[0xF], 0);
+        cpu.step().unwrap();
+        assert_eq!(cpu.v[0xF], 1);
+        assert!(screen.rows_iter().all(|row| row == 0));
+
+        // Low resolution keeps the CHIP-8 meaning, a sprite 0 rows tall
+        let screen = crate::Screen::new();
+        let mut cpu = CPU::new(&screen, &NoopInput);
+        cpu.load_program(&program.finish()).unwrap();
+        cpu.step_frame(3).unwrap();
+        assert!(screen.rows_iter().all(|row| row == 0));
+        assert!(!cpu.is_hires());
+
+        // 32 bytes from I have to fit in memory
+        let mut cpu = TestCPU::default();
+        cpu.set_hires(true);
+        cpu.load_program(&[0xAF, 0xF0, 0xD0, 0x10]).unwrap();
+        cpu.step().unwrap();
+        assert_eq!(
+            cpu.step(),
+            Err(Chip8Error::MemoryOutOfBoundsError {
+                addr: 0xFF0,
+                len: 32
+            })
+        );
+    }
+
+    #[test]
+    fn hook_skips_draws() {
+        let screen = crate::Screen::new();
+        let mut cpu = CPU::new(&screen, &NoopInput);
+        let program = ProgramBuilder::from(
+            &[
+                OpCodes::_ANNN { nnn: 0x050 },
+                OpCodes::_DXYN { x: 0, y: 0, n: 5 },
+                OpCodes::_6XNN { x: 0, nn: 0x08 },
+                OpCodes::_DXYN { x: 0, y: 0, n: 5 },
+                OpCodes::_7XNN { x: 0, nn: 0x08 },
+            ][..],
+        );
+        cpu.load_program(&program.finish()).unwrap();
+        cpu.set_pre_step_hook(Box::new(|view| match view.next_opcode {
+            OpCodes::_DXYN { .. } => HookAction::SkipInstruction,
+            _ => HookAction::Continue,
+        }));
+        let results: Vec<_> = (0..5).map(|_| cpu.step().unwrap()).collect();
+        assert_eq!(
+            results,
+            vec![
+                StepResult::Executed,
+                StepResult::Skipped,
+                StepResult::Executed,
+                StepResult::Skipped,
+                StepResult::Executed,
+            ]
+        );
+        assert_eq!(cpu.v[0], 0x10);
+        assert_eq!(cpu.pc(), 0x20A);
+        assert_eq!(cpu.step_count(), 3);
+        assert!(!screen.draw_as_string().contains('█'));
+    }
+
+    #[test]
+    fn hook_pauses_until_resume() {
+        let mut cpu = TestCPU::default();
+        let program = ProgramBuilder::from(
+            &[
+                OpCodes::_6XNN { x: 0, nn: 0x01 },
+                OpCodes::_6XNN { x: 1, nn: 0x02 },
+                OpCodes::_6XNN { x: 2, nn: 0x03 },
+            ][..],
+        );
+        cpu.load_program(&program.finish()).unwrap();
+        cpu.set_pre_step_hook(Box::new(|view| {
+            if view.pc == 0x202 {
+                return HookAction::Pause;
+            }
+            return HookAction::Continue;
+        }));
+        assert_eq!(cpu.step(), Ok(StepResult::Executed));
+        assert_eq!(cpu.step(), Ok(StepResult::Paused));
+        assert!(cpu.is_paused());
+        // Paused frames don't run anything or tick the timers
+        cpu.timer = 5;
+        cpu.step_frame(10).unwrap();
+        assert_eq!(cpu.step(), Ok(StepResult::Paused));
+        assert_eq!((cpu.pc(), cpu.v[1], cpu.delay_timer()), (0x202, 0, 5));
+
+        // The instruction the hook paused on runs after resuming
+        cpu.resume();
+        assert!(!cpu.is_paused());
+        assert_eq!(cpu.step(), Ok(StepResult::Executed));
+        assert_eq!(cpu.step(), Ok(StepResult::Executed));
+        assert_eq!(cpu.v[..3], [0x01, 0x02, 0x03]);
+    }
+
+    #[test]
+    fn test_cpu_default_matches_new() {
+        let default_cpu = TestCPU::default();
+        let new_cpu = CPU::new(&NoopScreen, &NoopInput);
+        assert_eq!(default_cpu.memory, new_cpu.memory);
+        assert_eq!(default_cpu.v, new_cpu.v);
+        assert_eq!(default_cpu.i, new_cpu.i);
+        assert_eq!(default_cpu.pc, new_cpu.pc);
+        assert_eq!(default_cpu.stack_ptr, new_cpu.stack_ptr);
+        assert_eq!(default_cpu.timer, new_cpu.timer);
+        assert_eq!(default_cpu.sound, new_cpu.sound);
+    }
+
     mod instructions {
         use super::*;
         use crate::{
-            run,
-            test::{op_run_program, NoopScreen},
+            run, run_from_pc,
+            test::{op_run_from_program_counter, op_run_program},
         };
 
+        #[test]
+        fn _0nnn() {
+            let mut cpu = TestCPU::default();
+            cpu.load_program(&[0x01, 0x23]).unwrap();
+            assert_eq!(cpu.step(), Ok(StepResult::Executed));
+            assert_eq!(cpu.pc(), 0x202);
+        }
+
+        #[test]
+        fn _0nnn_error() {
+            let mut cpu = TestCPU::default();
+            cpu.set_quirks(Chip8Quirks {
+                machine_code: MachineCodePolicy::Error,
+                ..Default::default()
+            });
+            cpu.load_program(&[0x01, 0x23]).unwrap();
+            assert_eq!(
+                cpu.step(),
+                Err(Chip8Error::UnimplementedOpcodeError(OpCodes::_0NNN {
+                    nnn: 0x123
+                }))
+            );
+        }
+
+        #[test]
+        fn _0nnn_error_skipped_by_step_frame() {
+            let mut cpu = TestCPU::default();
+            cpu.set_quirks(Chip8Quirks {
+                machine_code: MachineCodePolicy::Error,
+                ..Default::default()
+            });
+            cpu.load_program(&[0x01, 0x23, 0x60, 0x07, 0x5A, 0xB1])
+                .unwrap();
+            cpu.set_skip_non_fatal_errors(true);
+            // The invalid 5AB1 is fatal even when skipping
+            assert_eq!(
+                cpu.step_frame(3),
+                Err(Chip8Error::InvalidOpcodeError(0x5AB1))
+            );
+            assert_eq!((cpu.pc(), cpu.v[0]), (0x204, 0x07));
+
+            cpu.reset();
+            cpu.load_program(&[0x01, 0x23, 0x60, 0x07]).unwrap();
+            cpu.set_skip_non_fatal_errors(false);
+            assert!(cpu.step_frame(2).is_err());
+            assert_eq!(cpu.pc(), 0x200);
+        }
+
+        #[test]
+        fn _0nnn_trap() {
+            let calls = std::cell::RefCell::new(vec![]);
+            let mut cpu = TestCPU::default();
+            cpu.set_quirks(Chip8Quirks {
+                machine_code: MachineCodePolicy::Trap,
+                ..Default::default()
+            });
+            cpu.load_program(&[0x01, 0x23, 0x60, 0x07, 0x0A, 0xBC])
+                .unwrap();
+            assert!(cpu.step().is_err(), "trapping needs a handler");
+
+            cpu.set_machine_code_trap(Box::new(|nnn, view| {
+                calls.borrow_mut().push((nnn, view.v[0]));
+            }));
+            for _ in 0..3 {
+                cpu.step().unwrap();
+            }
+            assert_eq!(cpu.pc(), 0x206);
+            drop(cpu);
+            assert_eq!(calls.into_inner(), vec![(0x123, 0x00), (0xABC, 0x07)]);
+        }
+
+        #[test]
+        fn _00ee() {
+            let mut cpu = TestCPU::default();
+            run! {
+                cpu,
+                _2NNN { nnn: 0x204 },
+                _6XNN { x: 0, nn: 0x01 },
+                _00EE {},
+            };
+            // CALL 0x204, RET back to 0x202, then LOAD
+            assert_eq!(cpu.v[0], 0x01);
+            assert_eq!(cpu.pc, 0x204);
+            assert_eq!(cpu.stack_ptr, 0xFFF);
+
+            cpu.reset();
+            cpu.load_program(&[0x00, 0xEE]).unwrap();
+            assert_eq!(cpu.step(), Err(Chip8Error::StackUnderflowError));
+        }
+
+        #[test]
+        fn _00fd() {
+            let mut cpu = TestCPU::default();
+            cpu.load_program(&[0x00, 0xFD, 0x60, 0x07]).unwrap();
+            assert_eq!(cpu.step(), Err(Chip8Error::ExitRequested));
+            assert_eq!(cpu.pc(), 0x200);
+            assert_eq!(cpu.step_count(), 0);
+            // Exiting isn't something to skip past
+            cpu.set_skip_non_fatal_errors(true);
+            assert_eq!(cpu.step_frame(2), Err(Chip8Error::ExitRequested));
+            assert_eq!((cpu.pc(), cpu.v[0]), (0x200, 0));
+            assert_eq!(Chip8Error::ExitRequested.category(), crate::ErrorKind::Exit);
+        }
+
+        #[test]
+        fn invalid_opcode() {
+            let mut cpu = TestCPU::default();
+            cpu.load_program(&[0x5A, 0xB1]).unwrap();
+            assert_eq!(cpu.step(), Err(Chip8Error::InvalidOpcodeError(0x5AB1)));
+            assert_eq!(cpu.pc, 0x200);
+        }
+
         #[test]
         fn _3xnn() {
-            let mut cpu = CPU::new(&NoopScreen, &NoopInput);
+            let mut cpu = TestCPU::default();
             op_run_program(
                 &mut cpu,
                 [
@@ -518,7 +1709,7 @@ mod tests {
 
         #[test]
         fn _6xnn() {
-            let mut cpu = CPU::new(&NoopScreen, &NoopInput);
+            let mut cpu = TestCPU::default();
             run! {
                 cpu,
                 _6XNN { x: 0, nn: 0x12 },
@@ -528,7 +1719,7 @@ mod tests {
 
         #[test]
         fn _7xnn() {
-            let mut cpu = CPU::new(&NoopScreen, &NoopInput);
+            let mut cpu = TestCPU::default();
             run! {
                 cpu,
                 _6XNN { x: 0, nn: 0x12 },
@@ -540,7 +1731,7 @@ mod tests {
 
         #[test]
         fn _8xy0() {
-            let mut cpu = CPU::new(&NoopScreen, &NoopInput);
+            let mut cpu = TestCPU::default();
             run! {
                 cpu,
                 _6XNN { x: 0, nn: 0x12 },
@@ -553,46 +1744,70 @@ mod tests {
 
         #[test]
         fn _8xy1() {
-            let mut cpu = CPU::new(&NoopScreen, &NoopInput);
-            run! {
-                cpu,
-                _6XNN { x: 0, nn: 0x12 },
-                _6XNN { x: 1, nn: 0x13 },
-                _8XY1 { x: 0, y: 1 },
+            for logic_resets_vf in [false, true] {
+                let mut cpu = TestCPU::default();
+                cpu.set_quirks(Chip8Quirks {
+                    logic_resets_vf,
+                    ..Default::default()
+                });
+                run! {
+                    cpu,
+                    _6XNN { x: 0, nn: 0x12 },
+                    _6XNN { x: 1, nn: 0x13 },
+                    _6XNN { x: 0xF, nn: 0x01 },
+                    _8XY1 { x: 0, y: 1 },
+                }
+                assert_eq!(cpu.v[0], 0x12 | 0x13);
+                assert_eq!(cpu.v[1], 0x13);
+                assert_eq!(cpu.v[0xF], if logic_resets_vf { 0 } else { 1 });
             }
-            assert_eq!(cpu.v[0], 0x12 | 0x13);
-            assert_eq!(cpu.v[1], 0x13);
         }
 
         #[test]
         fn _8xy2() {
-            let mut cpu = CPU::new(&NoopScreen, &NoopInput);
-            run! {
-                cpu,
-                _6XNN { x: 0, nn: 0x12 },
-                _6XNN { x: 1, nn: 0x13 },
-                _8XY2 { x: 0, y: 1 },
+            for logic_resets_vf in [false, true] {
+                let mut cpu = TestCPU::default();
+                cpu.set_quirks(Chip8Quirks {
+                    logic_resets_vf,
+                    ..Default::default()
+                });
+                run! {
+                    cpu,
+                    _6XNN { x: 0, nn: 0x12 },
+                    _6XNN { x: 1, nn: 0x13 },
+                    _6XNN { x: 0xF, nn: 0x01 },
+                    _8XY2 { x: 0, y: 1 },
+                }
+                assert_eq!(cpu.v[0], 0x12 & 0x13);
+                assert_eq!(cpu.v[1], 0x13);
+                assert_eq!(cpu.v[0xF], if logic_resets_vf { 0 } else { 1 });
             }
-            assert_eq!(cpu.v[0], 0x12 & 0x13);
-            assert_eq!(cpu.v[1], 0x13);
         }
 
         #[test]
         fn _8xy3() {
-            let mut cpu = CPU::new(&NoopScreen, &NoopInput);
-            run! {
-                cpu,
-                _6XNN { x: 0, nn: 0x12 },
-                _6XNN { x: 1, nn: 0x13 },
-                _8XY3 { x: 0, y: 1 },
+            for logic_resets_vf in [false, true] {
+                let mut cpu = TestCPU::default();
+                cpu.set_quirks(Chip8Quirks {
+                    logic_resets_vf,
+                    ..Default::default()
+                });
+                run! {
+                    cpu,
+                    _6XNN { x: 0, nn: 0x12 },
+                    _6XNN { x: 1, nn: 0x13 },
+                    _6XNN { x: 0xF, nn: 0x01 },
+                    _8XY3 { x: 0, y: 1 },
+                }
+                assert_eq!(cpu.v[0], 0x12 ^ 0x13);
+                assert_eq!(cpu.v[1], 0x13);
+                assert_eq!(cpu.v[0xF], if logic_resets_vf { 0 } else { 1 });
             }
-            assert_eq!(cpu.v[0], 0x12 ^ 0x13);
-            assert_eq!(cpu.v[1], 0x13);
         }
 
         #[test]
         fn _8xy4() {
-            let mut cpu = CPU::new(&NoopScreen, &NoopInput);
+            let mut cpu = TestCPU::default();
             run! {
                 cpu,
                 _6XNN { x: 0, nn: 0x12 },
@@ -616,7 +1831,7 @@ mod tests {
 
         #[test]
         fn _8xy5() {
-            let mut cpu = CPU::new(&NoopScreen, &NoopInput);
+            let mut cpu = TestCPU::default();
             run! {
                 cpu,
                 _6XNN { x: 0, nn: 0x12 },
@@ -625,7 +1840,7 @@ mod tests {
             }
             assert_eq!(cpu.v[0], 0x12);
             assert_eq!(cpu.v[1], u8::wrapping_sub(0x13, 0x12));
-            assert_eq!(cpu.v[0xF], 0);
+            assert_eq!(cpu.v[0xF], 1);
             cpu.reset();
             run! {
                 cpu,
@@ -635,12 +1850,12 @@ mod tests {
             }
             assert_eq!(cpu.v[0], u8::wrapping_sub(0x12, 0x13));
             assert_eq!(cpu.v[1], 0x13);
-            assert_eq!(cpu.v[0xF], 1);
+            assert_eq!(cpu.v[0xF], 0);
         }
 
         #[test]
         fn _8xy6() {
-            let mut cpu = CPU::new(&NoopScreen, &NoopInput);
+            let mut cpu = TestCPU::default();
             run! {
                 cpu,
                 _6XNN { x: 0, nn: 0x12 },
@@ -666,7 +1881,7 @@ mod tests {
 
         #[test]
         fn _8xy7() {
-            let mut cpu = CPU::new(&NoopScreen, &NoopInput);
+            let mut cpu = TestCPU::default();
             run! {
                 cpu,
                 _6XNN { x: 0, nn: 0x12 },
@@ -675,7 +1890,7 @@ mod tests {
             }
             assert_eq!(cpu.v[0], u8::wrapping_sub(0x13, 0x12));
             assert_eq!(cpu.v[1], 0x13);
-            assert_eq!(cpu.v[0xF], 0);
+            assert_eq!(cpu.v[0xF], 1);
 
             cpu.reset();
 
@@ -687,12 +1902,12 @@ mod tests {
             }
             assert_eq!(cpu.v[0], 0x12);
             assert_eq!(cpu.v[1], u8::wrapping_sub(0x12, 0x13));
-            assert_eq!(cpu.v[0xF], 1);
+            assert_eq!(cpu.v[0xF], 0);
         }
 
         #[test]
         fn _8xye() {
-            let mut cpu = CPU::new(&NoopScreen, &NoopInput);
+            let mut cpu = TestCPU::default();
             run! {
                 cpu,
                 _6XNN { x: 0, nn: 0x12 },
@@ -715,5 +1930,285 @@ mod tests {
             assert_eq!(cpu.v[1], 0x12);
             assert_eq!(cpu.v[0xF], 0);
         }
+
+        #[test]
+        fn _cxnn() {
+            let mut cpu = CPU::new_seeded(&NoopScreen, &NoopInput, 42);
+            run! {
+                cpu,
+                _CXNN { x: 0, nn: 0xFF },
+                _CXNN { x: 1, nn: 0x0F },
+            }
+            assert_eq!(cpu.v[0], 0x42);
+            // NN masks the random byte
+            assert_eq!(cpu.v[1], 0x05);
+        }
+
+        #[test]
+        fn _cxnn_same_seed_same_sequence() {
+            let mut a = CPU::new_seeded(&NoopScreen, &NoopInput, 7);
+            let mut b = CPU::new_seeded(&NoopScreen, &NoopInput, 7);
+            let program = [OpCodes::_CXNN { x: 0, nn: 0xFF }];
+            let mut sequence_a = vec![];
+            let mut sequence_b = vec![];
+            for _ in 0..4 {
+                op_run_from_program_counter(&mut a, &program);
+                op_run_from_program_counter(&mut b, &program);
+                sequence_a.push(a.v[0]);
+                sequence_b.push(b.v[0]);
+            }
+            assert_eq!(sequence_a, sequence_b);
+            // A different seed gives a different sequence
+            let mut c = CPU::new_seeded(&NoopScreen, &NoopInput, 8);
+            let mut sequence_c = vec![];
+            for _ in 0..4 {
+                op_run_from_program_counter(&mut c, &program);
+                sequence_c.push(c.v[0]);
+            }
+            assert_ne!(sequence_a, sequence_c);
+        }
+
+        #[test]
+        fn _fx1e() {
+            let mut cpu = TestCPU::default();
+            run! {
+                cpu,
+                _ANNN { nnn: 0xFF0 },
+                _6XNN { x: 0, nn: 0x10 },
+                _FX1E { x: 0 },
+            }
+            // VF is left alone by default
+            assert_eq!(cpu.i, 0x1000);
+            assert_eq!(cpu.v[0xF], 0);
+        }
+
+        #[test]
+        fn _fx29() {
+            let mut cpu = TestCPU::default();
+            run! {
+                cpu,
+                _6XNN { x: 2, nn: 0x1A },
+                _FX29 { x: 2 },
+                _6XNN { x: 3, nn: 0x01 },
+            }
+            // Only the low nibble picks the digit, and the next instruction still runs
+            assert_eq!(cpu.i, FONT_START_ADDR + 0xA * 5);
+            assert_eq!(cpu.pc, 0x206);
+            assert_eq!(cpu.v[3], 0x01);
+        }
+
+        #[test]
+        fn _fx30() {
+            // Top row of each large digit
+            let tops = [0x3C, 0x18, 0x3E, 0x3C, 0x06, 0xFF, 0x3E, 0xFF, 0x3C, 0x3C];
+            for (digit, top) in (0..10u8).zip(tops) {
+                let mut cpu = TestCPU::default();
+                run! {
+                    cpu,
+                    _6XNN { x: 4, nn: digit },
+                    _FX30 { x: 4 },
+                }
+                assert_eq!(cpu.i, LARGE_FONT_START_ADDR + u16::from(digit) * 10);
+                let sprite = cpu.memory_snapshot(cpu.i, 10).unwrap();
+                assert_eq!(sprite[0], top, "digit {}", digit);
+                assert!(sprite.iter().all(|row| *row != 0), "digit {}", digit);
+            }
+            // Right after the small font
+            assert_eq!(LARGE_FONT_START_ADDR, 0xA0);
+        }
+
+        #[test]
+        fn _fx75_fx85() {
+            let mut cpu = TestCPU::default();
+            run! {
+                cpu,
+                _6XNN { x: 0, nn: 0x11 },
+                _6XNN { x: 1, nn: 0x22 },
+                _6XNN { x: 2, nn: 0x33 },
+                _6XNN { x: 3, nn: 0x44 },
+                _FX75 { x: 3 },
+            }
+            // The flags outlive a reset, the registers don't
+            cpu.reset();
+            assert_eq!(cpu.v[..4], [0; 4]);
+            run! {
+                cpu,
+                _6XNN { x: 0, nn: 0xAA },
+                _6XNN { x: 1, nn: 0xBB },
+                _6XNN { x: 2, nn: 0xCC },
+                _6XNN { x: 3, nn: 0xDD },
+                _6XNN { x: 4, nn: 0xEE },
+                _FX85 { x: 3 },
+            }
+            assert_eq!(cpu.v[..5], [0x11, 0x22, 0x33, 0x44, 0xEE]);
+            assert_eq!(cpu.pc, 0x20C);
+
+            // Only 8 flags, VF is never stored or loaded
+            let mut cpu = TestCPU::default();
+            run! {
+                cpu,
+                _6XNN { x: 7, nn: 0x77 },
+                _6XNN { x: 0xF, nn: 0xFF },
+                _FX75 { x: 0xF },
+                _6XNN { x: 7, nn: 0 },
+                _FX85 { x: 0xF },
+            }
+            assert_eq!(cpu.rpl_flags[7], 0x77);
+            assert_eq!((cpu.v[7], cpu.v[0xF]), (0x77, 0xFF));
+        }
+
+        #[test]
+        fn _fx1e_i_overflow_flag() {
+            let mut cpu = TestCPU::default();
+            cpu.set_quirks(Chip8Quirks {
+                i_overflow_flag: true,
+                ..Default::default()
+            });
+            run! {
+                cpu,
+                _ANNN { nnn: 0xFF0 },
+                _6XNN { x: 0, nn: 0x0F },
+                _FX1E { x: 0 },
+            }
+            assert_eq!(cpu.i, 0xFFF);
+            assert_eq!(cpu.v[0xF], 0);
+
+            run_from_pc! {
+                cpu,
+                _6XNN { x: 0, nn: 0x01 },
+                _FX1E { x: 0 },
+            }
+            assert_eq!(cpu.i, 0x000);
+            assert_eq!(cpu.v[0xF], 1);
+        }
+
+        #[test]
+        fn _bnnn() {
+            let mut cpu = TestCPU::default();
+            run! {
+                cpu,
+                _6XNN { x: 0, nn: 0x10 },
+                _6XNN { x: 2, nn: 0x05 },
+                _BNNN { nnn: 0x210 },
+            }
+            assert_eq!(cpu.pc, 0x220);
+        }
+
+        #[test]
+        fn _bnnn_jump_uses_vx() {
+            let mut cpu = TestCPU::default();
+            cpu.set_quirks(Chip8Quirks {
+                jump_uses_vx: true,
+                ..Default::default()
+            });
+            run! {
+                cpu,
+                _6XNN { x: 0, nn: 0x10 },
+                _6XNN { x: 2, nn: 0x05 },
+                _BNNN { nnn: 0x210 },
+            }
+            assert_eq!(cpu.pc, 0x215);
+        }
+
+        #[derive(Default)]
+        struct ScriptedInput {
+            held: Cell<Option<u8>>,
+            released: Cell<Option<u8>>,
+        }
+
+        impl Chip8Input for ScriptedInput {
+            fn get_key(&self) -> Option<u8> {
+                return self.held.get();
+            }
+
+            fn was_key_released(&self) -> Option<u8> {
+                return self.released.take();
+            }
+        }
+
+        #[test]
+        fn _fx0a() {
+            let input = ScriptedInput::default();
+            let mut cpu = CPU::new(&NoopScreen, &input);
+            cpu.load_program(&[0xF3, 0x0A]).unwrap();
+
+            // Pressed and held, nothing is stored yet
+            input.held.set(Some(0x7));
+            cpu.step().unwrap();
+            cpu.step().unwrap();
+            assert_eq!(cpu.v[3], 0);
+            assert_eq!(cpu.pc, 0x200);
+
+            input.held.set(None);
+            input.released.set(Some(0x7));
+            cpu.step().unwrap();
+            assert_eq!(cpu.v[3], 0x7);
+            assert_eq!(cpu.pc, 0x202);
+        }
+
+        // Holds several keys at once, one bit per key
+        struct ChordInput(u16);
+
+        impl Chip8Input for ChordInput {
+            fn get_key(&self) -> Option<u8> {
+                return None;
+            }
+
+            fn was_key_released(&self) -> Option<u8> {
+                return None;
+            }
+
+            fn is_key_held(&self, key: u8) -> bool {
+                return key <= 0xF && self.0 & 1 << key != 0;
+            }
+        }
+
+        #[test]
+        fn _ex9e_exa1_check_each_held_key() {
+            let input = ChordInput(1 << 0x2 | 1 << 0x9);
+            for (key, ex9e_skips) in [(0x2, true), (0x9, true), (0x3, false), (0x19, false)] {
+                let mut cpu = CPU::new(&NoopScreen, &input);
+                cpu.v[1] = key;
+                cpu.load_program(&[0xE1, 0x9E]).unwrap();
+                cpu.step().unwrap();
+                assert_eq!(cpu.pc, if ex9e_skips { 0x204 } else { 0x202 }, "{key}");
+
+                let mut cpu = CPU::new(&NoopScreen, &input);
+                cpu.v[1] = key;
+                cpu.load_program(&[0xE1, 0xA1]).unwrap();
+                cpu.step().unwrap();
+                assert_eq!(cpu.pc, if ex9e_skips { 0x202 } else { 0x204 }, "{key}");
+            }
+        }
+
+        // Remembers the step count each before_step call saw
+        #[derive(Default)]
+        struct StepInput(std::cell::RefCell<Vec<u64>>);
+
+        impl Chip8Input for StepInput {
+            fn get_key(&self) -> Option<u8> {
+                return None;
+            }
+
+            fn was_key_released(&self) -> Option<u8> {
+                return None;
+            }
+
+            fn before_step(&self, step: u64) {
+                self.0.borrow_mut().push(step);
+            }
+        }
+
+        #[test]
+        fn before_step_sees_the_step_count() {
+            let input = StepInput::default();
+            let mut cpu = CPU::new(&NoopScreen, &input);
+            cpu.load_program(&[0x12, 0x00]).unwrap();
+            cpu.step_frame(3).unwrap();
+            // A paused CPU doesn't step
+            cpu.pause();
+            cpu.step().unwrap();
+            assert_eq!(*input.0.borrow(), [0, 1, 2]);
+        }
     }
 }
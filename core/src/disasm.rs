@@ -0,0 +1,258 @@
+use std::{
+    collections::{BTreeSet, HashSet},
+    fmt::Display,
+};
+
+use crate::{OpCodeIter, OpCodes};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DisasmKind {
+    // Reachable from the entry point and decodes to an instruction
+    Code(OpCodes),
+    // Never executed (or undecodable), emitted as a raw word
+    Data,
+    // Odd byte left over at the end of the ROM
+    TrailingByte,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DisasmLine {
+    pub address: u16,
+    pub word: u16,
+    pub kind: DisasmKind,
+    pub label: Option<String>,
+    pub text: String,
+}
+
+impl DisasmLine {
+    pub fn bytes(&self) -> Vec<u8> {
+        match self.kind {
+            DisasmKind::TrailingByte => vec![self.word as u8],
+            _ => self.word.to_be_bytes().to_vec(),
+        }
+    }
+}
+
+impl Display for DisasmLine {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.kind {
+            DisasmKind::TrailingByte => write!(
+                f,
+                "0x{:04X}: {:02X}    {}",
+                self.address, self.word, self.text
+            ),
+            _ => write!(
+                f,
+                "0x{:04X}: {:04X}  {}",
+                self.address, self.word, self.text
+            ),
+        }
+    }
+}
+
+pub fn label_name(address: u16) -> String {
+    return format!("L_{:04X}", address);
+}
+
+// Walks the ROM from base_addr following jumps and calls, anything never reached is treated as data.
+pub fn disassemble(rom: &[u8], base_addr: u16) -> Vec<DisasmLine> {
+    let end = base_addr as usize + rom.len();
+    let in_rom = |addr: u16| {
+        addr >= base_addr && (addr as usize) + 1 < end && (addr - base_addr).is_multiple_of(2)
+    };
+
+    let mut code = HashSet::new();
+    let mut labels = BTreeSet::new();
+    let mut pending = vec![base_addr];
+    while let Some(addr) = pending.pop() {
+        if !in_rom(addr) || code.contains(&addr) {
+            continue;
+        }
+        let offset = (addr - base_addr) as usize;
+        let Ok(opcode) = OpCodes::try_from((rom[offset], rom[offset + 1])) else {
+            continue;
+        };
+        code.insert(addr);
+        let next = addr.wrapping_add(2);
+        match opcode {
+            OpCodes::_1NNN { nnn } | OpCodes::_BNNN { nnn } => {
+                labels.insert(nnn);
+                pending.push(nnn);
+            }
+            OpCodes::_2NNN { nnn } => {
+                labels.insert(nnn);
+                pending.push(nnn);
+                pending.push(next);
+            }
+            OpCodes::_00EE => {}
+            OpCodes::_3XNN { .. }
+            | OpCodes::_4XNN { .. }
+            | OpCodes::_5XY0 { .. }
+            | OpCodes::_9XY0 { .. }
+            | OpCodes::_EX9E { .. }
+            | OpCodes::_EXA1 { .. } => {
+                pending.push(next);
+                pending.push(next.wrapping_add(2));
+            }
+            _ => pending.push(next),
+        }
+    }
+    // Only keep labels that land on a line we emit
+    labels.retain(|addr| in_rom(*addr));
+
+    let label_for = |nnn: u16| {
+        if labels.contains(&nnn) {
+            format!(":{}", label_name(nnn))
+        } else {
+            format!("0x{:03X}", nnn)
+        }
+    };
+
+    let mut lines = Vec::with_capacity(rom.len() / 2 + 1);
+    let mut words = OpCodeIter::new(rom, base_addr);
+    for (address, opcode) in words.by_ref() {
+        let offset = usize::from(address - base_addr);
+        let word = u16::from_be_bytes([rom[offset], rom[offset + 1]]);
+        let (kind, text) = match opcode {
+            Ok(opcode) if code.contains(&address) => {
+                (DisasmKind::Code(opcode), format_opcode(opcode, label_for))
+            }
+            _ => (DisasmKind::Data, format!("DW 0x{:04X}", word)),
+        };
+        lines.push(DisasmLine {
+            address,
+            word,
+            kind,
+            label: labels.contains(&address).then(|| label_name(address)),
+            text,
+        });
+    }
+    if let Some(byte) = words.trailing_byte() {
+        let address = base_addr + (rom.len() - 1) as u16;
+        lines.push(DisasmLine {
+            address,
+            word: byte as u16,
+            kind: DisasmKind::TrailingByte,
+            label: labels.contains(&address).then(|| label_name(address)),
+            text: format!("DB 0x{:02X}", byte),
+        });
+    }
+    return lines;
+}
+
+// Renders the lines as assembler source, with label definitions on their own line
+pub fn to_source(lines: &[DisasmLine]) -> String {
+    let mut source = String::new();
+    for line in lines {
+        if let Some(label) = &line.label {
+            source.push_str(&format!(":{}\n", label));
+        }
+        source.push_str(&format!("    {}\n", line.text));
+    }
+    return source;
+}
+
+// Same as the Display impl, but jump and call targets use labels where one exists
+fn format_opcode(opcode: OpCodes, address: impl Fn(u16) -> String) -> String {
+    match opcode {
+        OpCodes::_1NNN { nnn } | OpCodes::_2NNN { nnn } | OpCodes::_BNNN { nnn } => {
+            format!("{} {}", opcode.mnemonic(), address(nnn))
+        }
+        _ => opcode.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::convert_opcodes_into_u8;
+
+    fn sample_rom() -> Vec<u8> {
+        let mut rom = convert_opcodes_into_u8(&[
+            OpCodes::_6XNN { x: 0, nn: 0x05 }, // 0x200
+            OpCodes::_2NNN { nnn: 0x20C },     // 0x202
+            OpCodes::_3XNN { x: 0, nn: 0x00 }, // 0x204
+            OpCodes::_1NNN { nnn: 0x202 },     // 0x206
+            OpCodes::_1NNN { nnn: 0x206 },     // 0x208
+            OpCodes::_ANNN { nnn: 0x212 },     // 0x20A
+            OpCodes::_7XNN { x: 0, nn: 0xFF }, // 0x20C
+            OpCodes::_00EE,                    // 0x20E
+        ]);
+        // Sprite data that happens to decode as instructions
+        rom.extend_from_slice(&[0x60, 0x90, 0xF0, 0x90]); // 0x210
+        rom.push(0xAB);
+        return rom;
+    }
+
+    #[test]
+    fn follows_jumps_and_calls() {
+        let lines = disassemble(&sample_rom(), 0x200);
+        let kinds = lines.iter().map(|line| line.kind).collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            vec![
+                DisasmKind::Code(OpCodes::_6XNN { x: 0, nn: 0x05 }),
+                DisasmKind::Code(OpCodes::_2NNN { nnn: 0x20C }),
+                DisasmKind::Code(OpCodes::_3XNN { x: 0, nn: 0x00 }),
+                DisasmKind::Code(OpCodes::_1NNN { nnn: 0x202 }),
+                DisasmKind::Code(OpCodes::_1NNN { nnn: 0x206 }),
+                // Only reachable by falling through a jump, so it's data
+                DisasmKind::Data,
+                DisasmKind::Code(OpCodes::_7XNN { x: 0, nn: 0xFF }),
+                DisasmKind::Code(OpCodes::_00EE),
+                DisasmKind::Data,
+                DisasmKind::Data,
+                DisasmKind::TrailingByte,
+            ]
+        );
+    }
+
+    #[test]
+    fn labels_jump_and_call_targets() {
+        let lines = disassemble(&sample_rom(), 0x200);
+        let labelled = lines
+            .iter()
+            .filter_map(|line| line.label.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(labelled, vec!["L_0202", "L_0206", "L_020C"]);
+        assert_eq!(lines[1].text, "CALL :L_020C");
+        assert_eq!(lines[3].text, "JUMP :L_0202");
+        assert_eq!(lines[2].text, "SKE V0 0x00");
+        assert_eq!(lines[5].text, "DW 0xA212");
+        assert_eq!(lines[10].text, "DB 0xAB");
+        assert_eq!(lines[1].to_string(), "0x0202: 220C  CALL :L_020C");
+    }
+
+    #[test]
+    fn to_source_emits_label_definitions() {
+        let rom = convert_opcodes_into_u8(&[
+            OpCodes::_7XNN { x: 1, nn: 0x01 },
+            OpCodes::_1NNN { nnn: 0x200 },
+        ]);
+        let source = to_source(&disassemble(&rom, 0x200));
+        assert_eq!(source, ":L_0200\n    ADD V1 0x01\n    JUMP :L_0200\n");
+    }
+
+    #[test]
+    fn round_trips_to_identical_bytes() {
+        let rom = sample_rom();
+        let lines = disassemble(&rom, 0x200);
+        let reassembled = lines
+            .iter()
+            .flat_map(|line| match line.kind {
+                DisasmKind::Code(opcode) => convert_opcodes_into_u8(&[opcode]),
+                _ => line.bytes(),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(reassembled, rom);
+    }
+
+    #[test]
+    fn ignores_targets_outside_rom() {
+        let rom = convert_opcodes_into_u8(&[OpCodes::_1NNN { nnn: 0x400 }]);
+        let lines = disassemble(&rom, 0x200);
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0].label, None);
+        assert_eq!(lines[0].text, "JUMP 0x400");
+    }
+}
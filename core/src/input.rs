@@ -1,7 +1,16 @@
-use std::{thread, time::Duration};
-
 pub trait Chip8Input {
     fn get_key(&self) -> Option<u8>;
+    // The key released since the last call, if any. Reading it consumes the release so FX0A
+    // waiting on the next key doesn't see the same one twice.
+    fn was_key_released(&self) -> Option<u8>;
+    // EX9E / EXA1 ask about one key, inputs that track every key should override this so a key
+    // still counts as held while a lower one is down too
+    fn is_key_held(&self, key: u8) -> bool {
+        return self.get_key() == Some(key);
+    }
+    // Called at the start of every step with the number of instructions run so far, for inputs
+    // that change keys at exact points in the program rather than whenever the keyboard does
+    fn before_step(&self, _step: u64) {}
 }
 
 pub struct NoopInput;
@@ -10,4 +19,12 @@ impl Chip8Input for NoopInput {
     fn get_key(&self) -> Option<u8> {
         return None;
     }
+
+    fn was_key_released(&self) -> Option<u8> {
+        return None;
+    }
+
+    fn is_key_held(&self, _key: u8) -> bool {
+        return false;
+    }
 }
@@ -1,11 +1,29 @@
 mod cpu;
+pub mod disasm;
 mod input;
 mod opcodes;
+mod palette;
+mod pixel_buffer;
+mod program_builder;
+mod quirks;
+mod replay;
+pub mod rom;
 mod screen;
+mod snapshot;
+mod sound;
+mod state;
 mod test;
 
 pub use cpu::*;
 pub use input::*;
 pub use opcodes::*;
+pub use palette::*;
+pub use pixel_buffer::*;
+pub use program_builder::*;
+pub use quirks::*;
+pub use replay::*;
 pub use screen::*;
+pub use snapshot::*;
+pub use sound::*;
+pub use state::*;
 pub use test::*;
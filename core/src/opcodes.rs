@@ -1,10 +1,15 @@
+use std::fmt::Display;
+
+use smallvec::{smallvec, SmallVec};
 use thiserror::Error;
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum OpCodes {
     _0NNN { nnn: u16 },
     _00E0,
     _00EE,
+    _00FD,
     _1NNN { nnn: u16 },
     _2NNN { nnn: u16 },
     _3XNN { x: u8, nn: u8 },
@@ -34,22 +39,18 @@ pub enum OpCodes {
     _FX18 { x: u8 },
     _FX1E { x: u8 },
     _FX29 { x: u8 },
+    _FX30 { x: u8 },
     _FX33 { x: u8 },
     _FX55 { x: u8 },
     _FX65 { x: u8 },
+    _FX75 { x: u8 },
+    _FX85 { x: u8 },
 }
 
-fn nn(instruction: u16) -> u8 {
-    return (instruction & 0xFF) as u8;
-}
-
-fn nnn(instruction: u16) -> u16 {
-    return (instruction & 0xFFF) as u16;
-}
-
-#[derive(Error, Debug)]
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Chip8Error {
-    #[error("Invalid opcode: {0}")]
+    #[error("Invalid opcode: 0x{0:04X}")]
     InvalidOpcodeError(u16),
     #[error("Unknown opcode: {0:?}")]
     UnknownOpcodeError(OpCodes),
@@ -57,84 +58,314 @@ pub enum Chip8Error {
     UnimplementedOpcodeError(OpCodes),
     #[error("Stack underflow")]
     StackUnderflowError,
+    #[error("ROM too large: {size} bytes but only {available} bytes available")]
+    RomTooLargeError { size: usize, available: usize },
+    #[error("Memory access out of bounds: {len} bytes at 0x{addr:03X}")]
+    MemoryOutOfBoundsError { addr: u16, len: usize },
+    #[error("Patch at 0x{addr:03X} overlaps the patch at 0x{existing:03X}")]
+    PatchOverlapError { addr: u16, existing: u16 },
+    #[error("ROM is {len} bytes, instructions are 2 bytes so it looks truncated")]
+    OddLengthRomError { len: usize },
+    #[error("Memory at 0x{addr:03X} is reserved, can't load {len} bytes there")]
+    ProtectedMemoryError { addr: u16, len: usize },
+    // 00FD, not a fault but it stops the CPU like one
+    #[error("Program exited")]
+    ExitRequested,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    // The bytes at PC aren't an instruction
+    Decode,
+    Memory,
+    Stack,
+    // A valid instruction this interpreter doesn't run
+    Unsupported,
+    // The program asked to stop
+    Exit,
+}
+
+impl Chip8Error {
+    pub fn category(&self) -> ErrorKind {
+        return match self {
+            Chip8Error::InvalidOpcodeError(_)
+            | Chip8Error::UnknownOpcodeError(_)
+            | Chip8Error::OddLengthRomError { .. } => ErrorKind::Decode,
+            Chip8Error::UnimplementedOpcodeError(_) => ErrorKind::Unsupported,
+            Chip8Error::StackUnderflowError => ErrorKind::Stack,
+            Chip8Error::ExitRequested => ErrorKind::Exit,
+            Chip8Error::RomTooLargeError { .. }
+            | Chip8Error::MemoryOutOfBoundsError { .. }
+            | Chip8Error::PatchOverlapError { .. }
+            | Chip8Error::ProtectedMemoryError { .. } => ErrorKind::Memory,
+        };
+    }
+
+    // Non-fatal errors leave the CPU in a state it can carry on from by skipping the instruction
+    pub fn is_fatal(&self) -> bool {
+        return self.category() != ErrorKind::Unsupported;
+    }
 }
 
 impl TryFrom<(u8, u8)> for OpCodes {
     type Error = Chip8Error;
 
+    // Hot path, dispatch on the top nibble and pull the operands straight out of the bytes
     fn try_from((op1, op2): (u8, u8)) -> Result<Self, Self::Error> {
-        let char1 = op1 >> 4;
-        let char2 = op1 & 0x0F;
-        let char3 = op2 >> 4;
-        let char4 = op2 & 0x0F;
-        let instruction: u16 = op1 as u16 * 256 + op2 as u16;
-
-        match (char1, char2, char3, char4) {
-            (0, 0, 0xE, 0) => Ok(Self::_00E0),
-            (0, 0, 0xE, 0xE) => Ok(Self::_00EE),
-            (0, _, _, _) => Ok(Self::_0NNN {
-                nnn: nnn(instruction),
-            }),
-            (1, _, _, _) => Ok(Self::_1NNN {
-                nnn: nnn(instruction),
-            }),
-            (2, _, _, _) => Ok(Self::_2NNN {
-                nnn: nnn(instruction),
-            }),
-            (3, x, _, _) => Ok(Self::_3XNN {
-                x,
-                nn: nn(instruction),
-            }),
-            (4, x, _, _) => Ok(Self::_4XNN {
-                x,
-                nn: nn(instruction),
-            }),
-            (5, x, y, 0) => Ok(Self::_5XY0 { x, y }),
-            (6, x, _, _) => Ok(Self::_6XNN {
-                x,
-                nn: nn(instruction),
-            }),
-            (7, x, _, _) => Ok(Self::_7XNN {
-                x,
-                nn: nn(instruction),
-            }),
-            (8, x, y, 0) => Ok(Self::_8XY0 { x, y }),
-            (8, x, y, 1) => Ok(Self::_8XY1 { x, y }),
-            (8, x, y, 2) => Ok(Self::_8XY2 { x, y }),
-            (8, x, y, 3) => Ok(Self::_8XY3 { x, y }),
-            (8, x, y, 4) => Ok(Self::_8XY4 { x, y }),
-            (8, x, y, 5) => Ok(Self::_8XY5 { x, y }),
-            (8, x, y, 6) => Ok(Self::_8XY6 { x, y }),
-            (8, x, y, 7) => Ok(Self::_8XY7 { x, y }),
-            (8, x, y, 0xE) => Ok(Self::_8XYE { x, y }),
-            (9, x, y, 0) => Ok(Self::_9XY0 { x, y }),
-            (0xA, _, _, _) => Ok(Self::_ANNN {
-                nnn: nnn(instruction),
-            }),
-            (0xB, _, _, _) => Ok(Self::_BNNN {
-                nnn: nnn(instruction),
-            }),
-            (0xC, x, _, _) => Ok(Self::_CXNN {
-                x,
-                nn: nn(instruction),
-            }),
-            (0xD, x, y, n) => Ok(Self::_DXYN { x, y, n }),
-            (0xE, x, 0x9, 0xE) => Ok(Self::_EX9E { x }),
-            (0xE, x, 0xA, 0x1) => Ok(Self::_EXA1 { x }),
-            (0xF, x, 0x0, 0x7) => Ok(Self::_FX07 { x }),
-            (0xF, x, 0x0, 0xA) => Ok(Self::_FX0A { x }),
-            (0xF, x, 0x1, 0x5) => Ok(Self::_FX15 { x }),
-            (0xF, x, 0x1, 0x8) => Ok(Self::_FX18 { x }),
-            (0xF, x, 0x1, 0xE) => Ok(Self::_FX1E { x }),
-            (0xF, x, 0x2, 0x9) => Ok(Self::_FX29 { x }),
-            (0xF, x, 0x3, 0x3) => Ok(Self::_FX33 { x }),
-            (0xF, x, 0x5, 0x5) => Ok(Self::_FX55 { x }),
-            (0xF, x, 0x6, 0x5) => Ok(Self::_FX65 { x }),
-            _ => Err(Chip8Error::InvalidOpcodeError(instruction)),
+        let instruction = u16::from_be_bytes([op1, op2]);
+        let x = op1 & 0x0F;
+        let y = op2 >> 4;
+        let n = op2 & 0x0F;
+        let nn = op2;
+        let nnn = instruction & 0x0FFF;
+
+        let opcode = match op1 >> 4 {
+            0x0 => match instruction {
+                0x00E0 => Self::_00E0,
+                0x00EE => Self::_00EE,
+                0x00FD => Self::_00FD,
+                _ => Self::_0NNN { nnn },
+            },
+            0x1 => Self::_1NNN { nnn },
+            0x2 => Self::_2NNN { nnn },
+            0x3 => Self::_3XNN { x, nn },
+            0x4 => Self::_4XNN { x, nn },
+            0x5 if n == 0 => Self::_5XY0 { x, y },
+            0x6 => Self::_6XNN { x, nn },
+            0x7 => Self::_7XNN { x, nn },
+            0x8 => match n {
+                0x0 => Self::_8XY0 { x, y },
+                0x1 => Self::_8XY1 { x, y },
+                0x2 => Self::_8XY2 { x, y },
+                0x3 => Self::_8XY3 { x, y },
+                0x4 => Self::_8XY4 { x, y },
+                0x5 => Self::_8XY5 { x, y },
+                0x6 => Self::_8XY6 { x, y },
+                0x7 => Self::_8XY7 { x, y },
+                0xE => Self::_8XYE { x, y },
+                _ => return Err(Chip8Error::InvalidOpcodeError(instruction)),
+            },
+            0x9 if n == 0 => Self::_9XY0 { x, y },
+            0xA => Self::_ANNN { nnn },
+            0xB => Self::_BNNN { nnn },
+            0xC => Self::_CXNN { x, nn },
+            0xD => Self::_DXYN { x, y, n },
+            0xE => match nn {
+                0x9E => Self::_EX9E { x },
+                0xA1 => Self::_EXA1 { x },
+                _ => return Err(Chip8Error::InvalidOpcodeError(instruction)),
+            },
+            0xF => match nn {
+                0x07 => Self::_FX07 { x },
+                0x0A => Self::_FX0A { x },
+                0x15 => Self::_FX15 { x },
+                0x18 => Self::_FX18 { x },
+                0x1E => Self::_FX1E { x },
+                0x29 => Self::_FX29 { x },
+                0x30 => Self::_FX30 { x },
+                0x33 => Self::_FX33 { x },
+                0x55 => Self::_FX55 { x },
+                0x65 => Self::_FX65 { x },
+                0x75 => Self::_FX75 { x },
+                0x85 => Self::_FX85 { x },
+                _ => return Err(Chip8Error::InvalidOpcodeError(instruction)),
+            },
+            _ => return Err(Chip8Error::InvalidOpcodeError(instruction)),
+        };
+        return Ok(opcode);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operand {
+    Register(u8),
+    Byte(u8),
+    Nibble(u8),
+    Address(u16),
+}
+
+impl Display for Operand {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Operand::Register(x) => write!(f, "V{:X}", x),
+            Operand::Byte(nn) => write!(f, "0x{:02X}", nn),
+            Operand::Nibble(n) => write!(f, "0x{:X}", n),
+            Operand::Address(nnn) => write!(f, "0x{:03X}", nnn),
+        }
+    }
+}
+
+impl OpCodes {
+    // Encoding pattern as written in the spec, X / Y are registers and N is an immediate
+    pub fn pattern(&self) -> &'static str {
+        match self {
+            OpCodes::_0NNN { .. } => "0NNN",
+            OpCodes::_00E0 => "00E0",
+            OpCodes::_00EE => "00EE",
+            OpCodes::_00FD => "00FD",
+            OpCodes::_1NNN { .. } => "1NNN",
+            OpCodes::_2NNN { .. } => "2NNN",
+            OpCodes::_3XNN { .. } => "3XNN",
+            OpCodes::_4XNN { .. } => "4XNN",
+            OpCodes::_5XY0 { .. } => "5XY0",
+            OpCodes::_6XNN { .. } => "6XNN",
+            OpCodes::_7XNN { .. } => "7XNN",
+            OpCodes::_8XY0 { .. } => "8XY0",
+            OpCodes::_8XY1 { .. } => "8XY1",
+            OpCodes::_8XY2 { .. } => "8XY2",
+            OpCodes::_8XY3 { .. } => "8XY3",
+            OpCodes::_8XY4 { .. } => "8XY4",
+            OpCodes::_8XY5 { .. } => "8XY5",
+            OpCodes::_8XY6 { .. } => "8XY6",
+            OpCodes::_8XY7 { .. } => "8XY7",
+            OpCodes::_8XYE { .. } => "8XYE",
+            OpCodes::_9XY0 { .. } => "9XY0",
+            OpCodes::_ANNN { .. } => "ANNN",
+            OpCodes::_BNNN { .. } => "BNNN",
+            OpCodes::_CXNN { .. } => "CXNN",
+            OpCodes::_DXYN { .. } => "DXYN",
+            OpCodes::_EX9E { .. } => "EX9E",
+            OpCodes::_EXA1 { .. } => "EXA1",
+            OpCodes::_FX07 { .. } => "FX07",
+            OpCodes::_FX0A { .. } => "FX0A",
+            OpCodes::_FX15 { .. } => "FX15",
+            OpCodes::_FX18 { .. } => "FX18",
+            OpCodes::_FX1E { .. } => "FX1E",
+            OpCodes::_FX29 { .. } => "FX29",
+            OpCodes::_FX30 { .. } => "FX30",
+            OpCodes::_FX33 { .. } => "FX33",
+            OpCodes::_FX55 { .. } => "FX55",
+            OpCodes::_FX65 { .. } => "FX65",
+            OpCodes::_FX75 { .. } => "FX75",
+            OpCodes::_FX85 { .. } => "FX85",
+        }
+    }
+
+    // For a word that doesn't decode, the valid opcodes in the same family that are one nibble away.
+    // Each nibble only suggests the nearest valid value below and above it (8AB8 -> 8AB7 / 8ABE).
+    pub fn closest_matches(word: u16) -> Vec<(OpCodes, &'static str)> {
+        let [op1, op2] = word.to_be_bytes();
+        if OpCodes::try_from((op1, op2)).is_ok() {
+            return vec![];
+        }
+        let mut matches: Vec<(OpCodes, &'static str)> = vec![];
+        // The top nibble picks the family so it's never changed
+        for shift in [8, 4, 0] {
+            let current = (word >> shift) & 0xF;
+            let decode = |nibble: u16| {
+                let [op1, op2] = ((word & !(0xF << shift)) | (nibble << shift)).to_be_bytes();
+                return OpCodes::try_from((op1, op2)).ok();
+            };
+            let below = (0..current).rev().find_map(decode);
+            let above = (current + 1..=0xF).find_map(decode);
+            for opcode in [below, above].into_iter().flatten() {
+                if !matches.iter().any(|(existing, _)| *existing == opcode) {
+                    matches.push((opcode, opcode.pattern()));
+                }
+            }
+        }
+        return matches;
+    }
+
+    // Mnemonics follow the assembler syntax so formatted output can be re-assembled
+    pub fn mnemonic(&self) -> &'static str {
+        match self {
+            OpCodes::_0NNN { .. } => "SYS",
+            OpCodes::_00E0 => "CLR",
+            OpCodes::_00EE => "RTS",
+            OpCodes::_00FD => "EXIT",
+            OpCodes::_1NNN { .. } => "JUMP",
+            OpCodes::_2NNN { .. } => "CALL",
+            OpCodes::_3XNN { .. } => "SKE",
+            OpCodes::_4XNN { .. } => "SKNE",
+            OpCodes::_5XY0 { .. } => "SKRE",
+            OpCodes::_6XNN { .. } => "LOAD",
+            OpCodes::_7XNN { .. } => "ADD",
+            OpCodes::_8XY0 { .. } => "MOVE",
+            OpCodes::_8XY1 { .. } => "OR",
+            OpCodes::_8XY2 { .. } => "AND",
+            OpCodes::_8XY3 { .. } => "XOR",
+            OpCodes::_8XY4 { .. } => "ADDR",
+            OpCodes::_8XY5 { .. } => "SUB",
+            OpCodes::_8XY6 { .. } => "SHR",
+            OpCodes::_8XY7 { .. } => "SUBR",
+            OpCodes::_8XYE { .. } => "SHL",
+            OpCodes::_9XY0 { .. } => "SKRNE",
+            OpCodes::_ANNN { .. } => "LOADI",
+            OpCodes::_BNNN { .. } => "JUMPI",
+            OpCodes::_CXNN { .. } => "RAND",
+            OpCodes::_DXYN { .. } => "DRAW",
+            OpCodes::_EX9E { .. } => "SKPR",
+            OpCodes::_EXA1 { .. } => "SKUP",
+            OpCodes::_FX07 { .. } => "MOVED",
+            OpCodes::_FX0A { .. } => "KEYD",
+            OpCodes::_FX15 { .. } => "LOADD",
+            OpCodes::_FX18 { .. } => "LOADS",
+            OpCodes::_FX1E { .. } => "ADDI",
+            OpCodes::_FX29 { .. } => "LDSPR",
+            OpCodes::_FX30 { .. } => "LDHSPR",
+            OpCodes::_FX33 { .. } => "BCD",
+            OpCodes::_FX55 { .. } => "STOR",
+            OpCodes::_FX65 { .. } => "READ",
+            OpCodes::_FX75 { .. } => "STORF",
+            OpCodes::_FX85 { .. } => "READF",
+        }
+    }
+
+    pub fn operands(&self) -> SmallVec<[Operand; 3]> {
+        use Operand::*;
+        match *self {
+            OpCodes::_00E0 | OpCodes::_00EE | OpCodes::_00FD => smallvec![],
+            OpCodes::_0NNN { nnn }
+            | OpCodes::_1NNN { nnn }
+            | OpCodes::_2NNN { nnn }
+            | OpCodes::_ANNN { nnn }
+            | OpCodes::_BNNN { nnn } => smallvec![Address(nnn)],
+            OpCodes::_3XNN { x, nn }
+            | OpCodes::_4XNN { x, nn }
+            | OpCodes::_6XNN { x, nn }
+            | OpCodes::_7XNN { x, nn }
+            | OpCodes::_CXNN { x, nn } => smallvec![Register(x), Byte(nn)],
+            OpCodes::_5XY0 { x, y }
+            | OpCodes::_8XY0 { x, y }
+            | OpCodes::_8XY1 { x, y }
+            | OpCodes::_8XY2 { x, y }
+            | OpCodes::_8XY3 { x, y }
+            | OpCodes::_8XY4 { x, y }
+            | OpCodes::_8XY5 { x, y }
+            | OpCodes::_8XY6 { x, y }
+            | OpCodes::_8XY7 { x, y }
+            | OpCodes::_8XYE { x, y }
+            | OpCodes::_9XY0 { x, y } => smallvec![Register(x), Register(y)],
+            OpCodes::_DXYN { x, y, n } => smallvec![Register(x), Register(y), Nibble(n)],
+            OpCodes::_EX9E { x }
+            | OpCodes::_EXA1 { x }
+            | OpCodes::_FX07 { x }
+            | OpCodes::_FX0A { x }
+            | OpCodes::_FX15 { x }
+            | OpCodes::_FX18 { x }
+            | OpCodes::_FX1E { x }
+            | OpCodes::_FX29 { x }
+            | OpCodes::_FX30 { x }
+            | OpCodes::_FX33 { x }
+            | OpCodes::_FX55 { x }
+            | OpCodes::_FX65 { x }
+            | OpCodes::_FX75 { x }
+            | OpCodes::_FX85 { x } => smallvec![Register(x)],
         }
     }
 }
 
+impl Display for OpCodes {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.mnemonic())?;
+        for operand in self.operands() {
+            write!(f, " {}", operand)?;
+        }
+        Ok(())
+    }
+}
+
 fn left_bit(hex: u8) -> u8 {
     return hex << 4;
 }
@@ -142,8 +373,9 @@ fn left_bit(hex: u8) -> u8 {
 impl From<OpCodes> for (u8, u8) {
     fn from(op_code: OpCodes) -> Self {
         match op_code {
-            OpCodes::_00E0 => (left_bit(0) & 0, 0xE),
-            OpCodes::_00EE => (left_bit(0) & 0, 0xEE),
+            OpCodes::_00E0 => (0x00, 0xE0),
+            OpCodes::_00EE => (0x00, 0xEE),
+            OpCodes::_00FD => (0x00, 0xFD),
             OpCodes::_0NNN { nnn } => (left_bit(0) | (nnn >> 8) as u8, nnn as u8),
             OpCodes::_1NNN { nnn } => (left_bit(1) | (nnn >> 8) as u8, nnn as u8),
             OpCodes::_2NNN { nnn } => (left_bit(2) | (nnn >> 8) as u8, nnn as u8),
@@ -174,13 +406,58 @@ impl From<OpCodes> for (u8, u8) {
             OpCodes::_FX18 { x } => (left_bit(0xF) | x, 0x18),
             OpCodes::_FX1E { x } => (left_bit(0xF) | x, 0x1E),
             OpCodes::_FX29 { x } => (left_bit(0xF) | x, 0x29),
+            OpCodes::_FX30 { x } => (left_bit(0xF) | x, 0x30),
             OpCodes::_FX33 { x } => (left_bit(0xF) | x, 0x33),
             OpCodes::_FX55 { x } => (left_bit(0xF) | x, 0x55),
             OpCodes::_FX65 { x } => (left_bit(0xF) | x, 0x65),
+            OpCodes::_FX75 { x } => (left_bit(0xF) | x, 0x75),
+            OpCodes::_FX85 { x } => (left_bit(0xF) | x, 0x85),
         }
     }
 }
 
+// Lazily decodes a ROM one word at a time, yielding the address of each word alongside the result
+// so callers can decide what to do with words that don't decode (usually sprite data).
+pub struct OpCodeIter<'a> {
+    bytes: &'a [u8],
+    base_addr: u16,
+    offset: usize,
+}
+
+impl<'a> OpCodeIter<'a> {
+    pub fn new(bytes: &'a [u8], base_addr: u16) -> OpCodeIter<'a> {
+        return OpCodeIter {
+            bytes,
+            base_addr,
+            offset: 0,
+        };
+    }
+
+    // The last byte of an odd length ROM, which is never yielded as part of a word
+    pub fn trailing_byte(&self) -> Option<u8> {
+        if !self.bytes.len().is_multiple_of(2) {
+            return self.bytes.last().copied();
+        }
+        return None;
+    }
+}
+
+impl Iterator for OpCodeIter<'_> {
+    type Item = (u16, Result<OpCodes, Chip8Error>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let word = self.bytes.get(self.offset..self.offset + 2)?;
+        let address = self.base_addr.wrapping_add(self.offset as u16);
+        self.offset += 2;
+        return Some((address, OpCodes::try_from((word[0], word[1]))));
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.bytes.len().saturating_sub(self.offset) / 2;
+        return (remaining, Some(remaining));
+    }
+}
+
 pub fn convert_opcodes_into_u8_tuples(slice: &[OpCodes]) -> Vec<(u8, u8)> {
     slice.iter().map(|&b| b.into()).collect()
 }
@@ -190,7 +467,7 @@ pub fn convert_opcodes_into_u8(slice: &[OpCodes]) -> Vec<u8> {
         .iter()
         .flat_map(|&b| {
             let (op1, op2) = b.into();
-            [op1 as u8, op2 as u8]
+            [op1, op2]
         })
         .collect()
 }
@@ -213,15 +490,298 @@ pub fn convert_u8_tuples_into_opcodes(slice: &[(u8, u8)]) -> Result<Vec<OpCodes>
 }
 
 pub fn convert_u8_into_opcodes(slice: &[u8]) -> Result<Vec<OpCodes>, Chip8Error> {
+    // A trailing byte is half an instruction, the file was most likely cut off
+    if !slice.len().is_multiple_of(2) {
+        return Err(Chip8Error::OddLengthRomError { len: slice.len() });
+    }
     slice
-        .chunks(2)
-        .filter_map(|chunk| {
-            // Convert chunk to tuple if it has 2 elements
-            if chunk.len() == 2 {
-                Some((chunk[0], chunk[1]).try_into())
-            } else {
-                None // Ignore incomplete chunks
-            }
-        })
+        .chunks_exact(2)
+        .map(|chunk| (chunk[0], chunk[1]).try_into())
         .collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display_every_opcode() {
+        let cases = [
+            (OpCodes::_0NNN { nnn: 0x123 }, "SYS 0x123"),
+            (OpCodes::_00E0, "CLR"),
+            (OpCodes::_00EE, "RTS"),
+            (OpCodes::_00FD, "EXIT"),
+            (OpCodes::_1NNN { nnn: 0x228 }, "JUMP 0x228"),
+            (OpCodes::_2NNN { nnn: 0x300 }, "CALL 0x300"),
+            (OpCodes::_3XNN { x: 0x1, nn: 0x12 }, "SKE V1 0x12"),
+            (OpCodes::_4XNN { x: 0x2, nn: 0x34 }, "SKNE V2 0x34"),
+            (OpCodes::_5XY0 { x: 0x3, y: 0x4 }, "SKRE V3 V4"),
+            (OpCodes::_6XNN { x: 0x0, nn: 0x12 }, "LOAD V0 0x12"),
+            (OpCodes::_7XNN { x: 0xA, nn: 0x01 }, "ADD VA 0x01"),
+            (OpCodes::_8XY0 { x: 0x1, y: 0x2 }, "MOVE V1 V2"),
+            (OpCodes::_8XY1 { x: 0x1, y: 0x2 }, "OR V1 V2"),
+            (OpCodes::_8XY2 { x: 0x1, y: 0x2 }, "AND V1 V2"),
+            (OpCodes::_8XY3 { x: 0x1, y: 0x2 }, "XOR V1 V2"),
+            (OpCodes::_8XY4 { x: 0x1, y: 0x2 }, "ADDR V1 V2"),
+            (OpCodes::_8XY5 { x: 0x1, y: 0x2 }, "SUB V1 V2"),
+            (OpCodes::_8XY6 { x: 0x1, y: 0x2 }, "SHR V1 V2"),
+            (OpCodes::_8XY7 { x: 0x1, y: 0x2 }, "SUBR V1 V2"),
+            (OpCodes::_8XYE { x: 0x1, y: 0x2 }, "SHL V1 V2"),
+            (OpCodes::_9XY0 { x: 0xE, y: 0xF }, "SKRNE VE VF"),
+            (OpCodes::_ANNN { nnn: 0x050 }, "LOADI 0x050"),
+            (OpCodes::_BNNN { nnn: 0x400 }, "JUMPI 0x400"),
+            (OpCodes::_CXNN { x: 0x5, nn: 0x0F }, "RAND V5 0x0F"),
+            (
+                OpCodes::_DXYN {
+                    x: 0x1,
+                    y: 0x2,
+                    n: 0x5,
+                },
+                "DRAW V1 V2 0x5",
+            ),
+            (OpCodes::_EX9E { x: 0x3 }, "SKPR V3"),
+            (OpCodes::_EXA1 { x: 0x3 }, "SKUP V3"),
+            (OpCodes::_FX07 { x: 0x4 }, "MOVED V4"),
+            (OpCodes::_FX0A { x: 0x4 }, "KEYD V4"),
+            (OpCodes::_FX15 { x: 0x4 }, "LOADD V4"),
+            (OpCodes::_FX18 { x: 0x4 }, "LOADS V4"),
+            (OpCodes::_FX1E { x: 0x4 }, "ADDI V4"),
+            (OpCodes::_FX29 { x: 0x4 }, "LDSPR V4"),
+            (OpCodes::_FX30 { x: 0x4 }, "LDHSPR V4"),
+            (OpCodes::_FX33 { x: 0x4 }, "BCD V4"),
+            (OpCodes::_FX55 { x: 0x4 }, "STOR V4"),
+            (OpCodes::_FX65 { x: 0x4 }, "READ V4"),
+            (OpCodes::_FX75 { x: 0x4 }, "STORF V4"),
+            (OpCodes::_FX85 { x: 0x4 }, "READF V4"),
+        ];
+        for (opcode, expected) in cases {
+            assert_eq!(opcode.to_string(), expected);
+            assert!(expected.starts_with(opcode.mnemonic()));
+        }
+    }
+
+    #[test]
+    fn operands_are_typed() {
+        let opcode = OpCodes::_DXYN { x: 1, y: 2, n: 3 };
+        assert_eq!(
+            opcode.operands().as_slice(),
+            &[
+                Operand::Register(1),
+                Operand::Register(2),
+                Operand::Nibble(3)
+            ]
+        );
+        assert!(OpCodes::_00E0.operands().is_empty());
+    }
+
+    // One of every variant, with all registers and the boundary values for nn / nnn / n
+    fn representative_opcodes() -> Vec<OpCodes> {
+        let mut opcodes = vec![OpCodes::_00E0, OpCodes::_00EE, OpCodes::_00FD];
+        // 0x0E0, 0x0EE and 0x0FD are taken by CLR / RTS / EXIT so SYS can't round trip those
+        for nnn in [0x000, 0x001, 0x0FF, 0x100, 0x800, 0xFFE, 0xFFF] {
+            opcodes.push(OpCodes::_0NNN { nnn });
+            opcodes.push(OpCodes::_1NNN { nnn });
+            opcodes.push(OpCodes::_2NNN { nnn });
+            opcodes.push(OpCodes::_ANNN { nnn });
+            opcodes.push(OpCodes::_BNNN { nnn });
+        }
+        for x in 0..=0xF {
+            for nn in [0x00, 0x01, 0x7F, 0x80, 0xFE, 0xFF] {
+                opcodes.push(OpCodes::_3XNN { x, nn });
+                opcodes.push(OpCodes::_4XNN { x, nn });
+                opcodes.push(OpCodes::_6XNN { x, nn });
+                opcodes.push(OpCodes::_7XNN { x, nn });
+                opcodes.push(OpCodes::_CXNN { x, nn });
+            }
+            for y in 0..=0xF {
+                opcodes.push(OpCodes::_5XY0 { x, y });
+                opcodes.push(OpCodes::_8XY0 { x, y });
+                opcodes.push(OpCodes::_8XY1 { x, y });
+                opcodes.push(OpCodes::_8XY2 { x, y });
+                opcodes.push(OpCodes::_8XY3 { x, y });
+                opcodes.push(OpCodes::_8XY4 { x, y });
+                opcodes.push(OpCodes::_8XY5 { x, y });
+                opcodes.push(OpCodes::_8XY6 { x, y });
+                opcodes.push(OpCodes::_8XY7 { x, y });
+                opcodes.push(OpCodes::_8XYE { x, y });
+                opcodes.push(OpCodes::_9XY0 { x, y });
+                for n in [0x0, 0x1, 0xF] {
+                    opcodes.push(OpCodes::_DXYN { x, y, n });
+                }
+            }
+            opcodes.push(OpCodes::_EX9E { x });
+            opcodes.push(OpCodes::_EXA1 { x });
+            opcodes.push(OpCodes::_FX07 { x });
+            opcodes.push(OpCodes::_FX0A { x });
+            opcodes.push(OpCodes::_FX15 { x });
+            opcodes.push(OpCodes::_FX18 { x });
+            opcodes.push(OpCodes::_FX1E { x });
+            opcodes.push(OpCodes::_FX29 { x });
+            opcodes.push(OpCodes::_FX30 { x });
+            opcodes.push(OpCodes::_FX33 { x });
+            opcodes.push(OpCodes::_FX55 { x });
+            opcodes.push(OpCodes::_FX65 { x });
+            opcodes.push(OpCodes::_FX75 { x });
+            opcodes.push(OpCodes::_FX85 { x });
+        }
+        return opcodes;
+    }
+
+    #[test]
+    fn invalid_opcode_error_is_hex() {
+        assert_eq!(
+            Chip8Error::InvalidOpcodeError(0x8AB8).to_string(),
+            "Invalid opcode: 0x8AB8"
+        );
+    }
+
+    #[test]
+    fn errors_compare_and_categorize() {
+        let sys = OpCodes::_0NNN { nnn: 0x123 };
+        assert_eq!(
+            Chip8Error::UnimplementedOpcodeError(sys),
+            Chip8Error::UnimplementedOpcodeError(sys).clone()
+        );
+        assert_ne!(
+            Chip8Error::InvalidOpcodeError(0x5AB1),
+            Chip8Error::InvalidOpcodeError(0x5AB2)
+        );
+        let cases = [
+            (Chip8Error::InvalidOpcodeError(0x5AB1), ErrorKind::Decode),
+            (Chip8Error::UnknownOpcodeError(sys), ErrorKind::Decode),
+            (
+                Chip8Error::UnimplementedOpcodeError(sys),
+                ErrorKind::Unsupported,
+            ),
+            (Chip8Error::StackUnderflowError, ErrorKind::Stack),
+            (
+                Chip8Error::RomTooLargeError {
+                    size: 4000,
+                    available: 3584,
+                },
+                ErrorKind::Memory,
+            ),
+            (
+                Chip8Error::MemoryOutOfBoundsError {
+                    addr: 0xFFF,
+                    len: 2,
+                },
+                ErrorKind::Memory,
+            ),
+            (
+                Chip8Error::PatchOverlapError {
+                    addr: 0x301,
+                    existing: 0x300,
+                },
+                ErrorKind::Memory,
+            ),
+        ];
+        for (err, kind) in cases {
+            assert_eq!(err.category(), kind, "{:?}", err);
+            assert_eq!(err.is_fatal(), kind != ErrorKind::Unsupported, "{:?}", err);
+        }
+    }
+
+    #[test]
+    fn closest_matches_suggest_neighbouring_encodings() {
+        assert_eq!(
+            OpCodes::closest_matches(0x8AB8),
+            vec![
+                (OpCodes::_8XY7 { x: 0xA, y: 0xB }, "8XY7"),
+                (OpCodes::_8XYE { x: 0xA, y: 0xB }, "8XYE"),
+            ]
+        );
+        assert_eq!(
+            OpCodes::closest_matches(0xE19F),
+            vec![(OpCodes::_EX9E { x: 0x1 }, "EX9E")]
+        );
+        assert_eq!(
+            OpCodes::closest_matches(0xF316),
+            vec![
+                (OpCodes::_FX15 { x: 0x3 }, "FX15"),
+                (OpCodes::_FX18 { x: 0x3 }, "FX18"),
+            ]
+        );
+        // Valid words have nothing to suggest
+        assert!(OpCodes::closest_matches(0x8AB7).is_empty());
+    }
+
+    #[test]
+    fn opcode_iter_yields_addresses_and_results() {
+        let rom = [0x60, 0x05, 0x5A, 0xB1, 0x12, 0x00, 0xAB];
+        let mut iter = OpCodeIter::new(&rom, 0x200);
+        assert_eq!(iter.trailing_byte(), Some(0xAB));
+        assert_eq!(iter.size_hint(), (3, Some(3)));
+        assert_eq!(
+            iter.by_ref().collect::<Vec<_>>(),
+            vec![
+                (0x200, Ok(OpCodes::_6XNN { x: 0, nn: 0x05 })),
+                (0x202, Err(Chip8Error::InvalidOpcodeError(0x5AB1))),
+                (0x204, Ok(OpCodes::_1NNN { nnn: 0x200 })),
+            ]
+        );
+        assert_eq!(iter.next(), None);
+    }
+
+    #[test]
+    fn opcode_iter_even_length_has_no_trailing_byte() {
+        let iter = OpCodeIter::new(&[0x00, 0xE0], 0x300);
+        assert_eq!(iter.trailing_byte(), None);
+        assert_eq!(iter.collect::<Vec<_>>(), vec![(0x300, Ok(OpCodes::_00E0))]);
+        assert_eq!(OpCodeIter::new(&[0xFF], 0x200).count(), 0);
+    }
+
+    #[test]
+    fn encode_round_trips_every_opcode() {
+        for opcode in representative_opcodes() {
+            let encoded: (u8, u8) = opcode.into();
+            assert_eq!(OpCodes::try_from(encoded), Ok(opcode), "{:02X?}", encoded);
+        }
+    }
+
+    #[test]
+    fn clear_and_return_encode_to_spec() {
+        assert_eq!(<(u8, u8)>::from(OpCodes::_00E0), (0x00, 0xE0));
+        assert_eq!(<(u8, u8)>::from(OpCodes::_00EE), (0x00, 0xEE));
+        assert_eq!(<(u8, u8)>::from(OpCodes::_00FD), (0x00, 0xFD));
+        assert_eq!(OpCodes::try_from((0x00, 0xFD)), Ok(OpCodes::_00FD));
+    }
+
+    #[test]
+    fn decode_encode_is_stable_for_every_word() {
+        for word in 0..=u16::MAX {
+            let [op1, op2] = word.to_be_bytes();
+            let Ok(opcode) = OpCodes::try_from((op1, op2)) else {
+                continue;
+            };
+            // Every word that decodes encodes back to itself
+            assert_eq!(<(u8, u8)>::from(opcode), (op1, op2), "0x{:04X}", word);
+        }
+    }
+
+    #[test]
+    fn convert_rejects_a_trailing_byte() {
+        assert_eq!(
+            convert_u8_into_opcodes(&[0x60, 0x2A, 0x70]),
+            Err(Chip8Error::OddLengthRomError { len: 3 })
+        );
+        assert_eq!(
+            convert_u8_into_opcodes(&[0x60, 0x2A]),
+            Ok(vec![OpCodes::_6XNN { x: 0, nn: 0x2A }])
+        );
+        assert_eq!(convert_u8_into_opcodes(&[]), Ok(vec![]));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn opcodes_and_errors_round_trip_through_json() {
+        let opcode = OpCodes::_6XNN { x: 3, nn: 0x42 };
+        let json = serde_json::to_string(&opcode).unwrap();
+        assert_eq!(json, r#"{"_6XNN":{"x":3,"nn":66}}"#);
+        assert_eq!(serde_json::from_str::<OpCodes>(&json).unwrap(), opcode);
+
+        let err = Chip8Error::UnknownOpcodeError(opcode);
+        let json = serde_json::to_string(&err).unwrap();
+        assert_eq!(serde_json::from_str::<Chip8Error>(&json).unwrap(), err);
+    }
+}
@@ -0,0 +1,69 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rgb {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Rgb {
+    pub const fn new(r: u8, g: u8, b: u8) -> Rgb {
+        return Rgb { r, g, b };
+    }
+}
+
+// Ordered list of colors, index 0 is "pixel off" and index 1 is "pixel on".
+// Extended modes (XO-CHIP planes) can use the indexes after that.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Palette {
+    colors: Vec<Rgb>,
+}
+
+impl Palette {
+    pub fn new(colors: Vec<Rgb>) -> Palette {
+        assert!(
+            colors.len() >= 2,
+            "A palette needs at least an off and an on color"
+        );
+        return Palette { colors };
+    }
+
+    pub fn monochrome(off: Rgb, on: Rgb) -> Palette {
+        return Palette::new(vec![off, on]);
+    }
+
+    pub fn classic() -> Palette {
+        return Palette::monochrome(Rgb::new(0x00, 0x00, 0x00), Rgb::new(0xFF, 0xFF, 0xFF));
+    }
+
+    pub fn green_phosphor() -> Palette {
+        return Palette::monochrome(Rgb::new(0x0A, 0x1A, 0x0A), Rgb::new(0x33, 0xFF, 0x33));
+    }
+
+    pub fn amber() -> Palette {
+        return Palette::monochrome(Rgb::new(0x1A, 0x10, 0x00), Rgb::new(0xFF, 0xB0, 0x00));
+    }
+
+    pub fn colors(&self) -> &[Rgb] {
+        return &self.colors;
+    }
+
+    pub fn off(&self) -> Rgb {
+        return self.colors[0];
+    }
+
+    pub fn on(&self) -> Rgb {
+        return self.colors[1];
+    }
+
+    // Indexes past the end of the palette fall back to the last color
+    pub fn color(&self, index: u8) -> Rgb {
+        let index = usize::from(index).min(self.colors.len() - 1);
+        return self.colors[index];
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Palette::classic()
+    }
+}
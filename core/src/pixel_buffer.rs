@@ -0,0 +1,477 @@
+pub const SCREEN_WIDTH: u8 = 64;
+pub const SCREEN_HEIGHT: u8 = 32;
+// 1 bit so 64 * 32 / 8 (1 byte = 8 pixels horizontally)
+pub const SCREEN_BUFFER_SIZE_FULL: usize = (SCREEN_WIDTH as usize) * (SCREEN_HEIGHT as usize);
+pub const SCREEN_BUFFER_SIZE_COMPRESSED: usize = SCREEN_BUFFER_SIZE_FULL / 8;
+
+const ROW_BYTES: usize = SCREEN_WIDTH as usize / 8;
+const ROWS: usize = SCREEN_HEIGHT as usize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PixelChange {
+    pub x: u8,
+    pub y: u8,
+    pub on: bool,
+}
+
+// 1 bit per pixel screen contents, the screen is exactly 64 pixels wide so each row is a u64 with
+// the leftmost pixel in the high bit. This owns all of the XOR / collision / wrapping rules so
+// frontends don't have to re-derive them.
+#[derive(Clone, PartialEq, Eq)]
+pub struct PixelBuffer {
+    rows: [u64; ROWS],
+}
+
+impl Default for PixelBuffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PixelBuffer {
+    pub fn new() -> PixelBuffer {
+        return PixelBuffer { rows: [0; ROWS] };
+    }
+
+    // Same layout as as_bytes
+    pub fn from_bytes(data: [u8; SCREEN_BUFFER_SIZE_COMPRESSED]) -> PixelBuffer {
+        let mut buffer = PixelBuffer::new();
+        for (row, bytes) in buffer.rows.iter_mut().zip(data.chunks_exact(ROW_BYTES)) {
+            *row = u64::from_be_bytes(bytes.try_into().unwrap());
+        }
+        return buffer;
+    }
+
+    // Packed 8 bytes a row, the leftmost pixel in the high bit, for save states
+    pub fn as_bytes(&self) -> [u8; SCREEN_BUFFER_SIZE_COMPRESSED] {
+        let mut data = [0; SCREEN_BUFFER_SIZE_COMPRESSED];
+        for (bytes, row) in data.chunks_exact_mut(ROW_BYTES).zip(self.rows) {
+            bytes.copy_from_slice(&row.to_be_bytes());
+        }
+        return data;
+    }
+
+    // Top row first, the leftmost pixel in bit 63
+    pub fn rows(&self) -> &[u64; ROWS] {
+        return &self.rows;
+    }
+
+    pub fn get_pixel(&self, x: u8, y: u8) -> bool {
+        let (row, mask) = Self::locate(x, y);
+        return self.rows[row] & mask != 0;
+    }
+
+    pub fn set_pixel(&mut self, x: u8, y: u8, on: bool) {
+        let (row, mask) = Self::locate(x, y);
+        if on {
+            self.rows[row] |= mask;
+        } else {
+            self.rows[row] &= !mask;
+        }
+    }
+
+    fn locate(x: u8, y: u8) -> (usize, u64) {
+        let x = x % SCREEN_WIDTH;
+        let y = usize::from(y % SCREEN_HEIGHT);
+        return (y, 1 << 63 >> x);
+    }
+
+    // The starting position wraps around the screen. Past that the buffer is addressed as one run
+    // of pixels like the original per-pixel loop did: a row that runs off the right edge carries
+    // on at the left of the next screen row, and running off the bottom stops the draw and
+    // reports no collision, even for pixels already turned off.
+    // Each sprite row is shifted into place and XORed into its screen row in one go, plus the
+    // bits that carried over into the next row when x is past 56.
+    pub fn draw_sprite(&mut self, x: u8, y: u8, sprite: &[u8]) -> bool {
+        let x = u32::from(x % SCREEN_WIDTH);
+        let y = usize::from(y % SCREEN_HEIGHT);
+        let mut was_unset = false;
+        for (row, sprite_row) in sprite.iter().enumerate() {
+            let bits = u64::from(*sprite_row) << 56;
+            let py = y + row;
+            if py >= ROWS {
+                return false;
+            }
+            was_unset |= self.xor_row(py, bits >> x);
+            if x > 56 {
+                if py + 1 >= ROWS {
+                    return false;
+                }
+                was_unset |= self.xor_row(py + 1, bits << (64 - x));
+            }
+        }
+        return was_unset;
+    }
+
+    // True if any of the bits was already set
+    fn xor_row(&mut self, row: usize, bits: u64) -> bool {
+        let was_set = self.rows[row] & bits != 0;
+        self.rows[row] ^= bits;
+        return was_set;
+    }
+
+    // A 16 pixel wide sprite, two bytes per row with the left half first. Drawn as two 8 pixel
+    // wide halves with draw_sprite, the same as Chip8Screen's default, so the right half is left
+    // out when it would start past the right edge.
+    pub fn draw_wide_sprite(&mut self, x: u8, y: u8, sprite: &[u8]) -> bool {
+        let x = x % SCREEN_WIDTH;
+        let left = sprite.iter().step_by(2).copied().collect::<Vec<_>>();
+        let right = sprite
+            .iter()
+            .skip(1)
+            .step_by(2)
+            .copied()
+            .collect::<Vec<_>>();
+        let mut was_unset = self.draw_sprite(x, y, &left);
+        if x + 8 < SCREEN_WIDTH {
+            was_unset |= self.draw_sprite(x + 8, y, &right);
+        }
+        return was_unset;
+    }
+
+    pub fn draw_sprite_tracked(
+        &mut self,
+        x: u8,
+        y: u8,
+        sprite: &[u8],
+        changes: &mut Vec<PixelChange>,
+    ) -> bool {
+        return self.draw_sprite_with(x, y, sprite, |change| changes.push(change));
+    }
+
+    // draw_sprite one pixel at a time, so each change can be reported
+    fn draw_sprite_with(
+        &mut self,
+        x: u8,
+        y: u8,
+        sprite: &[u8],
+        mut on_change: impl FnMut(PixelChange),
+    ) -> bool {
+        let x = usize::from(x % SCREEN_WIDTH);
+        let y = usize::from(y % SCREEN_HEIGHT);
+        let width = SCREEN_WIDTH as usize;
+        let mut was_unset = false;
+        for (row, sprite_row) in sprite.iter().enumerate() {
+            for bit in 0..8 {
+                let index = (y + row) * width + x + bit;
+                if index >= SCREEN_BUFFER_SIZE_FULL {
+                    return false;
+                }
+                if sprite_row & (0x80 >> bit) == 0 {
+                    continue;
+                }
+                let (px, py) = ((index % width) as u8, (index / width) as u8);
+                let was_on = self.get_pixel(px, py);
+                self.set_pixel(px, py, !was_on);
+                was_unset |= was_on;
+                on_change(PixelChange {
+                    x: px,
+                    y: py,
+                    on: !was_on,
+                });
+            }
+        }
+        return was_unset;
+    }
+
+    pub fn clear(&mut self) {
+        self.fill(false);
+    }
+
+    // Every pixel on or every pixel off
+    pub fn fill(&mut self, set: bool) {
+        self.rows.fill(if set { u64::MAX } else { 0 });
+    }
+
+    pub fn clear_tracked(&mut self, changes: &mut Vec<PixelChange>) {
+        for y in 0..SCREEN_HEIGHT {
+            for x in 0..SCREEN_WIDTH {
+                if self.get_pixel(x, y) {
+                    changes.push(PixelChange { x, y, on: false });
+                }
+            }
+        }
+        self.clear();
+    }
+
+    // Scroll the screen contents down by n rows, the rows scrolled in are blank
+    pub fn scroll_down(&mut self, n: u8) {
+        let shift = usize::from(n.min(SCREEN_HEIGHT));
+        self.rows.copy_within(..ROWS - shift, shift);
+        self.rows[..shift].fill(0);
+    }
+
+    // Scroll the screen contents up by n rows, the rows scrolled in are blank
+    pub fn scroll_up(&mut self, n: u8) {
+        let shift = usize::from(n.min(SCREEN_HEIGHT));
+        self.rows.copy_within(shift.., 0);
+        self.rows[ROWS - shift..].fill(0);
+    }
+
+    // Scroll every row left by n pixels, the columns scrolled in are blank
+    pub fn scroll_left(&mut self, n: u8) {
+        for row in self.rows.iter_mut() {
+            *row = row.checked_shl(u32::from(n)).unwrap_or(0);
+        }
+    }
+
+    // Scroll every row right by n pixels, the columns scrolled in are blank
+    pub fn scroll_right(&mut self, n: u8) {
+        for row in self.rows.iter_mut() {
+            *row = row.checked_shr(u32::from(n)).unwrap_or(0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+
+    fn lit_pixels(buffer: &PixelBuffer) -> Vec<(u8, u8)> {
+        let mut lit = vec![];
+        for y in 0..SCREEN_HEIGHT {
+            for x in 0..SCREEN_WIDTH {
+                if buffer.get_pixel(x, y) {
+                    lit.push((x, y));
+                }
+            }
+        }
+        return lit;
+    }
+
+    #[test]
+    fn draw_sets_pixels_msb_first() {
+        let mut buffer = PixelBuffer::new();
+        let collision = buffer.draw_sprite(3, 2, &[0b1000_0001, 0b0100_0000]);
+        assert!(!collision);
+        assert_eq!(lit_pixels(&buffer), vec![(3, 2), (10, 2), (4, 3)]);
+        assert_eq!(buffer.as_bytes()[2 * 8], 0b0001_0000);
+        assert_eq!(buffer.as_bytes()[2 * 8 + 1], 0b0010_0000);
+    }
+
+    #[test]
+    fn draw_xors_and_reports_collision() {
+        let mut buffer = PixelBuffer::new();
+        assert!(!buffer.draw_sprite(0, 0, &[0xF0]));
+        assert!(buffer.draw_sprite(2, 0, &[0xF0]));
+        assert_eq!(lit_pixels(&buffer), vec![(0, 0), (1, 0), (4, 0), (5, 0)]);
+        // Drawing over blank pixels with a partially overlapping sprite doesn't collide
+        assert!(!buffer.draw_sprite(6, 0, &[0xC0]));
+    }
+
+    #[test]
+    fn draw_wraps_start_position() {
+        let mut buffer = PixelBuffer::new();
+        buffer.draw_sprite(64 + 1, 32 + 2, &[0x80]);
+        assert_eq!(lit_pixels(&buffer), vec![(1, 2)]);
+    }
+
+    #[test]
+    fn draw_past_right_edge_wraps_into_next_row() {
+        let mut buffer = PixelBuffer::new();
+        assert!(!buffer.draw_sprite(60, 0, &[0xFF]));
+        assert_eq!(
+            lit_pixels(&buffer),
+            vec![
+                (60, 0),
+                (61, 0),
+                (62, 0),
+                (63, 0),
+                (0, 1),
+                (1, 1),
+                (2, 1),
+                (3, 1)
+            ]
+        );
+        // The carried over pixels collide like any other
+        assert!(buffer.draw_sprite(0, 1, &[0x80]));
+    }
+
+    #[test]
+    fn draw_past_bottom_edge_reports_no_collision() {
+        let mut buffer = PixelBuffer::new();
+        buffer.draw_sprite(0, 30, &[0x80]);
+        // Turns (0, 30) off, then runs out of rows after row 31
+        assert!(!buffer.draw_sprite(0, 30, &[0x80, 0x80, 0x80]));
+        assert_eq!(lit_pixels(&buffer), vec![(0, 31)]);
+        // Fitting on screen it collides as usual
+        assert!(buffer.draw_sprite(0, 30, &[0x00, 0x80]));
+        // On the last row, x past 56 carries over to a row that isn't there
+        assert!(!buffer.draw_sprite(60, 31, &[0x00]));
+        assert!(!buffer.draw_sprite(57, 31, &[0x80]));
+        assert!(buffer.get_pixel(57, 31));
+    }
+
+    // The per-bit loop from the original Screen::draw_sprite, copied unchanged apart from taking
+    // the buffer as an argument and dropping its commented out debug prints, as the reference for
+    // what drawing has to keep doing
+    #[allow(clippy::unnecessary_cast, clippy::assign_op_pattern)]
+    fn original_draw_sprite(
+        buffer: &mut [u8; SCREEN_BUFFER_SIZE_COMPRESSED],
+        x: u8,
+        y: u8,
+        sprite: &[u8],
+    ) -> bool {
+        let x = x % SCREEN_WIDTH;
+        let y: u16 = (y as u16 % SCREEN_HEIGHT as u16) * SCREEN_WIDTH as u16;
+        let mut was_unset = false;
+        for row in 0..sprite.len() {
+            for bit in 0..8 {
+                let row_offset = (row as usize * SCREEN_WIDTH as usize) as usize;
+                let index = (usize::from(y) + row_offset + usize::from(x + bit)) / 8;
+                let bit_offset = usize::from(x + bit) % 8;
+                let mask = 1 << (7 - bit_offset);
+
+                if index >= buffer.len() {
+                    return false;
+                }
+                let val_before = buffer[index] & mask != 0;
+                let sprite_mask = 1 << (7 - bit);
+                let sprite_val = (sprite[row as usize] & sprite_mask) >> (7 - bit);
+                let sprite_adjusted = sprite_val << (7 - bit_offset);
+                buffer[index] ^= mask & sprite_adjusted;
+                let val_after = buffer[index] & mask != 0;
+                was_unset = was_unset | (val_before && !val_after);
+            }
+        }
+        return was_unset;
+    }
+
+    #[test]
+    fn draw_matches_original_routine() {
+        let mut rng = StdRng::seed_from_u64(0xC8);
+        let mut original = [0; SCREEN_BUFFER_SIZE_COMPRESSED];
+        let mut bytewise = PixelBuffer::new();
+        let mut per_pixel = PixelBuffer::new();
+        for _ in 0..5000 {
+            let x = rng.gen();
+            let y = rng.gen();
+            let sprite = (0..rng.gen_range(1..=15))
+                .map(|_| rng.gen())
+                .collect::<Vec<u8>>();
+            let expected = original_draw_sprite(&mut original, x, y, &sprite);
+            let collision = bytewise.draw_sprite(x, y, &sprite);
+            let tracked = per_pixel.draw_sprite_tracked(x, y, &sprite, &mut vec![]);
+            let context = format!("x: {} y: {} sprite: {:02X?}", x, y, sprite);
+            assert_eq!(collision, expected, "{}", context);
+            assert_eq!(tracked, expected, "{}", context);
+            assert!(bytewise.as_bytes() == original, "{}", context);
+            assert!(per_pixel.as_bytes() == original, "{}", context);
+            // Clear now and then so the screen doesn't fill up with noise
+            if rng.gen_ratio(1, 50) {
+                original.fill(0);
+                bytewise.clear();
+                per_pixel.clear();
+            }
+        }
+    }
+
+    #[test]
+    fn wide_sprites_are_two_halves_side_by_side() {
+        let mut rng = StdRng::seed_from_u64(0x16);
+        let mut wide = PixelBuffer::new();
+        let mut halves = PixelBuffer::new();
+        for _ in 0..2000 {
+            let x: u8 = rng.gen();
+            let y = rng.gen();
+            let sprite = (0..32).map(|_| rng.gen()).collect::<Vec<u8>>();
+            let collision = wide.draw_wide_sprite(x, y, &sprite);
+            let left = sprite.iter().step_by(2).copied().collect::<Vec<_>>();
+            let right = sprite
+                .iter()
+                .skip(1)
+                .step_by(2)
+                .copied()
+                .collect::<Vec<_>>();
+            let x = x % SCREEN_WIDTH;
+            let mut expected = halves.draw_sprite(x, y, &left);
+            if x + 8 < SCREEN_WIDTH {
+                expected |= halves.draw_sprite(x + 8, y, &right);
+            }
+            assert_eq!(collision, expected, "x: {} y: {}", x, y);
+            assert!(wide == halves, "x: {} y: {}", x, y);
+            if rng.gen_ratio(1, 50) {
+                wide.clear();
+                halves.clear();
+            }
+        }
+        // The left half carries over into the next row, the right half would start past the edge
+        let mut buffer = PixelBuffer::new();
+        buffer.draw_wide_sprite(60, 0, &[0xFF, 0xFF]);
+        assert_eq!(
+            lit_pixels(&buffer),
+            vec![
+                (60, 0),
+                (61, 0),
+                (62, 0),
+                (63, 0),
+                (0, 1),
+                (1, 1),
+                (2, 1),
+                (3, 1)
+            ]
+        );
+    }
+
+    #[test]
+    fn draw_tracked_reports_changes() {
+        let mut buffer = PixelBuffer::new();
+        buffer.draw_sprite(0, 0, &[0x80]);
+        let mut changes = vec![];
+        buffer.draw_sprite_tracked(0, 0, &[0xC0], &mut changes);
+        assert_eq!(
+            changes,
+            vec![
+                PixelChange {
+                    x: 0,
+                    y: 0,
+                    on: false
+                },
+                PixelChange {
+                    x: 1,
+                    y: 0,
+                    on: true
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn clear_blanks_buffer() {
+        let mut buffer = PixelBuffer::new();
+        buffer.draw_sprite(10, 10, &[0xAA]);
+        let mut changes = vec![];
+        buffer.clear_tracked(&mut changes);
+        assert_eq!(changes.len(), 4);
+        assert!(changes.iter().all(|change| !change.on));
+        assert!(lit_pixels(&buffer).is_empty());
+    }
+
+    #[test]
+    fn scroll_vertically() {
+        let mut buffer = PixelBuffer::new();
+        buffer.set_pixel(5, 0, true);
+        buffer.set_pixel(5, 31, true);
+        buffer.scroll_down(4);
+        assert_eq!(lit_pixels(&buffer), vec![(5, 4)]);
+        buffer.scroll_up(3);
+        assert_eq!(lit_pixels(&buffer), vec![(5, 1)]);
+        buffer.scroll_down(40);
+        assert!(lit_pixels(&buffer).is_empty());
+    }
+
+    #[test]
+    fn scroll_horizontally() {
+        let mut buffer = PixelBuffer::new();
+        buffer.set_pixel(6, 3, true);
+        buffer.set_pixel(62, 3, true);
+        buffer.scroll_right(4);
+        assert_eq!(lit_pixels(&buffer), vec![(10, 3)]);
+        buffer.scroll_left(8);
+        assert_eq!(lit_pixels(&buffer), vec![(2, 3)]);
+        buffer.scroll_left(64);
+        assert!(lit_pixels(&buffer).is_empty());
+    }
+}
@@ -0,0 +1,180 @@
+use std::collections::HashMap;
+
+use crate::{OpCodes, PGRM_LOAD_START_ADDR};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LabelId(usize);
+
+// Builds ROMs from Rust for tests and examples, jump / call targets are back-patched in finish()
+// so labels can be referenced before they're bound.
+pub struct ProgramBuilder {
+    base_addr: u16,
+    bytes: Vec<u8>,
+    names: HashMap<String, LabelId>,
+    labels: Vec<(String, Option<u16>)>,
+    // Offset of the instruction to patch and the label it points at
+    fixups: Vec<(usize, LabelId)>,
+}
+
+impl Default for ProgramBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ProgramBuilder {
+    pub fn new() -> ProgramBuilder {
+        return ProgramBuilder::with_base_addr(PGRM_LOAD_START_ADDR);
+    }
+
+    pub fn with_base_addr(base_addr: u16) -> ProgramBuilder {
+        return ProgramBuilder {
+            base_addr,
+            bytes: vec![],
+            names: HashMap::new(),
+            labels: vec![],
+            fixups: vec![],
+        };
+    }
+
+    // Address the next instruction or data will be written to
+    pub fn address(&self) -> u16 {
+        return self.base_addr + self.bytes.len() as u16;
+    }
+
+    pub fn push(&mut self, opcode: OpCodes) -> &mut Self {
+        let (op1, op2) = opcode.into();
+        self.bytes.extend_from_slice(&[op1, op2]);
+        return self;
+    }
+
+    pub fn data(&mut self, data: &[u8]) -> &mut Self {
+        self.bytes.extend_from_slice(data);
+        return self;
+    }
+
+    // Returns the label with this name, creating it unbound the first time it's seen
+    pub fn label(&mut self, name: &str) -> LabelId {
+        if let Some(label) = self.names.get(name) {
+            return *label;
+        }
+        let label = LabelId(self.labels.len());
+        self.labels.push((name.to_string(), None));
+        self.names.insert(name.to_string(), label);
+        return label;
+    }
+
+    // Points the label at the current address
+    pub fn bind(&mut self, label: LabelId) -> &mut Self {
+        let address = self.address();
+        let (name, bound) = &mut self.labels[label.0];
+        assert!(bound.is_none(), "Label {} is bound twice", name);
+        bound.replace(address);
+        return self;
+    }
+
+    pub fn jump_to(&mut self, label: LabelId) -> &mut Self {
+        self.fixups.push((self.bytes.len(), label));
+        return self.push(OpCodes::_1NNN { nnn: 0 });
+    }
+
+    pub fn call(&mut self, label: LabelId) -> &mut Self {
+        self.fixups.push((self.bytes.len(), label));
+        return self.push(OpCodes::_2NNN { nnn: 0 });
+    }
+
+    // Panics if a referenced label was never bound, these programs are written by hand so that's a bug
+    pub fn finish(&self) -> Vec<u8> {
+        let mut bytes = self.bytes.clone();
+        for (offset, label) in self.fixups.iter() {
+            let (name, address) = &self.labels[label.0];
+            let address = address.unwrap_or_else(|| panic!("Label {} was never bound", name));
+            assert!(address <= 0xFFF, "Label {} is out of range", name);
+            bytes[*offset] |= (address >> 8) as u8;
+            bytes[*offset + 1] = address as u8;
+        }
+        return bytes;
+    }
+}
+
+impl From<&[OpCodes]> for ProgramBuilder {
+    fn from(opcodes: &[OpCodes]) -> Self {
+        let mut builder = ProgramBuilder::new();
+        for opcode in opcodes {
+            builder.push(*opcode);
+        }
+        return builder;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Chip8CPU, TestCPU};
+
+    // Counts V0 up to 5 then stores it at 0x300 and spins
+    fn counting_loop() -> ProgramBuilder {
+        let mut builder = ProgramBuilder::new();
+        let top = builder.label("top");
+        let done = builder.label("done");
+        builder
+            .push(OpCodes::_6XNN { x: 0, nn: 0 })
+            .bind(top)
+            .push(OpCodes::_7XNN { x: 0, nn: 1 })
+            .push(OpCodes::_3XNN { x: 0, nn: 5 })
+            .jump_to(top)
+            .jump_to(done)
+            .data(&[0xAA, 0xBB])
+            .bind(done)
+            .push(OpCodes::_ANNN { nnn: 0x300 })
+            .push(OpCodes::_FX55 { x: 0 });
+        let spin = builder.label("spin");
+        builder.bind(spin).jump_to(spin);
+        return builder;
+    }
+
+    #[test]
+    fn back_patches_forward_and_backward_labels() {
+        assert_eq!(
+            counting_loop().finish(),
+            vec![
+                0x60, 0x00, // 0x200
+                0x70, 0x01, // 0x202 top
+                0x30, 0x05, // 0x204
+                0x12, 0x02, // 0x206 JUMP top
+                0x12, 0x0C, // 0x208 JUMP done
+                0xAA, 0xBB, // 0x20A
+                0xA3, 0x00, // 0x20C done
+                0xF0, 0x55, // 0x20E
+                0x12, 0x10, // 0x210 spin
+            ]
+        );
+    }
+
+    #[test]
+    fn runs_on_cpu() {
+        let mut cpu = TestCPU::default();
+        cpu.load_program(&counting_loop().finish()).unwrap();
+        for _ in 0..50 {
+            cpu.step().unwrap();
+        }
+        assert_eq!(cpu.memory_snapshot(0x300, 1), Ok(vec![5]));
+    }
+
+    #[test]
+    fn from_opcodes_and_labels_are_reused_by_name() {
+        let mut builder = ProgramBuilder::from(&[OpCodes::_00E0][..]);
+        let label = builder.label("end");
+        assert_eq!(builder.label("end"), label);
+        builder.call(label).bind(label).push(OpCodes::_00EE);
+        assert_eq!(builder.finish(), vec![0x00, 0xE0, 0x22, 0x04, 0x00, 0xEE]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Label missing was never bound")]
+    fn unbound_label_panics() {
+        let mut builder = ProgramBuilder::new();
+        let label = builder.label("missing");
+        builder.jump_to(label).finish();
+    }
+}
@@ -0,0 +1,54 @@
+// Behaviour that differs between CHIP-8 interpreters, the default is Chip8Quirks::chip8()
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Chip8Quirks {
+    // FX1E sets VF when I goes past 0xFFF and wraps I to 12 bits (Spacefight 2091 relies on this)
+    pub i_overflow_flag: bool,
+    // CHIP-48 reads BNNN as BXNN and jumps to XNN + VX instead of NNN + V0
+    pub jump_uses_vx: bool,
+    // 8XY1 / 8XY2 / 8XY3 clear VF after the operation (CHIP-48 / SUPER-CHIP)
+    pub logic_resets_vf: bool,
+    // What 0NNN, a call into the host machine's own code, does
+    pub machine_code: MachineCodePolicy,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MachineCodePolicy {
+    // Stop with UnimplementedOpcodeError
+    Error,
+    // Treat it as a no-op, what virtually every interpreter does
+    Ignore,
+    // Pass NNN to the handler set with CPU::set_machine_code_trap, e.g. for test ROM syscalls
+    Trap,
+}
+
+impl Chip8Quirks {
+    // Original CHIP-8 behaviour, every quirk off and machine code calls ignored
+    pub fn chip8() -> Chip8Quirks {
+        return Chip8Quirks {
+            i_overflow_flag: false,
+            jump_uses_vx: false,
+            logic_resets_vf: false,
+            machine_code: MachineCodePolicy::Ignore,
+        };
+    }
+
+    // SUPER-CHIP 1.1, which inherited the CHIP-48 changes
+    pub fn schip() -> Chip8Quirks {
+        return Chip8Quirks {
+            jump_uses_vx: true,
+            logic_resets_vf: true,
+            ..Chip8Quirks::chip8()
+        };
+    }
+
+    // XO-CHIP went back to the original CHIP-8 behaviour for these
+    pub fn xochip() -> Chip8Quirks {
+        return Chip8Quirks::chip8();
+    }
+}
+
+impl Default for Chip8Quirks {
+    fn default() -> Self {
+        Chip8Quirks::chip8()
+    }
+}
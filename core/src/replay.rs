@@ -0,0 +1,315 @@
+use std::{
+    cell::{Cell, RefCell},
+    collections::VecDeque,
+    rc::Rc,
+};
+
+use crate::{Chip8CPU, Chip8Error, Chip8Input, Chip8Screen, StepResult, CPU};
+
+// Set on the key value of a recorded event when the key was released rather than pressed
+pub const KEY_RELEASED: u8 = 0x80;
+// Recorded in place of a key value when the timers ticked
+pub const TIMER_TICK: u8 = 0x40;
+
+// Keypad driven by Chip8Replay, either from press/release calls or from a recording
+#[derive(Debug, Default)]
+pub struct ReplayKeypad {
+    held: Cell<[bool; 16]>,
+    released: Cell<Option<u8>>,
+}
+
+impl ReplayKeypad {
+    // A key value as recorded, with KEY_RELEASED set for a release
+    pub fn apply(&self, event: u8) {
+        let key = event & 0xF;
+        let mut held = self.held.get();
+        if event & KEY_RELEASED == 0 {
+            held[usize::from(key)] = true;
+        } else if held[usize::from(key)] {
+            held[usize::from(key)] = false;
+            self.released.set(Some(key));
+        }
+        self.held.set(held);
+    }
+
+    fn clear(&self) {
+        self.held.set([false; 16]);
+        self.released.set(None);
+    }
+}
+
+impl Chip8Input for ReplayKeypad {
+    fn get_key(&self) -> Option<u8> {
+        let held = self.held.get();
+        return (0..16u8).find(|key| held[usize::from(*key)]);
+    }
+
+    fn was_key_released(&self) -> Option<u8> {
+        return self.released.take();
+    }
+
+    fn is_key_held(&self, key: u8) -> bool {
+        return key <= 0xF && self.held.get()[usize::from(key)];
+    }
+}
+
+// Shared with Chip8Replay, which appends to it until the next record or replay
+#[derive(Debug, Clone, Default)]
+pub struct RecordingHandle {
+    events: Rc<RefCell<Vec<(u64, u8)>>>,
+}
+
+impl RecordingHandle {
+    // (step count, key value), the key value has KEY_RELEASED set for releases or is TIMER_TICK
+    pub fn events(&self) -> Vec<(u64, u8)> {
+        return self.events.borrow().clone();
+    }
+}
+
+// Runs a CPU whose only source of randomness is its seed and whose only inputs are the key events
+// and timer ticks given to it, so a recording of those plays back to exactly the same states.
+// Events are stamped with the step count they happened before, FX0A waiting counts as a step.
+pub struct Chip8Replay<'a, TScreen>
+where
+    TScreen: Chip8Screen,
+{
+    cpu: CPU<'a, TScreen, ReplayKeypad>,
+    screen: &'a TScreen,
+    keypad: &'a ReplayKeypad,
+    seed: u64,
+    recording: Option<RecordingHandle>,
+    pending: VecDeque<(u64, u8)>,
+    // The timers tick when the recording says so, not when tick_timers is called
+    replaying: bool,
+}
+
+impl<'a, TScreen> Chip8Replay<'a, TScreen>
+where
+    TScreen: Chip8Screen,
+{
+    pub fn new(screen: &'a TScreen, keypad: &'a ReplayKeypad, seed: u64) -> Self {
+        return Chip8Replay {
+            cpu: CPU::new_seeded(screen, keypad, seed),
+            screen,
+            keypad,
+            seed,
+            recording: None,
+            pending: VecDeque::new(),
+            replaying: false,
+        };
+    }
+
+    pub fn cpu(&self) -> &CPU<'a, TScreen, ReplayKeypad> {
+        return &self.cpu;
+    }
+
+    // Starts the ROM from scratch with a freshly seeded CPU. Quirks carry over, hooks and patches
+    // don't since a replay couldn't reproduce them.
+    pub fn load_rom(&mut self, rom: &[u8]) -> Result<(), Chip8Error> {
+        let quirks = self.cpu.quirks();
+        self.cpu = CPU::new_seeded(self.screen, self.keypad, self.seed);
+        self.cpu.set_quirks(quirks);
+        self.screen.clear();
+        self.keypad.clear();
+        self.pending.clear();
+        self.replaying = false;
+        return self.cpu.load_program(rom);
+    }
+
+    // Records every press, release and timer tick from now on, call it right after load_rom so the
+    // recording replays from the start of the ROM
+    pub fn record(&mut self) -> RecordingHandle {
+        self.replaying = false;
+        let handle = RecordingHandle::default();
+        self.recording = Some(handle.clone());
+        return handle;
+    }
+
+    pub fn press(&mut self, key: u8) {
+        self.key_event(key & 0xF);
+    }
+
+    pub fn release(&mut self, key: u8) {
+        self.key_event(key & 0xF | KEY_RELEASED);
+    }
+
+    fn key_event(&mut self, event: u8) {
+        self.keypad.apply(event);
+        self.record_event(event);
+    }
+
+    fn record_event(&self, event: u8) {
+        if let Some(recording) = &self.recording {
+            recording
+                .events
+                .borrow_mut()
+                .push((self.cpu.step_count(), event));
+        }
+    }
+
+    // Restarts the ROM and queues the recorded events, step then feeds each one in at its step
+    pub fn replay(&mut self, recording: &[(u64, u8)], rom: &[u8]) -> Result<(), Chip8Error> {
+        self.recording = None;
+        self.load_rom(rom)?;
+        self.pending = recording.iter().copied().collect();
+        self.replaying = true;
+        return Ok(());
+    }
+
+    pub fn step(&mut self) -> Result<StepResult, Chip8Error> {
+        while let Some((_, event)) = self
+            .pending
+            .front()
+            .filter(|(step, _)| *step <= self.cpu.step_count())
+        {
+            if *event == TIMER_TICK {
+                self.cpu.tick_timers();
+            } else {
+                self.keypad.apply(*event);
+            }
+            self.pending.pop_front();
+        }
+        return self.cpu.step();
+    }
+
+    // Does nothing during a replay, the recorded ticks are played back instead
+    pub fn tick_timers(&mut self) {
+        if self.replaying {
+            return;
+        }
+        self.cpu.tick_timers();
+        self.record_event(TIMER_TICK);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CpuState, NoopScreen, OpCodes, ProgramBuilder};
+
+    #[test]
+    fn replay_matches_the_recorded_session() {
+        let mut builder = ProgramBuilder::new();
+        let top = builder.label("top");
+        builder
+            .push(OpCodes::_FX0A { x: 0x0 })
+            .push(OpCodes::_CXNN { x: 0x2, nn: 0xFF })
+            .push(OpCodes::_FX0A { x: 0x1 })
+            .push(OpCodes::_CXNN { x: 0x3, nn: 0xFF })
+            .bind(top)
+            .push(OpCodes::_EX9E { x: 0x1 })
+            .push(OpCodes::_7XNN { x: 0x4, nn: 0x01 })
+            .jump_to(top);
+        let rom = builder.finish();
+
+        let keypad = ReplayKeypad::default();
+        let mut replay = Chip8Replay::new(&NoopScreen, &keypad, 1234);
+        replay.load_rom(&rom).unwrap();
+        let recording = replay.record();
+
+        let mut checkpoints: Vec<CpuState> = vec![];
+        let mut run = |replay: &mut Chip8Replay<NoopScreen>, steps: usize| {
+            for _ in 0..steps {
+                replay.step().unwrap();
+                checkpoints.push(replay.cpu().state_with_memory());
+            }
+        };
+        run(&mut replay, 5);
+        replay.press(0x3);
+        run(&mut replay, 2);
+        replay.release(0x3);
+        run(&mut replay, 4);
+        // FX0A keeps waiting while the key is held, it only finishes on the release
+        replay.press(0xA);
+        run(&mut replay, 3);
+        replay.release(0xA);
+        run(&mut replay, 10);
+
+        let state = replay.cpu().state();
+        assert_eq!((state.v[0], state.v[1]), (0x3, 0xA));
+        assert_eq!(
+            recording.events(),
+            [
+                (5, 0x3),
+                (7, 0x3 | KEY_RELEASED),
+                (11, 0xA),
+                (14, 0xA | KEY_RELEASED)
+            ]
+        );
+
+        // A new keypad and CPU, only the seed and the events are shared
+        let keypad = ReplayKeypad::default();
+        let mut replay = Chip8Replay::new(&NoopScreen, &keypad, 1234);
+        replay.replay(&recording.events(), &rom).unwrap();
+        for (step, expected) in checkpoints.iter().enumerate() {
+            replay.step().unwrap();
+            assert_eq!(&replay.cpu().state_with_memory(), expected, "step {}", step);
+        }
+    }
+
+    #[test]
+    fn replay_restarts_the_rom() {
+        let rom = ProgramBuilder::from(&[OpCodes::_FX0A { x: 0x5 }][..]).finish();
+        let keypad = ReplayKeypad::default();
+        let mut replay = Chip8Replay::new(&NoopScreen, &keypad, 0);
+        replay.load_rom(&rom).unwrap();
+        replay.press(0x9);
+        replay.release(0x9);
+        replay.step().unwrap();
+        assert_eq!(replay.cpu().state().v[5], 0x9);
+
+        // Nothing recorded, the release above doesn't leak into the replay
+        replay.replay(&[], &rom).unwrap();
+        assert_eq!(replay.cpu().step_count(), 0);
+        replay.step().unwrap();
+        assert_eq!(replay.cpu().state().pc, 0x200);
+        assert_eq!(replay.cpu().state().v[5], 0);
+    }
+
+    #[test]
+    fn replay_matches_recorded_timer_ticks() {
+        let mut builder = ProgramBuilder::new();
+        let wait = builder.label("wait");
+        let done = builder.label("done");
+        // DT = 3, count the loops until it runs out in V2
+        builder
+            .push(OpCodes::_6XNN { x: 0x0, nn: 3 })
+            .push(OpCodes::_FX15 { x: 0x0 })
+            .bind(wait)
+            .push(OpCodes::_FX07 { x: 0x1 })
+            .push(OpCodes::_7XNN { x: 0x2, nn: 0x01 })
+            .push(OpCodes::_3XNN { x: 0x1, nn: 0x00 })
+            .jump_to(wait)
+            .bind(done)
+            .jump_to(done);
+        let rom = builder.finish();
+
+        let keypad = ReplayKeypad::default();
+        let mut replay = Chip8Replay::new(&NoopScreen, &keypad, 0);
+        replay.load_rom(&rom).unwrap();
+        let recording = replay.record();
+        let mut checkpoints = vec![];
+        // Ticks at uneven step counts, a replay that ticked on its own schedule would differ
+        for steps in [3, 7, 2, 9, 4] {
+            for _ in 0..steps {
+                replay.step().unwrap();
+                checkpoints.push(replay.cpu().state());
+            }
+            replay.tick_timers();
+        }
+        assert_eq!(replay.cpu().state().delay_timer, 0);
+        let ticks = recording.events();
+        assert_eq!(ticks[..2], [(3, TIMER_TICK), (10, TIMER_TICK)]);
+        assert_eq!(ticks.len(), 5);
+
+        let keypad = ReplayKeypad::default();
+        let mut replay = Chip8Replay::new(&NoopScreen, &keypad, 0);
+        replay.replay(&recording.events(), &rom).unwrap();
+        for (step, expected) in checkpoints.iter().enumerate() {
+            // Ignored, the recording decides when the timers tick
+            replay.tick_timers();
+            replay.step().unwrap();
+            assert_eq!(&replay.cpu().state(), expected, "step {}", step);
+        }
+    }
+}
@@ -0,0 +1,243 @@
+use thiserror::Error;
+
+use crate::{OpCodeIter, PGRM_LOAD_START_ADDR};
+
+// Programs are loaded at 0x200 so that's all the space a standard 4K interpreter has for them
+pub const MAX_ROM_SIZE: usize = 0x1000 - PGRM_LOAD_START_ADDR as usize;
+// XO-CHIP extends memory to 64K
+pub const MAX_EXTENDED_ROM_SIZE: usize = 0x10000 - PGRM_LOAD_START_ADDR as usize;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum RomError {
+    #[error("ROM is empty")]
+    Empty,
+    #[error("ROM too large: {size} bytes but at most {max} bytes are allowed")]
+    TooLarge { size: usize, max: usize },
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RomOptions {
+    pub extended_memory: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Platform {
+    Chip8,
+    SuperChip,
+    XoChip,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rom {
+    bytes: Vec<u8>,
+    checksum: u32,
+    instruction_words: usize,
+    data_words: usize,
+    super_chip: bool,
+    xo_chip: bool,
+}
+
+impl Rom {
+    pub fn parse(bytes: &[u8]) -> Result<Rom, RomError> {
+        return Rom::parse_with(bytes, RomOptions::default());
+    }
+
+    pub fn parse_with(bytes: &[u8], options: RomOptions) -> Result<Rom, RomError> {
+        let max = if options.extended_memory {
+            MAX_EXTENDED_ROM_SIZE
+        } else {
+            MAX_ROM_SIZE
+        };
+        if bytes.is_empty() {
+            return Err(RomError::Empty);
+        }
+        if bytes.len() > max {
+            return Err(RomError::TooLarge {
+                size: bytes.len(),
+                max,
+            });
+        }
+
+        let mut instruction_words = 0;
+        let mut data_words = 0;
+        let mut super_chip = false;
+        let mut xo_chip = false;
+        for (address, opcode) in OpCodeIter::new(bytes, PGRM_LOAD_START_ADDR) {
+            match opcode {
+                Ok(_) => instruction_words += 1,
+                Err(_) => data_words += 1,
+            }
+            let offset = usize::from(address - PGRM_LOAD_START_ADDR);
+            let word = u16::from_be_bytes([bytes[offset], bytes[offset + 1]]);
+            super_chip |= is_super_chip(word);
+            xo_chip |= is_xo_chip(word);
+        }
+
+        return Ok(Rom {
+            bytes: bytes.to_vec(),
+            checksum: crc32(bytes),
+            instruction_words,
+            data_words,
+            super_chip,
+            xo_chip,
+        });
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        return &self.bytes;
+    }
+
+    pub fn len(&self) -> usize {
+        return self.bytes.len();
+    }
+
+    // Always false, parse rejects empty ROMs
+    pub fn is_empty(&self) -> bool {
+        return self.bytes.is_empty();
+    }
+
+    // CRC-32 (IEEE), the same checksum ROM databases use to identify programs
+    pub fn checksum(&self) -> u32 {
+        return self.checksum;
+    }
+
+    // Words that decode as CHIP-8 instructions, sprite data often does too so this is an upper bound
+    pub fn instruction_words(&self) -> usize {
+        return self.instruction_words;
+    }
+
+    pub fn data_words(&self) -> usize {
+        return self.data_words;
+    }
+
+    pub fn trailing_byte(&self) -> Option<u8> {
+        return OpCodeIter::new(&self.bytes, PGRM_LOAD_START_ADDR).trailing_byte();
+    }
+
+    pub fn uses_super_chip(&self) -> bool {
+        return self.super_chip;
+    }
+
+    pub fn uses_xo_chip(&self) -> bool {
+        return self.xo_chip;
+    }
+
+    // Best guess at the platform the ROM was written for, XO-CHIP is a superset of SUPER-CHIP
+    pub fn platform(&self) -> Platform {
+        if self.xo_chip {
+            return Platform::XoChip;
+        }
+        if self.super_chip {
+            return Platform::SuperChip;
+        }
+        return Platform::Chip8;
+    }
+}
+
+// Heuristics only: these words are either invalid or meaningless on a plain CHIP-8, but data can
+// contain them by accident.
+fn is_super_chip(word: u16) -> bool {
+    return matches!(
+        word,
+        // Scroll down N, scroll right / left, exit, lores / hires
+        0x00C1..=0x00CF | 0x00FB..=0x00FF
+    ) || matches!(word & 0xF00F, 0xD000) // 16x16 sprite
+        || matches!(word & 0xF0FF, 0xF030 | 0xF075 | 0xF085); // Big font, RPL flags
+}
+
+fn is_xo_chip(word: u16) -> bool {
+    return matches!(word, 0x00D1..=0x00DF | 0xF000 | 0xF002) // Scroll up N, long I, audio
+        || matches!(word & 0xF00F, 0x5002 | 0x5003) // Save / load register range
+        || matches!(word & 0xF0FF, 0xF001 | 0xF03A); // Plane select, pitch
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in bytes {
+        crc ^= u32::from(*byte);
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    return !crc;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_empty_and_oversized() {
+        assert_eq!(Rom::parse(&[]), Err(RomError::Empty));
+        assert!(Rom::parse(&vec![0xA2; MAX_ROM_SIZE]).is_ok());
+        assert_eq!(
+            Rom::parse(&vec![0xA2; MAX_ROM_SIZE + 1]),
+            Err(RomError::TooLarge {
+                size: MAX_ROM_SIZE + 1,
+                max: MAX_ROM_SIZE,
+            })
+        );
+        let extended = RomOptions {
+            extended_memory: true,
+        };
+        assert!(Rom::parse_with(&vec![0xA2; MAX_ROM_SIZE + 1], extended).is_ok());
+        assert_eq!(
+            Rom::parse_with(&vec![0xA2; MAX_EXTENDED_ROM_SIZE + 1], extended),
+            Err(RomError::TooLarge {
+                size: MAX_EXTENDED_ROM_SIZE + 1,
+                max: MAX_EXTENDED_ROM_SIZE,
+            })
+        );
+    }
+
+    #[test]
+    fn counts_words_and_keeps_trailing_byte() {
+        // LOAD V0 0x05, invalid 5AB1, JUMP 0x200 and a leftover byte
+        let rom = Rom::parse(&[0x60, 0x05, 0x5A, 0xB1, 0x12, 0x00, 0xFF]).unwrap();
+        assert_eq!(rom.len(), 7);
+        assert_eq!(rom.instruction_words(), 2);
+        assert_eq!(rom.data_words(), 1);
+        assert_eq!(rom.trailing_byte(), Some(0xFF));
+        assert_eq!(rom.platform(), Platform::Chip8);
+    }
+
+    #[test]
+    fn checksum_is_crc32() {
+        assert_eq!(Rom::parse(b"123456789").unwrap().checksum(), 0xCBF4_3926);
+        let a = Rom::parse(&[0x00, 0xE0]).unwrap();
+        let b = Rom::parse(&[0x00, 0xEE]).unwrap();
+        assert_ne!(a.checksum(), b.checksum());
+    }
+
+    #[test]
+    fn detects_extensions() {
+        let plain = Rom::parse(&[0x00, 0xE0, 0xD0, 0x15, 0xF0, 0x29]).unwrap();
+        assert_eq!(plain.platform(), Platform::Chip8);
+
+        // HIGH then a 16x16 sprite
+        let schip = Rom::parse(&[0x00, 0xFF, 0xD0, 0x10]).unwrap();
+        assert!(schip.uses_super_chip());
+        assert!(!schip.uses_xo_chip());
+        assert_eq!(schip.platform(), Platform::SuperChip);
+        assert_eq!(
+            Rom::parse(&[0xF3, 0x75]).unwrap().platform(),
+            Platform::SuperChip
+        );
+
+        // Long I load with its address word, then a register range save
+        let xo = Rom::parse(&[0xF0, 0x00, 0x12, 0x34, 0x51, 0x22]).unwrap();
+        assert!(xo.uses_xo_chip());
+        assert_eq!(xo.platform(), Platform::XoChip);
+        assert_eq!(
+            Rom::parse(&[0xF1, 0x01]).unwrap().platform(),
+            Platform::XoChip
+        );
+
+        // Only aligned words are inspected, 0x00FF split across two words doesn't count
+        assert_eq!(
+            Rom::parse(&[0x12, 0x00, 0xFF, 0x00]).unwrap().platform(),
+            Platform::Chip8
+        );
+    }
+}
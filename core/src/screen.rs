@@ -1,93 +1,555 @@
-use std::cell::RefCell;
+use std::{cell::RefCell, io::Write};
 
-const SCREEN_WIDTH: u8 = 64;
-const SCREEN_HEIGHT: u8 = 32;
-// 1 bit so 64 * 32 / 8 (1 byte = 8 pixels horizontally)
-const SCREEN_BUFFER_SIZE_FULL: usize = (SCREEN_WIDTH as usize) * (SCREEN_HEIGHT as usize);
-const SCREEN_BUFFER_SIZE_COMPRESSED: usize = SCREEN_BUFFER_SIZE_FULL / 8;
+use crate::{
+    Palette, PixelBuffer, PixelChange, Rgb, SCREEN_BUFFER_SIZE_FULL, SCREEN_HEIGHT, SCREEN_WIDTH,
+};
 
 pub trait Chip8Screen {
     fn draw_sprite(&self, x: u8, y: u8, sprite: &[u8]) -> bool;
     fn clear(&self);
+
+    // SUPER-CHIP's 16x16 sprites, two bytes per row with the left half first. By default it's
+    // drawn as two 8 pixel wide halves, the right one left out when it would start past the edge.
+    fn draw_wide_sprite(&self, x: u8, y: u8, sprite: &[u8]) -> bool {
+        let x = x % SCREEN_WIDTH;
+        let left = sprite.iter().step_by(2).copied().collect::<Vec<_>>();
+        let right = sprite
+            .iter()
+            .skip(1)
+            .step_by(2)
+            .copied()
+            .collect::<Vec<_>>();
+        let mut was_unset = self.draw_sprite(x, y, &left);
+        if x + 8 < SCREEN_WIDTH {
+            was_unset |= self.draw_sprite(x + 8, y, &right);
+        }
+        return was_unset;
+    }
+}
+
+// Frontends that only want to know which pixels changed implement this and wrap it in a BlitScreen,
+// the XOR / collision / wrapping rules are handled by the PixelBuffer inside.
+pub trait PixelSink {
+    fn blit(&self, changes: &[PixelChange]);
+
+    // Called once after each batch of changes, e.g. to present a canvas
+    fn present(&self) {}
+}
+
+pub struct BlitScreen<TSink: PixelSink> {
+    buffer: RefCell<PixelBuffer>,
+    sink: TSink,
+}
+
+impl<TSink: PixelSink> BlitScreen<TSink> {
+    pub fn new(sink: TSink) -> BlitScreen<TSink> {
+        return BlitScreen {
+            buffer: RefCell::new(PixelBuffer::new()),
+            sink,
+        };
+    }
+
+    pub fn sink(&self) -> &TSink {
+        return &self.sink;
+    }
+
+    pub fn get_pixel(&self, x: u8, y: u8) -> bool {
+        return self.buffer.borrow().get_pixel(x, y);
+    }
+}
+
+impl<TSink: PixelSink> Chip8Screen for BlitScreen<TSink> {
+    fn draw_sprite(&self, x: u8, y: u8, sprite: &[u8]) -> bool {
+        let mut changes = vec![];
+        let was_unset = self
+            .buffer
+            .borrow_mut()
+            .draw_sprite_tracked(x, y, sprite, &mut changes);
+        self.sink.blit(&changes);
+        self.sink.present();
+        return was_unset;
+    }
+
+    fn clear(&self) {
+        let mut changes = vec![];
+        self.buffer.borrow_mut().clear_tracked(&mut changes);
+        self.sink.blit(&changes);
+        self.sink.present();
+    }
 }
 
 pub struct Screen {
-    pub buffer: Box<RefCell<[u8; SCREEN_BUFFER_SIZE_COMPRESSED]>>,
+    pub buffer: RefCell<PixelBuffer>,
     pub pending_draw: RefCell<bool>,
+    palette: RefCell<Palette>,
+    // Bit y is set when pixel row y may differ from what the frontend last drew
+    dirty_rows: RefCell<u32>,
+}
+
+const ALL_ROWS: u32 = u32::MAX;
+
+// Snapshot of the screen as one palette index per pixel, row-major
+pub struct Frame {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>,
+    pub palette: Palette,
+}
+
+impl Frame {
+    pub fn color_at(&self, x: usize, y: usize) -> Rgb {
+        return self.palette.color(self.pixels[y * self.width + x]);
+    }
+
+    // Binary PPM (P6), one RGB triple per pixel
+    pub fn write_ppm<W: Write>(&self, out: &mut W) -> std::io::Result<()> {
+        write!(out, "P6\n{} {}\n255\n", self.width, self.height)?;
+        let mut data = Vec::with_capacity(self.pixels.len() * 3);
+        for index in self.pixels.iter() {
+            let color = self.palette.color(*index);
+            data.extend_from_slice(&[color.r, color.g, color.b]);
+        }
+        out.write_all(&data)
+    }
+}
+
+impl Default for Screen {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Screen {
     pub fn new() -> Screen {
         let screen = Screen {
-            buffer: Box::new(RefCell::new([0; SCREEN_BUFFER_SIZE_COMPRESSED])),
+            buffer: RefCell::new(PixelBuffer::new()),
             pending_draw: RefCell::new(false),
+            palette: RefCell::new(Palette::default()),
+            // Nothing has been drawn yet, the first draw paints every row
+            dirty_rows: RefCell::new(ALL_ROWS),
         };
         return screen;
     }
 
+    pub fn palette(&self) -> Palette {
+        return self.palette.borrow().clone();
+    }
+
+    // Changing the palette changes every pixel on screen, so force a redraw
+    pub fn set_palette(&self, palette: Palette) {
+        self.palette.replace(palette);
+        self.pending_draw.replace(true);
+        self.dirty_rows.replace(ALL_ROWS);
+    }
+
+    pub fn get_pixel(&self, x: u8, y: u8) -> bool {
+        return self.buffer.borrow().get_pixel(x, y);
+    }
+
+    // Row-major like Frame, indexed grid[y][x]
+    pub fn as_bool_grid(&self) -> [[bool; SCREEN_WIDTH as usize]; SCREEN_HEIGHT as usize] {
+        let mut grid = [[false; SCREEN_WIDTH as usize]; SCREEN_HEIGHT as usize];
+        for (row, bits) in grid.iter_mut().zip(self.rows_iter()) {
+            for (x, pixel) in row.iter_mut().enumerate() {
+                *pixel = bits << x & 1 << 63 != 0;
+            }
+        }
+        return grid;
+    }
+
+    // One mask per row, top row first, with the leftmost pixel in bit 63. Lets renderers work on
+    // whole rows with bit operations instead of asking for every pixel.
+    pub fn rows_iter(&self) -> impl Iterator<Item = u64> {
+        // Copied so the iterator doesn't hold the buffer borrowed while the CPU draws
+        let rows = *self.buffer.borrow().rows();
+        return rows.into_iter();
+    }
+
+    pub fn frame(&self) -> Frame {
+        let mut pixels = Vec::with_capacity(SCREEN_BUFFER_SIZE_FULL);
+        for bits in self.rows_iter() {
+            pixels.extend((0..SCREEN_WIDTH).map(|x| (bits << x >> 63) as u8));
+        }
+        return Frame {
+            width: SCREEN_WIDTH as usize,
+            height: SCREEN_HEIGHT as usize,
+            pixels,
+            palette: self.palette(),
+        };
+    }
+
+    pub fn write_ppm<W: Write>(&self, out: &mut W) -> std::io::Result<()> {
+        self.frame().write_ppm(out)
+    }
+
+    // Binary PBM (P4), one bit per pixel with the leftmost pixel in the high bit, which is how
+    // the rows are stored already. Palette colors aren't kept, lit pixels come out black.
+    pub fn to_pbm(&self) -> Vec<u8> {
+        let mut pbm = format!("P4\n{} {}\n", SCREEN_WIDTH, SCREEN_HEIGHT).into_bytes();
+        for bits in self.rows_iter() {
+            pbm.extend_from_slice(&bits.to_be_bytes());
+        }
+        return pbm;
+    }
+
+    // Replaces every pixel, e.g. when loading a save state, so the whole screen needs drawing
+    pub fn set_buffer(&self, buffer: PixelBuffer) {
+        self.buffer.replace(buffer);
+        self.pending_draw.replace(true);
+        self.dirty_rows.replace(ALL_ROWS);
+    }
+
+    // Lights every pixel, e.g. for collision tests. fill(false) is the same as clear().
+    pub fn fill(&self, set: bool) {
+        if !set {
+            self.clear();
+            return;
+        }
+        self.pending_draw.replace(true);
+        self.dirty_rows.replace(ALL_ROWS);
+        self.buffer.borrow_mut().fill(true);
+    }
+
+    // The rows changed since the last call, bit y for pixel row y. Frontends that repaint only
+    // what changed take these when drawing, all rows are dirty before the first take.
+    pub fn take_dirty_rows(&self) -> u32 {
+        return self.dirty_rows.replace(0);
+    }
+
     pub fn mark_drawn(&self) {
         self.pending_draw.replace(false);
     }
 
     pub fn is_pending_draw(&self) -> bool {
-        return self.pending_draw.borrow().clone();
+        return *self.pending_draw.borrow();
+    }
+
+    // A sprite of height rows is about to be drawn at (x, y)
+    fn mark_drawing(&self, x: u8, y: u8, height: usize) {
+        self.pending_draw.replace(true);
+        // Past x = 56 each row carries over into the next one, and the draw stops at the bottom
+        // edge, same as in the buffer
+        let carried = u32::from(x % SCREEN_WIDTH > 56);
+        let top = u32::from(y % SCREEN_HEIGHT);
+        let bottom = (top + height as u32 + carried).min(u32::from(SCREEN_HEIGHT));
+        for row in top..bottom {
+            *self.dirty_rows.borrow_mut() |= 1 << row;
+        }
     }
 
     pub fn draw_as_string(&self) -> String {
         let mut str = String::with_capacity(SCREEN_BUFFER_SIZE_FULL + SCREEN_HEIGHT as usize); // Add extra space for the newline
-        let buffer = self.buffer.borrow();
-        for y in 0_usize..32 {
-            for x in 0_usize..64 {
-                let val = buffer[(y * 64 + x) / 8];
-                let bit = usize::from(x) % 8;
-                let mask = 1 << 7 - bit;
-                let val = val & mask != 0;
-                str.push(if val { '█' } else { ' ' });
+        for bits in self.rows_iter() {
+            for x in 0..SCREEN_WIDTH {
+                str.push(if bits << x & 1 << 63 != 0 { '█' } else { ' ' });
             }
             str.push('\n');
         }
         return str;
     }
+
+    // Like draw_as_string with two rows per line, 64x16 characters
+    pub fn draw_as_string_half_block(&self) -> String {
+        let rows = self.rows_iter().collect::<Vec<_>>();
+        let mut str = String::with_capacity(SCREEN_BUFFER_SIZE_FULL / 2 + rows.len() / 2);
+        for pair in rows.chunks(2) {
+            for x in 0..SCREEN_WIDTH {
+                let lit = |bits: u64| bits << x & 1 << 63 != 0;
+                str.push(match (lit(pair[0]), lit(pair[1])) {
+                    (true, true) => '█',
+                    (true, false) => '▀',
+                    (false, true) => '▄',
+                    (false, false) => ' ',
+                });
+            }
+            str.push('\n');
+        }
+        return str;
+    }
+}
+
+// A fresh clone hasn't been drawn anywhere yet, so it doesn't inherit the pending draw flag
+impl Clone for Screen {
+    fn clone(&self) -> Self {
+        return Screen {
+            buffer: RefCell::new(self.buffer.borrow().clone()),
+            pending_draw: RefCell::new(false),
+            palette: RefCell::new(self.palette()),
+            dirty_rows: RefCell::new(ALL_ROWS),
+        };
+    }
+}
+
+pub struct NoopScreen;
+
+impl Chip8Screen for NoopScreen {
+    fn draw_sprite(&self, _x: u8, _y: u8, _sprite: &[u8]) -> bool {
+        return false;
+    }
+
+    fn clear(&self) {}
 }
+
 impl Chip8Screen for Screen {
-    // Each row is a byte, with each bit representing a pixel, this is the same as the buffer
     fn draw_sprite(&self, x: u8, y: u8, sprite: &[u8]) -> bool {
+        self.mark_drawing(x, y, sprite.len());
+        return self.buffer.borrow_mut().draw_sprite(x, y, sprite);
+    }
+
+    fn clear(&self) {
         self.pending_draw.replace(true);
-        let x = x % SCREEN_WIDTH;
-        let y: u16 = (y as u16 % SCREEN_HEIGHT as u16) * SCREEN_WIDTH as u16;
-        let mut was_unset = false;
-        let mut buffer = self.buffer.borrow_mut();
-        for row in 0..sprite.len() {
-            for bit in 0..8 {
-                let row_offset = (row as usize * SCREEN_WIDTH as usize) as usize;
-                let index = (usize::from(y) + row_offset + usize::from(x + bit)) / 8;
-                let bit_offset = usize::from(x + bit) % 8;
-                let mask = 1 << (7 - bit_offset);
-
-                if index >= buffer.len() {
-                    return false;
-                }
-                let val_before = buffer[index] & mask != 0;
-                let sprite_mask = 1 << (7 - bit);
-                let sprite_val = (sprite[row as usize] & sprite_mask) >> (7 - bit);
-                let sprite_adjusted = sprite_val << (7 - bit_offset);
-                // println!(
-                //     "x: {}, y: {}, row: {}, bit: {}, mask: {}, index: {}, bit_offset: {}, row_offset: {}, sprite_mask: {}, sprite_val: {}, sprite_adjusted: {}",
-                //     x, y, row, bit, mask, index, bit_offset, row_offset, sprite_mask, sprite_val, sprite_adjusted
-                // );
-                // println!(
-                //     "row: {}, bit: {}, mask: {}, sprite_mask: {}, sprite_val: {}",
-                //     row, bit, mask, sprite_mask, sprite_val
-                // );
-                buffer[index] ^= mask & sprite_adjusted;
-                let val_after = buffer[index] & mask != 0;
-                was_unset = was_unset | (val_before && !val_after);
+        self.dirty_rows.replace(ALL_ROWS);
+        self.buffer.borrow_mut().clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{run, CPU};
+
+    fn ppm_bytes(screen: &Screen) -> Vec<u8> {
+        let mut out = vec![];
+        screen.write_ppm(&mut out).unwrap();
+        return out;
+    }
+
+    #[test]
+    fn frame_uses_palette_indexes() {
+        let screen = Screen::new();
+        screen.draw_sprite(0, 0, &[0b1000_0001]);
+        let frame = screen.frame();
+        assert_eq!(frame.pixels.len(), 64 * 32);
+        assert_eq!(frame.pixels[0], 1);
+        assert_eq!(frame.pixels[1], 0);
+        assert_eq!(frame.pixels[7], 1);
+        assert_eq!(frame.color_at(0, 0), Palette::classic().on());
+        assert_eq!(frame.color_at(1, 0), Palette::classic().off());
+    }
+
+    #[test]
+    fn ppm_changes_with_palette() {
+        let screen = Screen::new();
+        screen.draw_sprite(2, 1, &[0xF0, 0x90, 0xF0]);
+        let classic_frame = screen.frame();
+        let classic = ppm_bytes(&screen);
+
+        screen.set_palette(Palette::green_phosphor());
+        let green_frame = screen.frame();
+        let green = ppm_bytes(&screen);
+
+        screen.set_palette(Palette::amber());
+        let amber = ppm_bytes(&screen);
+
+        assert_eq!(classic_frame.pixels, green_frame.pixels);
+        assert_ne!(classic, green);
+        assert_ne!(green, amber);
+
+        let header = b"P6\n64 32\n255\n";
+        assert!(green.starts_with(header));
+        assert_eq!(green.len(), header.len() + 64 * 32 * 3);
+        let on = Palette::green_phosphor().on();
+        let lit = header.len() + (64 + 2) * 3;
+        assert_eq!(&green[lit..lit + 3], &[on.r, on.g, on.b]);
+        let off = Palette::green_phosphor().off();
+        assert_eq!(
+            &green[header.len()..header.len() + 3],
+            &[off.r, off.g, off.b]
+        );
+    }
+
+    #[test]
+    fn bool_grid_is_indexed_by_row() {
+        let screen = Screen::new();
+        screen.draw_sprite(62, 31, &[0b1100_0000]);
+        screen.draw_sprite(3, 0, &[0b1000_0000]);
+        let grid = screen.as_bool_grid();
+        assert!(grid[31][62] && grid[31][63] && grid[0][3]);
+        assert_eq!(grid.iter().flatten().filter(|on| **on).count(), 3);
+    }
+
+    #[test]
+    fn draw_as_string_matches_pixels() {
+        let screen = Screen::new();
+        screen.draw_sprite(1, 0, &[0xA0]);
+        screen.draw_sprite(0, 1, &[0x80]);
+        let drawn = screen.draw_as_string();
+        let lines = drawn.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 32);
+        assert!(lines.iter().all(|line| line.chars().count() == 64));
+        assert!(lines[0].starts_with(" █ █ "));
+        assert!(lines[1].starts_with("█ "));
+        assert!(screen.get_pixel(1, 0));
+        assert!(!screen.get_pixel(2, 0));
+    }
+
+    #[test]
+    fn draw_as_string_half_block_pairs_rows() {
+        let screen = Screen::new();
+        // Rows 0 and 1 make one line, rows 2 and 3 the next
+        screen.draw_sprite(0, 0, &[0xA0, 0xC0, 0x00, 0x40]);
+        screen.draw_sprite(62, 30, &[0x00, 0x80]);
+        let drawn = screen.draw_as_string_half_block();
+        let lines = drawn.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 16);
+        assert!(lines.iter().all(|line| line.chars().count() == 64));
+        assert!(lines[0].starts_with("█▄▀ "));
+        assert!(lines[1].starts_with(" ▄ "));
+        assert!(lines[15].ends_with("▄ "));
+        assert!(lines[2..15].iter().all(|line| line.trim().is_empty()));
+    }
+
+    struct RecordingSink {
+        changes: RefCell<Vec<PixelChange>>,
+        presents: RefCell<usize>,
+    }
+
+    impl PixelSink for RecordingSink {
+        fn blit(&self, changes: &[PixelChange]) {
+            self.changes.borrow_mut().extend_from_slice(changes);
+        }
+
+        fn present(&self) {
+            *self.presents.borrow_mut() += 1;
+        }
+    }
+
+    #[test]
+    fn blit_screen_forwards_changes() {
+        let screen = BlitScreen::new(RecordingSink {
+            changes: RefCell::new(vec![]),
+            presents: RefCell::new(0),
+        });
+        assert!(!screen.draw_sprite(4, 5, &[0xC0]));
+        assert!(screen.draw_sprite(5, 5, &[0x80]));
+        assert!(screen.get_pixel(4, 5));
+        assert!(!screen.get_pixel(5, 5));
+        screen.clear();
+
+        let on = |x, y| PixelChange { x, y, on: true };
+        let off = |x, y| PixelChange { x, y, on: false };
+        assert_eq!(
+            *screen.sink().changes.borrow(),
+            vec![on(4, 5), on(5, 5), off(5, 5), off(4, 5)]
+        );
+        assert_eq!(*screen.sink().presents.borrow(), 3);
+    }
+
+    #[test]
+    fn wide_sprites_draw_the_same_by_default() {
+        // BlitScreen draws pixel by pixel, Screen whole rows
+        let blit = BlitScreen::new(RecordingSink {
+            changes: RefCell::new(vec![]),
+            presents: RefCell::new(0),
+        });
+        let screen = Screen::new();
+        screen.take_dirty_rows();
+        let sprite = (0..32u8).map(|n| n.wrapping_mul(37)).collect::<Vec<_>>();
+        for (x, y) in [(0, 0), (52, 20), (60, 3), (3, 3)] {
+            assert_eq!(
+                blit.draw_wide_sprite(x, y, &sprite),
+                screen.draw_wide_sprite(x, y, &sprite)
+            );
+        }
+        for y in 0..SCREEN_HEIGHT {
+            for x in 0..SCREEN_WIDTH {
+                assert_eq!(
+                    blit.get_pixel(x, y),
+                    screen.get_pixel(x, y),
+                    "({}, {})",
+                    x,
+                    y
+                );
             }
         }
-        return was_unset;
+        // Rows 0-31, the left half at (60, 3) carries over into row 19
+        assert_eq!(screen.take_dirty_rows(), u32::MAX);
     }
 
-    fn clear(&self) {
-        self.buffer.borrow_mut().fill(0);
+    #[test]
+    fn set_palette_requests_redraw() {
+        let screen = Screen::new();
+        assert!(!screen.is_pending_draw());
+        screen.set_palette(Palette::amber());
+        assert!(screen.is_pending_draw());
+        assert_eq!(screen.palette(), Palette::amber());
+    }
+
+    #[test]
+    fn fill_lights_every_pixel() {
+        let screen = Screen::new();
+        screen.fill(true);
+        assert!(screen.rows_iter().all(|row| row == u64::MAX));
+        assert!(screen.is_pending_draw());
+
+        // The 1 glyph's top row, 0x20, is a single pixel at x + 2
+        let mut cpu = CPU::new(&screen, &crate::NoopInput);
+        run!(cpu, _ANNN { nnn: 0x55 }, _DXYN { x: 0, y: 0, n: 1 });
+        assert_eq!(cpu.state().v[0xF], 1);
+        assert!(!screen.get_pixel(2, 0));
+        assert!(screen.get_pixel(1, 0) && screen.get_pixel(3, 0));
+
+        let cleared = Screen::new();
+        cleared.fill(true);
+        cleared.clear();
+        screen.fill(false);
+        assert_eq!(screen.as_bool_grid(), cleared.as_bool_grid());
+        assert!(screen.rows_iter().all(|row| row == 0));
+        assert_eq!(screen.take_dirty_rows(), u32::MAX);
+    }
+
+    #[test]
+    fn rows_iter_packs_rows_into_masks() {
+        let screen = Screen::new();
+        // The 0 glyph, 0xF0 0x90 0x90 0x90 0xF0, at the left edge and again at x = 60
+        let zero = [0xF0, 0x90, 0x90, 0x90, 0xF0];
+        screen.draw_sprite(0, 2, &zero);
+        screen.draw_sprite(60, 2, &zero);
+
+        let rows = screen.rows_iter().collect::<Vec<_>>();
+        assert_eq!(rows.len(), 32);
+        assert_eq!(rows[..2], [0, 0]);
+        // The second 0 ends on the right edge, its blank low nibble carries over to the next row
+        assert_eq!(rows[2], 0xF000_0000_0000_000F);
+        for row in rows[3..6].iter() {
+            assert_eq!(*row, 0x9000_0000_0000_0009);
+            assert_ne!(row & 1 << 63, 0);
+            assert_eq!(row & 1 << 62, 0);
+        }
+        assert_eq!(rows[6], rows[2]);
+        assert!(rows[7..].iter().all(|row| *row == 0));
+    }
+
+    #[test]
+    fn tracks_dirty_rows() {
+        let screen = Screen::new();
+        assert_eq!(screen.take_dirty_rows(), u32::MAX);
+        assert_eq!(screen.take_dirty_rows(), 0);
+
+        screen.draw_sprite(10, 3, &[0xFF, 0x00]);
+        // Wraps to row 30 and stops after row 31
+        screen.draw_sprite(0, 62, &[0x80; 5]);
+        assert_eq!(screen.take_dirty_rows(), 0b11 << 3 | 0b11 << 30);
+        // Carries over into row 8
+        screen.draw_sprite(60, 6, &[0xFF, 0xFF]);
+        assert_eq!(screen.take_dirty_rows(), 0b111 << 6);
+
+        screen.clear();
+        assert_eq!(screen.take_dirty_rows(), u32::MAX);
+        screen.set_buffer(PixelBuffer::new());
+        assert_eq!(screen.take_dirty_rows(), u32::MAX);
+    }
+
+    #[test]
+    fn clone_keeps_contents_independent_of_original() {
+        let screen = Screen::new();
+        screen.draw_sprite(4, 4, &[0xF0]);
+        let snapshot = screen.clone();
+        assert!(!snapshot.is_pending_draw());
+        screen.clear();
+        assert!(snapshot.get_pixel(4, 4));
+        assert!(snapshot.get_pixel(7, 4));
+        assert!(!screen.get_pixel(4, 4));
+        assert_eq!(screen.draw_as_string().trim(), "");
     }
 }
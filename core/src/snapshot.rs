@@ -0,0 +1,234 @@
+use thiserror::Error;
+
+use crate::{PixelBuffer, SCREEN_BUFFER_SIZE_COMPRESSED};
+
+const MAGIC: &[u8; 4] = b"CH8S";
+// Bump whenever the layout in to_bytes changes, older files are rejected rather than misread
+pub const SNAPSHOT_VERSION: u8 = 2;
+const MEMORY_SIZE: usize = 4096;
+// Magic, version, V0-VF, I, PC, stack pointer, delay and sound timers, hires flag, RPL flags,
+// memory, screen
+const SNAPSHOT_SIZE: usize =
+    MAGIC.len() + 1 + 16 + 2 + 2 + 2 + 1 + 1 + 1 + 8 + MEMORY_SIZE + SCREEN_BUFFER_SIZE_COMPRESSED;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum SnapshotError {
+    #[error("not a save state")]
+    NotASnapshot,
+    #[error("save state version {found} is not supported, expected version {expected}")]
+    UnsupportedVersion { found: u8, expected: u8 },
+    #[error("save state is {size} bytes but should be {expected} bytes")]
+    WrongSize { size: usize, expected: usize },
+}
+
+// Everything needed to pick a program back up where it was, see CPU::snapshot and CPU::restore.
+// The stack lives in memory so it comes along with it. Quirks and patches are configuration and
+// the RNG isn't saved, CXNN results after a restore differ from the original run. The RPL flags
+// are saved even though they outlive a reset, FX85 after a restore reads what was there when the
+// state was saved.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    pub v: [u8; 16],
+    pub i: u16,
+    pub pc: u16,
+    pub stack_ptr: u16,
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+    // DXY0 draws 16x16 sprites
+    pub hires: bool,
+    pub rpl_flags: [u8; 8],
+    pub memory: Box<[u8; MEMORY_SIZE]>,
+    pub screen: PixelBuffer,
+}
+
+impl Snapshot {
+    // Big-endian, fixed size, starts with "CH8S" and the format version
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(SNAPSHOT_SIZE);
+        out.extend_from_slice(MAGIC);
+        out.push(SNAPSHOT_VERSION);
+        out.extend_from_slice(&self.v);
+        out.extend_from_slice(&self.i.to_be_bytes());
+        out.extend_from_slice(&self.pc.to_be_bytes());
+        out.extend_from_slice(&self.stack_ptr.to_be_bytes());
+        out.push(self.delay_timer);
+        out.push(self.sound_timer);
+        out.push(u8::from(self.hires));
+        out.extend_from_slice(&self.rpl_flags);
+        out.extend_from_slice(&self.memory[..]);
+        out.extend_from_slice(&self.screen.as_bytes());
+        return out;
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Snapshot, SnapshotError> {
+        if !bytes.starts_with(MAGIC) {
+            return Err(SnapshotError::NotASnapshot);
+        }
+        // The version comes first so a newer file reports that instead of a size mismatch
+        match bytes.get(MAGIC.len()) {
+            Some(&SNAPSHOT_VERSION) | None => {}
+            Some(&found) => {
+                return Err(SnapshotError::UnsupportedVersion {
+                    found,
+                    expected: SNAPSHOT_VERSION,
+                });
+            }
+        }
+        if bytes.len() != SNAPSHOT_SIZE {
+            return Err(SnapshotError::WrongSize {
+                size: bytes.len(),
+                expected: SNAPSHOT_SIZE,
+            });
+        }
+        let mut reader = Reader {
+            bytes: &bytes[MAGIC.len() + 1..],
+        };
+        return Ok(Snapshot {
+            v: reader.take(),
+            i: u16::from_be_bytes(reader.take()),
+            pc: u16::from_be_bytes(reader.take()),
+            stack_ptr: u16::from_be_bytes(reader.take()),
+            delay_timer: reader.take::<1>()[0],
+            sound_timer: reader.take::<1>()[0],
+            hires: reader.take::<1>()[0] != 0,
+            rpl_flags: reader.take(),
+            memory: Box::new(reader.take()),
+            screen: PixelBuffer::from_bytes(reader.take()),
+        });
+    }
+}
+
+// Memory and the screen are too big to be useful in test failures
+impl std::fmt::Debug for Snapshot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return f
+            .debug_struct("Snapshot")
+            .field("v", &self.v)
+            .field("i", &self.i)
+            .field("pc", &self.pc)
+            .field("stack_ptr", &self.stack_ptr)
+            .field("delay_timer", &self.delay_timer)
+            .field("sound_timer", &self.sound_timer)
+            .field("hires", &self.hires)
+            .field("rpl_flags", &self.rpl_flags)
+            .finish_non_exhaustive();
+    }
+}
+
+// Only used once the size has been checked, so it never runs out
+struct Reader<'b> {
+    bytes: &'b [u8],
+}
+
+impl Reader<'_> {
+    fn take<const N: usize>(&mut self) -> [u8; N] {
+        let (head, rest) = self.bytes.split_at(N);
+        self.bytes = rest;
+        return head.try_into().unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Chip8CPU, OpCodes, ProgramBuilder, Screen, TestCPU, CPU};
+
+    // Calls a subroutine that draws the 0 glyph and spins, so the snapshot has a stack entry, a
+    // sprite on screen, running timers and an RPL flag
+    fn program() -> Vec<u8> {
+        let mut builder = ProgramBuilder::new();
+        let draw = builder.label("draw");
+        let spin = builder.label("spin");
+        builder
+            .push(OpCodes::_6XNN { x: 0, nn: 30 })
+            .push(OpCodes::_FX75 { x: 0 })
+            .push(OpCodes::_FX15 { x: 0 })
+            .push(OpCodes::_FX18 { x: 0 })
+            .call(draw)
+            .bind(draw)
+            .push(OpCodes::_6XNN { x: 1, nn: 0 })
+            .push(OpCodes::_FX29 { x: 1 })
+            .push(OpCodes::_DXYN { x: 1, y: 1, n: 5 })
+            .bind(spin)
+            .jump_to(spin);
+        return builder.finish();
+    }
+
+    #[test]
+    fn round_trips_through_bytes_and_restores() {
+        let screen = Screen::new();
+        let mut cpu = CPU::new_seeded(&screen, &crate::NoopInput, 0);
+        cpu.load_program(&program()).unwrap();
+        for _ in 0..9 {
+            cpu.step().unwrap();
+        }
+        cpu.tick_timers();
+        cpu.set_hires(true);
+        let snapshot = cpu.snapshot(screen.buffer.borrow().clone());
+        assert_eq!(snapshot.delay_timer, 29);
+        assert!(snapshot.screen.get_pixel(0, 0));
+        assert!(snapshot.hires);
+        assert_eq!(snapshot.rpl_flags, [30, 0, 0, 0, 0, 0, 0, 0]);
+
+        let bytes = snapshot.to_bytes();
+        assert_eq!(bytes.len(), SNAPSHOT_SIZE);
+        let loaded = Snapshot::from_bytes(&bytes).unwrap();
+        assert_eq!(loaded, snapshot);
+
+        let restored_screen = Screen::new();
+        let mut restored = CPU::new_seeded(&restored_screen, &crate::NoopInput, 0);
+        restored.restore(&loaded);
+        restored_screen.set_buffer(loaded.screen.clone());
+        assert_eq!(restored.state_with_memory(), cpu.state_with_memory());
+        assert!(restored.is_hires());
+        assert_eq!(
+            restored.snapshot(PixelBuffer::new()).rpl_flags,
+            snapshot.rpl_flags
+        );
+        assert_eq!(restored.state().stack.len(), 1);
+        assert_eq!(restored_screen.as_bool_grid(), screen.as_bool_grid());
+        assert!(restored_screen.is_pending_draw());
+
+        // Both carry on the same way
+        for _ in 0..3 {
+            cpu.step().unwrap();
+            restored.step().unwrap();
+        }
+        assert_eq!(restored.state_with_memory(), cpu.state_with_memory());
+    }
+
+    #[test]
+    fn rejects_corrupt_files() {
+        let bytes = TestCPU::default().snapshot(PixelBuffer::new()).to_bytes();
+
+        assert_eq!(
+            Snapshot::from_bytes(b"not a state"),
+            Err(SnapshotError::NotASnapshot)
+        );
+        assert_eq!(Snapshot::from_bytes(&[]), Err(SnapshotError::NotASnapshot));
+
+        let mut newer = bytes.clone();
+        newer[MAGIC.len()] = SNAPSHOT_VERSION + 1;
+        assert_eq!(
+            Snapshot::from_bytes(&newer),
+            Err(SnapshotError::UnsupportedVersion {
+                found: SNAPSHOT_VERSION + 1,
+                expected: SNAPSHOT_VERSION
+            })
+        );
+
+        for size in [MAGIC.len(), 100, SNAPSHOT_SIZE - 1, SNAPSHOT_SIZE + 1] {
+            let mut resized = bytes.clone();
+            resized.resize(size, 0);
+            assert_eq!(
+                Snapshot::from_bytes(&resized),
+                Err(SnapshotError::WrongSize {
+                    size,
+                    expected: SNAPSHOT_SIZE
+                }),
+                "{}",
+                size
+            );
+        }
+    }
+}
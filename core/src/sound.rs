@@ -0,0 +1,14 @@
+// The tone CHIP-8 plays while the sound timer is above zero. The CPU calls play when the timer is
+// set from zero and stop once it runs out, so backends only ever see edges.
+pub trait Chip8Sound {
+    fn play(&self);
+    fn stop(&self);
+}
+
+pub struct NoopSound;
+
+impl Chip8Sound for NoopSound {
+    fn play(&self) {}
+
+    fn stop(&self) {}
+}
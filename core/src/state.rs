@@ -0,0 +1,113 @@
+use std::{fmt::Display, ops::Range};
+
+// Comparable copy of the CPU, see CPU::state and CPU::state_with_memory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CpuState {
+    pub v: [u8; 16],
+    pub i: u16,
+    pub pc: u16,
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+    // Return addresses, innermost call last
+    pub stack: Vec<u16>,
+    // Only captured on request, memory is only compared when both states have it
+    pub memory: Option<Vec<u8>>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StateDiff {
+    // (register, old, new)
+    pub registers: Vec<(u8, u8, u8)>,
+    pub i: Option<(u16, u16)>,
+    pub pc: Option<(u16, u16)>,
+    pub delay_timer: Option<(u8, u8)>,
+    pub sound_timer: Option<(u8, u8)>,
+    pub stack: Option<(Vec<u16>, Vec<u16>)>,
+    // Runs of consecutive changed bytes
+    pub memory: Vec<Range<u16>>,
+}
+
+impl CpuState {
+    pub fn diff(&self, other: &CpuState) -> StateDiff {
+        let registers = (0..16u8)
+            .zip(self.v.iter().zip(other.v.iter()))
+            .filter(|(_, (old, new))| old != new)
+            .map(|(x, (old, new))| (x, *old, *new))
+            .collect();
+        let memory = match (&self.memory, &other.memory) {
+            (Some(old), Some(new)) => changed_ranges(old, new),
+            _ => vec![],
+        };
+        return StateDiff {
+            registers,
+            i: changed(self.i, other.i),
+            pc: changed(self.pc, other.pc),
+            delay_timer: changed(self.delay_timer, other.delay_timer),
+            sound_timer: changed(self.sound_timer, other.sound_timer),
+            stack: changed(self.stack.clone(), other.stack.clone()),
+            memory,
+        };
+    }
+}
+
+impl StateDiff {
+    pub fn is_empty(&self) -> bool {
+        return *self == StateDiff::default();
+    }
+}
+
+fn changed<T: PartialEq>(old: T, new: T) -> Option<(T, T)> {
+    if old == new {
+        return None;
+    }
+    return Some((old, new));
+}
+
+fn changed_ranges(old: &[u8], new: &[u8]) -> Vec<Range<u16>> {
+    let mut ranges: Vec<Range<u16>> = vec![];
+    for (addr, _) in old
+        .iter()
+        .zip(new.iter())
+        .enumerate()
+        .filter(|(_, (a, b))| a != b)
+    {
+        let addr = addr as u16;
+        match ranges.last_mut() {
+            Some(range) if range.end == addr => range.end += 1,
+            _ => ranges.push(addr..addr + 1),
+        }
+    }
+    return ranges;
+}
+
+// One line, e.g. "V0 05->06 PC 0200->0202 MEM 0x300..0x303"
+impl Display for StateDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_empty() {
+            return write!(f, "no changes");
+        }
+        let mut parts = vec![];
+        for (x, old, new) in self.registers.iter() {
+            parts.push(format!("V{:X} {:02X}->{:02X}", x, old, new));
+        }
+        if let Some((old, new)) = self.i {
+            parts.push(format!("I {:04X}->{:04X}", old, new));
+        }
+        if let Some((old, new)) = self.pc {
+            parts.push(format!("PC {:04X}->{:04X}", old, new));
+        }
+        if let Some((old, new)) = self.delay_timer {
+            parts.push(format!("DT {:02X}->{:02X}", old, new));
+        }
+        if let Some((old, new)) = self.sound_timer {
+            parts.push(format!("ST {:02X}->{:02X}", old, new));
+        }
+        if let Some((old, new)) = &self.stack {
+            parts.push(format!("STACK {:04X?}->{:04X?}", old, new));
+        }
+        for range in self.memory.iter() {
+            parts.push(format!("MEM 0x{:03X}..0x{:03X}", range.start, range.end));
+        }
+        return write!(f, "{}", parts.join(" "));
+    }
+}
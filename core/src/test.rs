@@ -3,51 +3,14 @@ use crate::{
     Chip8CPU, Chip8Input, Chip8Screen, CPU,
 };
 
-pub(crate) struct NoopScreen;
+// The helpers below ignore load and step results. Test programs are a handful of instructions, so
+// they always fit in memory, and tests assert on the resulting CPU state: a failing step leaves the
+// PC on the faulting instruction, so later steps just repeat the same error.
 
-impl Chip8Screen for NoopScreen {
-    // Each row is a byte, with each bit representing a pixel, this is the same as the buffer
-    fn draw_sprite(&self, _x: u8, _y: u8, _sprite: &[u8]) -> bool {
-        return false;
-    }
-
-    fn clear(&self) {}
-}
-
-pub(crate) fn u16_to_u8(data: &[u16]) -> Vec<u8> {
-    data.iter()
-        .flat_map(|num| {
-            let left = ((num & 0xFF00) >> 8) as u8;
-            let right = (num & 0x00FF) as u8;
-            [left, right]
-        })
-        .collect::<Vec<u8>>()
-}
-
-pub(crate) fn run_program<TScreen: Chip8Screen, TInput: Chip8Input>(
-    cpu: &mut CPU<'_, TScreen, TInput>,
-    data: &[u16],
-) -> () {
-    cpu.load_program(u16_to_u8(data).as_slice()).ok();
-    for _ in 0..data.len() {
-        cpu.step().ok();
-    }
-}
-
-pub(crate) fn run_from_program_counter<TScreen: Chip8Screen, TInput: Chip8Input>(
-    cpu: &mut CPU<'_, TScreen, TInput>,
-    data: &[u16],
-) -> () {
-    cpu.load_at_program_counter(u16_to_u8(data).as_slice()).ok();
-    for _ in 0..data.len() {
-        cpu.step().ok();
-    }
-}
-
-pub fn op_run_program<TScreen: Chip8Screen, TInput: Chip8Input>(
+pub fn op_run_program<TScreen: Chip8Screen + ?Sized, TInput: Chip8Input + ?Sized>(
     cpu: &mut CPU<'_, TScreen, TInput>,
     data: &[OpCodes],
-) -> () {
+) {
     cpu.load_program(convert_opcodes_into_u8(data).as_slice())
         .ok();
     for _ in 0..data.len() {
@@ -55,10 +18,11 @@ pub fn op_run_program<TScreen: Chip8Screen, TInput: Chip8Input>(
     }
 }
 
+#[cfg(test)]
 pub(crate) fn op_run_from_program_counter<TScreen: Chip8Screen, TInput: Chip8Input>(
     cpu: &mut CPU<'_, TScreen, TInput>,
     data: &[OpCodes],
-) -> () {
+) {
     cpu.load_at_program_counter(convert_opcodes_into_u8(data).as_slice())
         .ok();
     for _ in 0..data.len() {
@@ -80,6 +44,29 @@ macro_rules! run {
     }};
 }
 
+// Same as run! but takes the raw instruction words CHIP-8 documentation lists, e.g.
+// run_bytes!(cpu, 0x6012, 0x7003). Panics on a word that isn't an instruction.
+#[macro_export]
+macro_rules! run_bytes {
+    ($cpu:expr, $($word:expr),* $(,)?) => {{
+        let words: &[u16] = &[$($word),*];
+        let pairs = words
+            .iter()
+            .map(|word| {
+                let [high, low] = word.to_be_bytes();
+                (high, low)
+            })
+            .collect::<Vec<_>>();
+        $crate::op_run_program(
+            &mut $cpu,
+            $crate::convert_u8_tuples_into_opcodes(&pairs)
+                .expect("run_bytes! takes instruction words")
+                .as_slice(),
+        )
+    }};
+}
+
+#[cfg(test)]
 #[macro_export]
 macro_rules! run_from_pc {
     ($cpu:expr, $($opcode:ident { $($field:ident: $value:expr),* }),* $(,)?) => {{
@@ -93,3 +80,34 @@ macro_rules! run_from_pc {
         )
     }};
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{Screen, CPU};
+
+    #[test]
+    fn run_bytes_matches_run() {
+        let screen = Screen::new();
+        let mut words = CPU::new_seeded(&screen, &crate::NoopInput, 0);
+        run_bytes!(words, 0x6012, 0x7003, 0x6105, 0x8014, 0xA050, 0xF033, 0xD015);
+
+        let op_screen = Screen::new();
+        let mut ops = CPU::new_seeded(&op_screen, &crate::NoopInput, 0);
+        run!(
+            ops,
+            _6XNN { x: 0, nn: 0x12 },
+            _7XNN { x: 0, nn: 0x03 },
+            _6XNN { x: 1, nn: 0x05 },
+            _8XY4 { x: 0, y: 1 },
+            _ANNN { nnn: 0x050 },
+            _FX33 { x: 0 },
+            _DXYN { x: 0, y: 1, n: 5 },
+        );
+
+        assert_eq!(words.state_with_memory(), ops.state_with_memory());
+        assert_eq!(words.state().v[0], 0x1A);
+        assert_eq!(screen.as_bool_grid(), op_screen.as_bool_grid());
+        // FX33 wrote 0, 2, 6 over the top of the 0 glyph at I, so row 1 is 0x02
+        assert!(screen.get_pixel(32, 6));
+    }
+}
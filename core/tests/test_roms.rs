@@ -0,0 +1,128 @@
+// Runs every ROM in tests/roms and compares the screen with its golden file. To add a ROM, drop
+// <name>.ch8 next to a <name>.txt holding "cycles: <n>" followed by the expected screen, one line
+// per row with # for lit pixels and . for unlit ones. Running with UPDATE_GOLDEN=1 writes the
+// golden files from the current output, check the result by eye before committing it.
+//
+// font.ch8 draws the 16 font digits. alu.ch8 runs a dozen arithmetic, BCD, skip and timer checks
+// and draws the number of each one that passes, or an E in its place when it fails.
+// ibm_logo.ch8 is the classic public domain IBM logo, its 20 instructions only use 00E0, ANNN,
+// 6XNN, 7XNN, DXYN and a final 1NNN jump to itself, so it's the first thing to break when those do.
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use chip8_core::{rom::Rom, Chip8CPU, NoopInput, Screen, CPU};
+
+const DEFAULT_CYCLES: u64 = 1000;
+
+fn roms_dir() -> PathBuf {
+    return Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("roms");
+}
+
+fn render(screen: &Screen) -> String {
+    let mut out = String::new();
+    for row in screen.as_bool_grid() {
+        out.extend(row.iter().map(|on| if *on { '#' } else { '.' }));
+        out.push('\n');
+    }
+    return out;
+}
+
+// Returns the cycle count and the expected screen
+fn parse_golden(golden: &str) -> Result<(u64, String), String> {
+    let (header, screen) = golden.split_once('\n').unwrap_or((golden, ""));
+    let cycles = header
+        .strip_prefix("cycles:")
+        .and_then(|n| n.trim().parse().ok())
+        .ok_or_else(|| format!("expected \"cycles: <n>\" on the first line, got {}", header))?;
+    return Ok((cycles, screen.to_string()));
+}
+
+fn run_rom(rom: &Path, cycles: u64) -> Result<String, String> {
+    let screen = Screen::new();
+    run_rom_on(rom, cycles, &screen)?;
+    return Ok(render(&screen));
+}
+
+fn run_rom_on(rom: &Path, cycles: u64, screen: &Screen) -> Result<(), String> {
+    let data = fs::read(rom).map_err(|err| err.to_string())?;
+    let rom = Rom::parse(&data).map_err(|err| err.to_string())?;
+    let mut cpu = CPU::new_seeded(screen, &NoopInput, 0);
+    cpu.load_rom(&rom).map_err(|err| err.to_string())?;
+    for _ in 0..cycles {
+        cpu.step()
+            .map_err(|err| format!("{} at PC=0x{:03X}", err, cpu.pc()))?;
+    }
+    return Ok(());
+}
+
+#[test]
+fn test_roms_match_golden_screens() {
+    let update = std::env::var_os("UPDATE_GOLDEN").is_some();
+    let mut roms = fs::read_dir(roms_dir())
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "ch8"))
+        .collect::<Vec<_>>();
+    roms.sort();
+    assert!(!roms.is_empty(), "no ROMs in {}", roms_dir().display());
+
+    let mut failures = vec![];
+    for rom in roms.iter() {
+        let name = rom.file_name().unwrap().to_string_lossy();
+        let golden_path = rom.with_extension("txt");
+        let golden = fs::read_to_string(&golden_path)
+            .map_err(|err| err.to_string())
+            .and_then(|golden| parse_golden(&golden));
+        let (cycles, expected) = match golden {
+            Ok(golden) => golden,
+            Err(_) if update => (DEFAULT_CYCLES, String::new()),
+            Err(err) => {
+                failures.push(format!("{}: {}: {}", name, golden_path.display(), err));
+                continue;
+            }
+        };
+        let actual = match run_rom(rom, cycles) {
+            Ok(actual) => actual,
+            Err(err) => {
+                failures.push(format!("{}: {}", name, err));
+                continue;
+            }
+        };
+        if update {
+            fs::write(&golden_path, format!("cycles: {}\n{}", cycles, actual)).unwrap();
+        } else if actual != expected {
+            failures.push(format!(
+                "{}: screen after {} cycles doesn't match {}, got\n{}",
+                name,
+                cycles,
+                golden_path.display(),
+                actual
+            ));
+        }
+    }
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
+
+#[test]
+fn ibm_logo_exports_as_pbm() {
+    let screen = Screen::new();
+    run_rom_on(&roms_dir().join("ibm_logo.ch8"), 20, &screen).unwrap();
+    let pbm = screen.to_pbm();
+    let header = b"P4\n64 32\n";
+    // The header, then the top row which the logo doesn't reach
+    assert_eq!(pbm[..10], [&header[..], &[0]].concat());
+    assert_eq!(pbm.len(), header.len() + 256);
+
+    let grid = screen.as_bool_grid();
+    for (y, row) in pbm[header.len()..].chunks(8).enumerate() {
+        for x in 0..64 {
+            let bit = row[x / 8] & 0x80 >> (x % 8) != 0;
+            assert_eq!(bit, grid[y][x], "pixel ({}, {})", x, y);
+        }
+    }
+    assert!(grid.iter().flatten().any(|on| *on));
+}
@@ -0,0 +1,81 @@
+#ifndef CHIP8_H
+#define CHIP8_H
+
+/* Generated by cbindgen from chip8-ffi, do not edit */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+#define CHIP8_OK 0
+
+#define CHIP8_ERR_NULL -1
+
+#define CHIP8_ERR_DECODE -2
+
+#define CHIP8_ERR_MEMORY -3
+
+#define CHIP8_ERR_STACK -4
+
+#define CHIP8_ERR_UNSUPPORTED -5
+
+#define CHIP8_ERR_EXIT -6
+
+/**
+ * Opaque CPU handle, create with chip8_cpu_new and release with chip8_cpu_free.
+ */
+typedef struct Chip8CpuHandle Chip8CpuHandle;
+
+/**
+ * Chip8Screen::draw_sprite: XOR the sprite in at (x, y) and return true on a collision.
+ * sprite is NULL and len 0 when the screen should be cleared instead.
+ */
+typedef bool (*Chip8DrawCallback)(uint8_t x, uint8_t y, const uint8_t *sprite, size_t len);
+
+/**
+ * Chip8Input: return the held key (0-15), or when released is true the key released since the
+ * last such call. Return -1 for none.
+ */
+typedef int32_t (*Chip8InputCallback)(bool released);
+
+/**
+ * Creates a CPU that draws and reads keys through the callbacks. Free it with chip8_cpu_free.
+ */
+struct Chip8CpuHandle *chip8_cpu_new(Chip8DrawCallback draw_cb, Chip8InputCallback input_cb);
+
+/**
+ * Runs one instruction, returns CHIP8_OK or a negative CHIP8_ERR_* code.
+ *
+ * # Safety
+ * handle must be NULL or a live pointer from chip8_cpu_new.
+ */
+int32_t chip8_cpu_step(struct Chip8CpuHandle *handle);
+
+/**
+ * Copies len bytes of ROM to 0x200, returns CHIP8_OK or a negative CHIP8_ERR_* code.
+ *
+ * # Safety
+ * handle must be NULL or a live pointer from chip8_cpu_new, and rom must point at len readable
+ * bytes.
+ */
+int32_t chip8_cpu_load_rom(struct Chip8CpuHandle *handle, const uint8_t *rom, size_t len);
+
+/**
+ * Copies V0-VF into out.
+ *
+ * # Safety
+ * handle must be NULL or a live pointer from chip8_cpu_new, and out must have room for 16 bytes.
+ */
+int32_t chip8_cpu_get_registers(struct Chip8CpuHandle *handle, uint8_t *out);
+
+/**
+ * Frees the CPU, passing NULL does nothing.
+ *
+ * # Safety
+ * handle must be NULL or a live pointer from chip8_cpu_new, it can't be used afterwards.
+ */
+void chip8_cpu_free(struct Chip8CpuHandle *handle);
+
+#endif  /* CHIP8_H */
@@ -0,0 +1,156 @@
+// C API over chip8_core, see include/chip8.h. Every function takes the handle returned by
+// chip8_cpu_new and is safe to call with NULL, which is reported as CHIP8_ERR_NULL.
+use std::ptr;
+
+use chip8_core::{Chip8CPU, Chip8Error, Chip8Input, Chip8Screen, ErrorKind, CPU};
+
+pub const CHIP8_OK: i32 = 0;
+pub const CHIP8_ERR_NULL: i32 = -1;
+pub const CHIP8_ERR_DECODE: i32 = -2;
+pub const CHIP8_ERR_MEMORY: i32 = -3;
+pub const CHIP8_ERR_STACK: i32 = -4;
+pub const CHIP8_ERR_UNSUPPORTED: i32 = -5;
+pub const CHIP8_ERR_EXIT: i32 = -6;
+
+/// Chip8Screen::draw_sprite: XOR the sprite in at (x, y) and return true on a collision.
+/// sprite is NULL and len 0 when the screen should be cleared instead.
+pub type Chip8DrawCallback = extern "C" fn(x: u8, y: u8, sprite: *const u8, len: usize) -> bool;
+
+/// Chip8Input: return the held key (0-15), or when released is true the key released since the
+/// last such call. Return -1 for none.
+pub type Chip8InputCallback = extern "C" fn(released: bool) -> i32;
+
+struct CallbackScreen(Chip8DrawCallback);
+
+impl Chip8Screen for CallbackScreen {
+    fn draw_sprite(&self, x: u8, y: u8, sprite: &[u8]) -> bool {
+        return (self.0)(x, y, sprite.as_ptr(), sprite.len());
+    }
+
+    fn clear(&self) {
+        (self.0)(0, 0, ptr::null(), 0);
+    }
+}
+
+struct CallbackInput(Chip8InputCallback);
+
+impl CallbackInput {
+    fn key(&self, released: bool) -> Option<u8> {
+        return u8::try_from((self.0)(released))
+            .ok()
+            .filter(|key| *key <= 0xF);
+    }
+}
+
+impl Chip8Input for CallbackInput {
+    fn get_key(&self) -> Option<u8> {
+        return self.key(false);
+    }
+
+    fn was_key_released(&self) -> Option<u8> {
+        return self.key(true);
+    }
+}
+
+/// Opaque CPU handle, create with chip8_cpu_new and release with chip8_cpu_free.
+pub struct Chip8CpuHandle {
+    cpu: CPU<'static, CallbackScreen, CallbackInput>,
+    // The CPU borrows these, they're freed after it in chip8_cpu_free
+    screen: *mut CallbackScreen,
+    input: *mut CallbackInput,
+}
+
+fn error_code(err: &Chip8Error) -> i32 {
+    return match err.category() {
+        ErrorKind::Decode => CHIP8_ERR_DECODE,
+        ErrorKind::Memory => CHIP8_ERR_MEMORY,
+        ErrorKind::Stack => CHIP8_ERR_STACK,
+        ErrorKind::Unsupported => CHIP8_ERR_UNSUPPORTED,
+        ErrorKind::Exit => CHIP8_ERR_EXIT,
+    };
+}
+
+fn to_code(result: Result<(), Chip8Error>) -> i32 {
+    return match result {
+        Ok(()) => CHIP8_OK,
+        Err(err) => error_code(&err),
+    };
+}
+
+/// Creates a CPU that draws and reads keys through the callbacks. Free it with chip8_cpu_free.
+#[no_mangle]
+pub extern "C" fn chip8_cpu_new(
+    draw_cb: Chip8DrawCallback,
+    input_cb: Chip8InputCallback,
+) -> *mut Chip8CpuHandle {
+    let screen = Box::into_raw(Box::new(CallbackScreen(draw_cb)));
+    let input = Box::into_raw(Box::new(CallbackInput(input_cb)));
+    // SAFETY: both boxes stay alive until chip8_cpu_free has dropped the CPU
+    let cpu = unsafe { CPU::new(&*screen, &*input) };
+    return Box::into_raw(Box::new(Chip8CpuHandle { cpu, screen, input }));
+}
+
+/// Runs one instruction, returns CHIP8_OK or a negative CHIP8_ERR_* code.
+///
+/// # Safety
+/// handle must be NULL or a live pointer from chip8_cpu_new.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_cpu_step(handle: *mut Chip8CpuHandle) -> i32 {
+    let Some(handle) = handle.as_mut() else {
+        return CHIP8_ERR_NULL;
+    };
+    return to_code(handle.cpu.step().map(|_| ()));
+}
+
+/// Copies len bytes of ROM to 0x200, returns CHIP8_OK or a negative CHIP8_ERR_* code.
+///
+/// # Safety
+/// handle must be NULL or a live pointer from chip8_cpu_new, and rom must point at len readable
+/// bytes.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_cpu_load_rom(
+    handle: *mut Chip8CpuHandle,
+    rom: *const u8,
+    len: usize,
+) -> i32 {
+    let Some(handle) = handle.as_mut() else {
+        return CHIP8_ERR_NULL;
+    };
+    if rom.is_null() {
+        return CHIP8_ERR_NULL;
+    }
+    let rom = std::slice::from_raw_parts(rom, len);
+    return to_code(handle.cpu.load_program(rom));
+}
+
+/// Copies V0-VF into out.
+///
+/// # Safety
+/// handle must be NULL or a live pointer from chip8_cpu_new, and out must have room for 16 bytes.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_cpu_get_registers(handle: *mut Chip8CpuHandle, out: *mut u8) -> i32 {
+    let Some(handle) = handle.as_ref() else {
+        return CHIP8_ERR_NULL;
+    };
+    if out.is_null() {
+        return CHIP8_ERR_NULL;
+    }
+    let registers = handle.cpu.state().v;
+    ptr::copy_nonoverlapping(registers.as_ptr(), out, registers.len());
+    return CHIP8_OK;
+}
+
+/// Frees the CPU, passing NULL does nothing.
+///
+/// # Safety
+/// handle must be NULL or a live pointer from chip8_cpu_new, it can't be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_cpu_free(handle: *mut Chip8CpuHandle) {
+    if handle.is_null() {
+        return;
+    }
+    let Chip8CpuHandle { cpu, screen, input } = *Box::from_raw(handle);
+    drop(cpu);
+    drop(Box::from_raw(screen));
+    drop(Box::from_raw(input));
+}
@@ -0,0 +1,73 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use chip8_ffi::*;
+
+static DRAWS: AtomicUsize = AtomicUsize::new(0);
+static CLEARS: AtomicUsize = AtomicUsize::new(0);
+
+extern "C" fn draw(_x: u8, _y: u8, sprite: *const u8, len: usize) -> bool {
+    if sprite.is_null() {
+        CLEARS.fetch_add(1, Ordering::SeqCst);
+    } else {
+        assert_eq!(len, 5);
+        DRAWS.fetch_add(1, Ordering::SeqCst);
+    }
+    return false;
+}
+
+// Key 0x7 is always held
+extern "C" fn input(released: bool) -> i32 {
+    return if released { -1 } else { 7 };
+}
+
+#[test]
+fn runs_a_rom_through_the_c_api() {
+    let rom = [
+        0x00, 0xE0, // CLR
+        0x60, 0x2A, // LOAD V0 0x2A
+        0x71, 0x05, // ADD V1 0x05
+        0xA0, 0x50, // LOADI 0x050
+        0xD0, 0x15, // DRAW V0 V1 0x5
+        0x32, 0x07, // SKE V2 0x07
+        0xE2, 0x9E, // SKPR V2 (V2 is 0, not held)
+        0x62, 0x07, // LOAD V2 0x07
+    ];
+    unsafe {
+        let cpu = chip8_cpu_new(draw, input);
+        assert!(!cpu.is_null());
+        assert_eq!(chip8_cpu_load_rom(cpu, rom.as_ptr(), rom.len()), CHIP8_OK);
+        for _ in 0..8 {
+            assert_eq!(chip8_cpu_step(cpu), CHIP8_OK);
+        }
+        let mut registers = [0xFFu8; 16];
+        assert_eq!(
+            chip8_cpu_get_registers(cpu, registers.as_mut_ptr()),
+            CHIP8_OK
+        );
+        assert_eq!(&registers[..3], &[0x2A, 0x05, 0x07]);
+        assert!(registers[3..].iter().all(|v| *v == 0));
+        assert_eq!(CLEARS.load(Ordering::SeqCst), 1);
+        assert_eq!(DRAWS.load(Ordering::SeqCst), 1);
+
+        let too_big = vec![0u8; 4096];
+        assert_eq!(
+            chip8_cpu_load_rom(cpu, too_big.as_ptr(), too_big.len()),
+            CHIP8_ERR_MEMORY
+        );
+        chip8_cpu_free(cpu);
+
+        assert_eq!(chip8_cpu_step(std::ptr::null_mut()), CHIP8_ERR_NULL);
+        chip8_cpu_free(std::ptr::null_mut());
+    }
+}
+
+#[test]
+fn invalid_opcodes_are_decode_errors() {
+    unsafe {
+        let cpu = chip8_cpu_new(draw, input);
+        let rom = [0x5A, 0xB1];
+        assert_eq!(chip8_cpu_load_rom(cpu, rom.as_ptr(), rom.len()), CHIP8_OK);
+        assert_eq!(chip8_cpu_step(cpu), CHIP8_ERR_DECODE);
+        chip8_cpu_free(cpu);
+    }
+}
@@ -0,0 +1,214 @@
+use std::cell::{Cell, RefCell};
+
+use chip8_core::{Chip8Input, Chip8Screen, Screen, SCREEN_HEIGHT, SCREEN_WIDTH};
+use sdl2::{
+    event::Event,
+    keyboard::{Keycode, Scancode},
+    pixels::Color,
+    rect::Rect,
+    render::Canvas,
+    video::Window,
+    EventPump, Sdl,
+};
+
+// The usual layout, the left 4x4 block of a QWERTY keyboard maps onto the CHIP-8 keypad
+//   1 2 3 4      1 2 3 C
+//   Q W E R  ->  4 5 6 D
+//   A S D F      7 8 9 E
+//   Z X C V      A 0 B F
+const KEYMAP: [(Scancode, u8); 16] = [
+    (Scancode::Num1, 0x1),
+    (Scancode::Num2, 0x2),
+    (Scancode::Num3, 0x3),
+    (Scancode::Num4, 0xC),
+    (Scancode::Q, 0x4),
+    (Scancode::W, 0x5),
+    (Scancode::E, 0x6),
+    (Scancode::R, 0xD),
+    (Scancode::A, 0x7),
+    (Scancode::S, 0x8),
+    (Scancode::D, 0x9),
+    (Scancode::F, 0xE),
+    (Scancode::Z, 0xA),
+    (Scancode::X, 0x0),
+    (Scancode::C, 0xB),
+    (Scancode::V, 0xF),
+];
+
+pub fn keypad_key(scancode: Scancode) -> Option<u8> {
+    return KEYMAP
+        .iter()
+        .find(|(mapped, _)| *mapped == scancode)
+        .map(|(_, key)| *key);
+}
+
+// A 64*scale x 32*scale window, the pixels live in a Screen and are presented after every change
+pub struct Sdl2Display {
+    sdl: Sdl,
+    canvas: RefCell<Canvas<Window>>,
+    screen: Screen,
+    scale: u32,
+}
+
+impl Sdl2Display {
+    pub fn new(scale: u32) -> Result<Sdl2Display, String> {
+        let sdl = sdl2::init()?;
+        let window = sdl
+            .video()?
+            .window(
+                "CHIP-8",
+                SCREEN_WIDTH as u32 * scale,
+                SCREEN_HEIGHT as u32 * scale,
+            )
+            .position_centered()
+            .build()
+            .map_err(|err| err.to_string())?;
+        let canvas = window
+            .into_canvas()
+            .build()
+            .map_err(|err| err.to_string())?;
+        let display = Sdl2Display {
+            sdl,
+            canvas: RefCell::new(canvas),
+            screen: Screen::new(),
+            scale,
+        };
+        display.present();
+        return Ok(display);
+    }
+
+    // Share the SDL context with Sdl2Input::new
+    pub fn context(&self) -> &Sdl {
+        return &self.sdl;
+    }
+
+    pub fn screen(&self) -> &Screen {
+        return &self.screen;
+    }
+
+    pub fn present(&self) {
+        let palette = self.screen.palette();
+        let mut canvas = self.canvas.borrow_mut();
+        let (off, on) = (palette.color(0), palette.color(1));
+        canvas.set_draw_color(Color::RGB(off.r, off.g, off.b));
+        canvas.clear();
+        canvas.set_draw_color(Color::RGB(on.r, on.g, on.b));
+        let scale = self.scale as i32;
+        for (y, row) in self.screen.rows_iter().enumerate() {
+            // One rect per run of lit pixels, bit 63 is the leftmost
+            let mut rest = row;
+            let mut x = 0;
+            while rest != 0 {
+                let gap = rest.leading_zeros();
+                rest <<= gap;
+                let run = rest.leading_ones();
+                let rect = Rect::new(
+                    (x + gap) as i32 * scale,
+                    y as i32 * scale,
+                    run * self.scale,
+                    self.scale,
+                );
+                // Only fails if the renderer is gone, the next present will try again
+                let _ = canvas.fill_rect(rect);
+                // A run can reach the right edge, shifting by 64 would overflow
+                rest = rest.checked_shl(run).unwrap_or(0);
+                x += gap + run;
+            }
+        }
+        canvas.present();
+        self.screen.mark_drawn();
+    }
+}
+
+impl Chip8Screen for Sdl2Display {
+    fn draw_sprite(&self, x: u8, y: u8, sprite: &[u8]) -> bool {
+        let collision = self.screen.draw_sprite(x, y, sprite);
+        self.present();
+        return collision;
+    }
+
+    fn clear(&self) {
+        self.screen.clear();
+        self.present();
+    }
+}
+
+// Reads the keyboard state from SDL, call poll_quit once per frame to pump the event queue
+pub struct Sdl2Input {
+    event_pump: RefCell<EventPump>,
+    // Keys held the last time was_key_released looked, one bit per key
+    last_held: Cell<u16>,
+}
+
+impl Sdl2Input {
+    // SDL allows one event pump at a time, so there can only be one Sdl2Input
+    pub fn new(sdl: &Sdl) -> Result<Sdl2Input, String> {
+        return Ok(Sdl2Input {
+            event_pump: RefCell::new(sdl.event_pump()?),
+            last_held: Cell::new(0),
+        });
+    }
+
+    // Drains pending events, true if the window was closed or Escape was pressed
+    pub fn poll_quit(&self) -> bool {
+        return self.event_pump.borrow_mut().poll_iter().any(|event| {
+            matches!(
+                event,
+                Event::Quit { .. }
+                    | Event::KeyDown {
+                        keycode: Some(Keycode::Escape),
+                        ..
+                    }
+            )
+        });
+    }
+
+    fn held(&self) -> u16 {
+        let mut event_pump = self.event_pump.borrow_mut();
+        event_pump.pump_events();
+        let keyboard = event_pump.keyboard_state();
+        return KEYMAP
+            .iter()
+            .filter(|(scancode, _)| keyboard.is_scancode_pressed(*scancode))
+            .fold(0, |held, (_, key)| held | 1 << key);
+    }
+}
+
+impl Chip8Input for Sdl2Input {
+    // Lowest held key wins when several are down
+    fn get_key(&self) -> Option<u8> {
+        let held = self.held();
+        if held == 0 {
+            return None;
+        }
+        return Some(held.trailing_zeros() as u8);
+    }
+
+    fn was_key_released(&self) -> Option<u8> {
+        let held = self.held();
+        let released = self.last_held.replace(held) & !held;
+        if released == 0 {
+            return None;
+        }
+        return Some(released.trailing_zeros() as u8);
+    }
+
+    fn is_key_held(&self, key: u8) -> bool {
+        return key <= 0xF && self.held() & 1 << key != 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keymap_covers_every_key_once() {
+        let mut keys = KEYMAP.iter().map(|(_, key)| *key).collect::<Vec<_>>();
+        keys.sort();
+        assert_eq!(keys, (0..16).collect::<Vec<u8>>());
+        assert_eq!(keypad_key(Scancode::Num4), Some(0xC));
+        assert_eq!(keypad_key(Scancode::X), Some(0x0));
+        assert_eq!(keypad_key(Scancode::P), None);
+    }
+}
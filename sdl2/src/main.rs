@@ -0,0 +1,41 @@
+use std::{
+    thread::sleep,
+    time::{Duration, Instant},
+};
+
+use chip8_core::{rom::Rom, Chip8Error, CPU};
+use chip8_sdl2::{Sdl2Display, Sdl2Input};
+
+const FRAME_DURATION: Duration = Duration::from_nanos(16_666_667);
+const INSTRUCTIONS_PER_FRAME: usize = 10;
+const DEFAULT_SCALE: u32 = 10;
+
+// chip8-sdl2 <rom> [scale]
+fn main() -> Result<(), String> {
+    let mut args = std::env::args().skip(1);
+    let filename = args.next().ok_or("No filename provided")?;
+    let scale = match args.next() {
+        Some(scale) => scale.parse().map_err(|_| "scale expects a number")?,
+        None => DEFAULT_SCALE,
+    };
+
+    let data =
+        std::fs::read(&filename).map_err(|err| format!("Could not open {}: {}", filename, err))?;
+    let rom = Rom::parse(&data).map_err(|err| err.to_string())?;
+    let display = Sdl2Display::new(scale)?;
+    let input = Sdl2Input::new(display.context())?;
+    let mut cpu = CPU::new(&display, &input);
+    cpu.load_rom(&rom).map_err(|err| err.to_string())?;
+
+    let mut next_frame = Instant::now();
+    while !input.poll_quit() {
+        match cpu.step_frame(INSTRUCTIONS_PER_FRAME) {
+            Ok(()) => {}
+            Err(Chip8Error::ExitRequested) => return Ok(()),
+            Err(err) => return Err(err.to_string()),
+        }
+        next_frame += FRAME_DURATION;
+        sleep(next_frame.saturating_duration_since(Instant::now()));
+    }
+    return Ok(());
+}
@@ -0,0 +1,267 @@
+use std::cell::Cell;
+
+use chip8_core::{
+    Chip8CPU, Chip8Input, Screen, CPU, SCREEN_BUFFER_SIZE_FULL, SCREEN_HEIGHT, SCREEN_WIDTH,
+};
+use wasm_bindgen::prelude::*;
+
+pub const FRAME_RGBA_SIZE: usize = SCREEN_BUFFER_SIZE_FULL * 4;
+pub const DISPLAY_SIZE: usize = SCREEN_BUFFER_SIZE_FULL;
+
+// Keys are driven by the page's keydown / keyup events, one bit per key
+#[derive(Default)]
+pub struct KeypadState {
+    pressed: Cell<u16>,
+    released: Cell<Option<u8>>,
+}
+
+impl KeypadState {
+    pub fn key_down(&self, key: u8) {
+        self.pressed.set(self.pressed.get() | 1 << (key & 0xF));
+    }
+
+    pub fn key_up(&self, key: u8) {
+        let mask = 1 << (key & 0xF);
+        if self.pressed.get() & mask != 0 {
+            self.pressed.set(self.pressed.get() & !mask);
+            self.released.set(Some(key & 0xF));
+        }
+    }
+}
+
+impl Chip8Input for KeypadState {
+    // Lowest pressed key wins when several are held
+    fn get_key(&self) -> Option<u8> {
+        let pressed = self.pressed.get();
+        if pressed == 0 {
+            return None;
+        }
+        return Some(pressed.trailing_zeros() as u8);
+    }
+
+    fn was_key_released(&self) -> Option<u8> {
+        return self.released.take();
+    }
+
+    fn is_key_held(&self, key: u8) -> bool {
+        return key <= 0xF && self.pressed.get() & 1 << key != 0;
+    }
+}
+
+// The CPU borrows its screen and input, so both are leaked to give them the 'static lifetime
+// wasm-bindgen needs. Pages create one emulator and keep it, so this costs a few hundred bytes once.
+#[wasm_bindgen]
+pub struct Chip8VM {
+    cpu: CPU<'static, Screen, KeypadState>,
+    screen: &'static Screen,
+    keypad: &'static KeypadState,
+}
+
+impl Default for Chip8VM {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[wasm_bindgen]
+impl Chip8VM {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Chip8VM {
+        let screen: &'static Screen = Box::leak(Box::default());
+        let keypad: &'static KeypadState = Box::leak(Box::default());
+        return Chip8VM {
+            cpu: CPU::new(screen, keypad),
+            screen,
+            keypad,
+        };
+    }
+
+    pub fn width() -> u32 {
+        return SCREEN_WIDTH as u32;
+    }
+
+    pub fn height() -> u32 {
+        return SCREEN_HEIGHT as u32;
+    }
+
+    // Resets the CPU and screen before loading so a page can swap ROMs on the same instance
+    pub fn load_rom(&mut self, data: &[u8]) -> Result<(), String> {
+        self.cpu.reset();
+        return self.cpu.load_program(data).map_err(|err| err.to_string());
+    }
+
+    // Runs a single instruction without touching the timers
+    pub fn step(&mut self) -> Result<(), String> {
+        self.cpu.step().map_err(|err| err.to_string())?;
+        return Ok(());
+    }
+
+    // Runs one 60Hz frame, call it from requestAnimationFrame
+    pub fn step_frame(&mut self, cycles: u32) -> Result<(), String> {
+        return self
+            .cpu
+            .step_frame(cycles as usize)
+            .map_err(|err| err.to_string());
+    }
+
+    pub fn tick_timers(&mut self) {
+        self.cpu.tick_timers();
+    }
+
+    pub fn set_key(&self, key: u8, pressed: bool) {
+        if pressed {
+            self.keypad.key_down(key);
+        } else {
+            self.keypad.key_up(key);
+        }
+    }
+
+    pub fn get_delay_timer(&self) -> u8 {
+        return self.cpu.delay_timer();
+    }
+
+    // The page should play its tone while this is true
+    pub fn is_sound_playing(&self) -> bool {
+        return self.cpu.sound_timer() > 0;
+    }
+
+    // One byte per pixel, row by row, 1 if lit
+    pub fn get_display(&self) -> Vec<u8> {
+        let frame = self.screen.frame();
+        self.screen.mark_drawn();
+        return frame
+            .pixels
+            .iter()
+            .map(|pixel| u8::from(*pixel != 0))
+            .collect();
+    }
+
+    // True if the screen changed since the last get_display or frame_rgba call
+    pub fn is_pending_draw(&self) -> bool {
+        return self.screen.is_pending_draw();
+    }
+
+    // Writes the screen as 64x32 RGBA pixels in the screen's palette, ready for an ImageData
+    pub fn frame_rgba(&self, out: &mut [u8]) {
+        let frame = self.screen.frame();
+        for (index, pixel) in out.chunks_exact_mut(4).take(frame.pixels.len()).enumerate() {
+            let color = frame.palette.color(frame.pixels[index]);
+            pixel.copy_from_slice(&[color.r, color.g, color.b, 0xFF]);
+        }
+        self.screen.mark_drawn();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chip8_core::{convert_opcodes_into_u8, OpCodes};
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    use super::*;
+
+    fn lit_pixels(chip8: &Chip8VM) -> Vec<usize> {
+        let mut frame = vec![0; FRAME_RGBA_SIZE];
+        chip8.frame_rgba(&mut frame);
+        return frame
+            .chunks_exact(4)
+            .enumerate()
+            .filter(|(_, pixel)| pixel[..3] != [0, 0, 0])
+            .map(|(index, _)| index)
+            .collect();
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn load_step_and_display() {
+        let mut chip8 = Chip8VM::new();
+        let rom = convert_opcodes_into_u8(&[
+            OpCodes::_ANNN { nnn: 0x050 },
+            OpCodes::_6XNN { x: 0, nn: 2 },
+            OpCodes::_DXYN { x: 0, y: 0, n: 1 },
+            OpCodes::_6XNN { x: 1, nn: 30 },
+            OpCodes::_FX15 { x: 1 },
+            OpCodes::_FX18 { x: 1 },
+        ]);
+        chip8.load_rom(&rom).unwrap();
+        for _ in 0..3 {
+            chip8.step().unwrap();
+        }
+        assert!(chip8.is_pending_draw());
+        // The first row of the "0" glyph is 0xF0, drawn at (2, 2)
+        let display = chip8.get_display();
+        assert_eq!(display.len(), DISPLAY_SIZE);
+        let lit = (0..DISPLAY_SIZE)
+            .filter(|index| display[*index] == 1)
+            .collect::<Vec<_>>();
+        assert_eq!(lit, vec![130, 131, 132, 133]);
+        assert!(display.iter().all(|pixel| *pixel <= 1));
+        assert!(!chip8.is_pending_draw());
+        assert_eq!(lit_pixels(&chip8), lit);
+
+        // Both timers are set to 30 and the frame ends with one tick
+        chip8.step_frame(3).unwrap();
+        assert_eq!(chip8.get_delay_timer(), 29);
+        assert!(chip8.is_sound_playing());
+        chip8.tick_timers();
+        assert_eq!(chip8.get_delay_timer(), 28);
+
+        // Loading again starts over with a blank screen and silent timers
+        chip8.load_rom(&rom).unwrap();
+        assert!(chip8.get_display().iter().all(|pixel| *pixel == 0));
+        assert!(!chip8.is_sound_playing());
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn step_reports_errors() {
+        let mut chip8 = Chip8VM::new();
+        chip8.load_rom(&[0x5A, 0xB1]).unwrap();
+        assert_eq!(chip8.step(), Err("Invalid opcode: 0x5AB1".to_string()));
+        assert_eq!(
+            chip8.step_frame(10),
+            Err("Invalid opcode: 0x5AB1".to_string())
+        );
+        assert!(chip8.load_rom(&vec![0; 4096]).is_err());
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn set_key_drives_the_keypad() {
+        let mut chip8 = Chip8VM::new();
+        // Wait for a key into V0, then store it at 0x300
+        let rom = convert_opcodes_into_u8(&[
+            OpCodes::_FX0A { x: 0 },
+            OpCodes::_ANNN { nnn: 0x300 },
+            OpCodes::_FX55 { x: 0 },
+        ]);
+        chip8.load_rom(&rom).unwrap();
+        chip8.step_frame(5).unwrap();
+        chip8.set_key(0x7, true);
+        chip8.step_frame(5).unwrap();
+        assert_eq!(chip8.keypad.get_key(), Some(0x7));
+        chip8.set_key(0x7, false);
+        chip8.step_frame(3).unwrap();
+        assert_eq!(chip8.keypad.get_key(), None);
+        assert_eq!(chip8.cpu.memory_snapshot(0x300, 1), Ok(vec![0x7]));
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn keypad_reports_presses_and_releases() {
+        let keypad = KeypadState::default();
+        keypad.key_down(0xA);
+        keypad.key_down(0x3);
+        assert_eq!(keypad.get_key(), Some(0x3));
+        assert!(keypad.is_key_held(0xA) && keypad.is_key_held(0x3));
+        assert!(!keypad.is_key_held(0x5) && !keypad.is_key_held(0x13));
+        assert_eq!(keypad.was_key_released(), None);
+        keypad.key_up(0x3);
+        assert_eq!(keypad.get_key(), Some(0xA));
+        assert_eq!(keypad.was_key_released(), Some(0x3));
+        assert_eq!(keypad.was_key_released(), None);
+        // Releasing a key that isn't held isn't a release
+        keypad.key_up(0x5);
+        assert_eq!(keypad.was_key_released(), None);
+    }
+}